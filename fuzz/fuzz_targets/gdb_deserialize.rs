@@ -0,0 +1,16 @@
+#![no_main]
+
+use gdbstub::arch::Registers;
+use gdbstub_mos_arch::MosRegs;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let mut regs: MosRegs = MosRegs::default();
+    if regs.gdb_deserialize(data).is_ok() {
+        let mut bytes = Vec::new();
+        regs.gdb_serialize(|b| bytes.push(b.unwrap()));
+        let mut again: MosRegs = MosRegs::default();
+        assert_eq!(again.gdb_deserialize(&bytes), Ok(()));
+        assert_eq!(again, regs);
+    }
+});
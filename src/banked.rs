@@ -0,0 +1,241 @@
+use gdbstub::arch::{Arch, Registers};
+
+use crate::memory_map::{MemoryKind, MemoryRegion};
+use crate::{
+    reg_info, xml, MosBreakpointKind, MosRegId, MosRegs, RegAccess, RegInfo, DEFAULT_RC_COUNT,
+};
+
+/// Implements `Arch` for a 6502 behind a bank-switching memory controller,
+/// such as the C64 PLA, NES mappers, the Atari XE's PORTB or the Commander
+/// X16's RAM and ROM bank registers, with `RC` llvm-mos imaginary registers.
+///
+/// Addresses are encoded with [`BankedAddress`], so that GDB can reach every
+/// bank and not just the ones currently mapped in. Describe the banks to the
+/// debugger with a memory map built from [`BankWindow`]s.
+pub enum MosBankedArch<const RC: usize = DEFAULT_RC_COUNT> {}
+
+/// A decoded [`MosBankedArch`] address.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum BankedAddress {
+    /// Address in the CPU's 64K view, as mapped at the time of the access.
+    /// Encoded as-is.
+    Cpu(u16),
+    /// CPU address `addr` with bank `bank` mapped in, whether or not it is
+    /// at the time of the access. Encoded with [`BankedAddress::BANK_TAG`]
+    /// set and the bank in bits 16–30.
+    Bank { bank: u16, addr: u16 },
+}
+
+impl BankedAddress {
+    /// Bit marking an encoded address as banked.
+    pub const BANK_TAG: u32 = 0x8000_0000;
+
+    /// Highest bank number that can be encoded.
+    pub const MAX_BANK: u16 = 0x7fff;
+
+    /// Splits a GDB address into bank and CPU address. Returns `None` for
+    /// addresses that are neither a 16-bit CPU address nor tagged as banked.
+    pub fn decode(addr: u32) -> Option<Self> {
+        if addr & Self::BANK_TAG != 0 {
+            Some(BankedAddress::Bank {
+                bank: (addr >> 16) as u16 & Self::MAX_BANK,
+                addr: addr as u16,
+            })
+        } else {
+            u16::try_from(addr).ok().map(BankedAddress::Cpu)
+        }
+    }
+
+    /// Joins bank and CPU address into a GDB address. Bank numbers above
+    /// [`BankedAddress::MAX_BANK`] are truncated.
+    pub const fn encode(self) -> u32 {
+        match self {
+            BankedAddress::Cpu(addr) => addr as u32,
+            BankedAddress::Bank { bank, addr } => {
+                Self::BANK_TAG | ((bank & Self::MAX_BANK) as u32) << 16 | addr as u32
+            }
+        }
+    }
+
+    /// The address as seen by the CPU.
+    pub fn cpu_address(self) -> u16 {
+        match self {
+            BankedAddress::Cpu(addr) | BankedAddress::Bank { addr, .. } => addr,
+        }
+    }
+
+    /// The bank the address refers to, if any.
+    pub fn bank(self) -> Option<u16> {
+        match self {
+            BankedAddress::Cpu(_) => None,
+            BankedAddress::Bank { bank, .. } => Some(bank),
+        }
+    }
+
+    /// The breakpoint that stops at this address: a
+    /// [`Banked`](MosBreakpointKind::Banked) one for banked addresses, and
+    /// `kind` otherwise.
+    pub fn breakpoint_kind(self, kind: MosBreakpointKind) -> MosBreakpointKind {
+        match self {
+            BankedAddress::Cpu(_) => kind,
+            BankedAddress::Bank { bank, .. } => MosBreakpointKind::Banked(bank),
+        }
+    }
+}
+
+/// A range of CPU addresses that one of several banks is mapped into, such
+/// as the X16's banked RAM at $A000–$BFFF.
+///
+/// The memory map of a banked machine lists the CPU's view followed by the
+/// regions of each window:
+///
+/// ```
+/// # use gdbstub_mos_arch::memory_map::{memory_map_xml, X16, X16_RAM_BANKS, X16_ROM_BANKS};
+/// let regions = X16
+///     .iter()
+///     .copied()
+///     .chain(X16_RAM_BANKS.regions())
+///     .chain(X16_ROM_BANKS.regions());
+/// # let (offset, mut buf) = (0, [0; 64]);
+/// let len = memory_map_xml(regions, offset, &mut buf);
+/// ```
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct BankWindow {
+    pub kind: MemoryKind,
+    /// First CPU address of the window.
+    pub start: u16,
+    pub length: u16,
+    /// Number of the first bank.
+    pub first_bank: u16,
+    /// Number of banks that can be mapped into the window.
+    pub banks: u16,
+}
+
+impl BankWindow {
+    /// Returns the memory map region of each bank, at its [`BankedAddress`]
+    /// encoding.
+    pub fn regions(&self) -> impl Iterator<Item = MemoryRegion> {
+        let window = *self;
+        (0..window.banks).map(move |i| MemoryRegion {
+            kind: window.kind,
+            start: BankedAddress::Bank {
+                bank: window.first_bank.wrapping_add(i),
+                addr: window.start,
+            }
+            .encode(),
+            length: window.length as u32,
+        })
+    }
+
+    /// Returns the banked address of `addr` in `bank`, or `None` if `addr`
+    /// is outside the window or the window has no such bank.
+    pub fn address(&self, bank: u16, addr: u16) -> Option<BankedAddress> {
+        let in_window = addr.wrapping_sub(self.start) < self.length;
+        let has_bank = bank.wrapping_sub(self.first_bank) < self.banks;
+        (in_window && has_bank).then_some(BankedAddress::Bank { bank, addr })
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct MosBankedRegs<const RC: usize = DEFAULT_RC_COUNT> {
+    pub base: MosRegs<RC>,
+    /// Bank mapped in at the PC, as the target defines it.
+    pub bank: u16,
+}
+
+impl<const RC: usize> Default for MosBankedRegs<RC> {
+    fn default() -> Self {
+        MosBankedRegs {
+            base: MosRegs::default(),
+            bank: 0,
+        }
+    }
+}
+
+impl<const RC: usize> RegAccess for MosBankedRegs<RC> {
+    type RegId = MosBankedRegId<RC>;
+    type Value = u16;
+
+    fn get_reg(&self, reg: &MosBankedRegId<RC>) -> Option<u16> {
+        match reg {
+            MosBankedRegId::Mos(reg) => self.base.get_reg(reg),
+            MosBankedRegId::Bank => Some(self.bank),
+        }
+    }
+
+    fn set_reg(&mut self, reg: &MosBankedRegId<RC>, value: u16) -> Option<()> {
+        match reg {
+            MosBankedRegId::Mos(reg) => return self.base.set_reg(reg, value),
+            MosBankedRegId::Bank => self.bank = value,
+        }
+        Some(())
+    }
+}
+
+impl<const RC: usize> Registers for MosBankedRegs<RC> {
+    type ProgramCounter = u32;
+
+    fn pc(&self) -> Self::ProgramCounter {
+        self.base.pc as u32
+    }
+
+    fn gdb_serialize(&self, write_byte: impl FnMut(Option<u8>)) {
+        reg_info::serialize(self, write_byte)
+    }
+
+    fn gdb_deserialize(&mut self, bytes: &[u8]) -> Result<(), ()> {
+        reg_info::deserialize(self, bytes)
+    }
+}
+
+/// Register ids of [`MosBankedArch`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MosBankedRegId<const RC: usize = DEFAULT_RC_COUNT> {
+    /// A register of the 6502.
+    Mos(MosRegId<RC>),
+    /// Bank mapped in at the PC.
+    Bank,
+}
+
+impl<const RC: usize> MosBankedRegId<RC> {
+    /// Registers following the 6502 register set.
+    const TAIL: [(Self, RegInfo); 1] = [(MosBankedRegId::Bank, RegInfo::new("BANK", 16))];
+
+    /// Number of registers in the target description.
+    pub const COUNT: usize = MosRegId::<RC>::COUNT + Self::TAIL.len();
+
+    /// Size of a complete `g` packet payload in bytes.
+    pub const PACKET_SIZE: usize =
+        MosRegId::<RC>::PACKET_SIZE + reg_info::packed_size(&Self::TAIL, Self::TAIL.len());
+
+    /// Returns the register with register number `regnum` along with its
+    /// description. The 6502 registers keep their numbers and offsets.
+    pub const fn lookup(regnum: usize) -> Option<(Self, RegInfo)> {
+        if let Some((reg, info)) = MosRegId::<RC>::lookup(regnum) {
+            Some((MosBankedRegId::Mos(reg), info))
+        } else if regnum < Self::COUNT {
+            let first = MosRegId::<RC>::COUNT;
+            let base = MosRegId::<RC>::PACKET_SIZE;
+            Some(reg_info::place(&Self::TAIL, regnum - first, first, base))
+        } else {
+            None
+        }
+    }
+}
+
+reg_info::reg_table!(MosBankedRegId);
+
+impl<const RC: usize> MosBankedArch<RC> {
+    xml::target_xml!(banked_target_xml());
+}
+
+impl<const RC: usize> Arch for MosBankedArch<RC> {
+    type Usize = u32;
+    type Registers = MosBankedRegs<RC>;
+    type RegId = MosBankedRegId<RC>;
+    type BreakpointKind = MosBreakpointKind;
+
+    fn target_description_xml() -> Option<&'static str> {
+        Some(Self::TARGET_XML_STR)
+    }
+}
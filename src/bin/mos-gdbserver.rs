@@ -0,0 +1,490 @@
+//! A gdbserver for 6502 programs, backed by the crate's NMOS 6502 emulator.
+//!
+//! Install with `cargo install gdbstub_mos_arch --features gdbserver`.
+
+use std::io::Write;
+use std::net::{TcpListener, TcpStream};
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::process::ExitCode;
+
+use gdbstub::common::Signal;
+use gdbstub::conn::ConnectionExt;
+use gdbstub::stub::run_blocking::{BlockingEventLoop, Event, WaitForStopReasonError};
+use gdbstub::stub::{DisconnectReason, GdbStub, SingleThreadStopReason};
+use gdbstub_mos_arch::emulator::{Cpu, Emulator, RunEvent};
+use gdbstub_mos_arch::loader::{
+    raw, Elf, ElfError, INes, INesError, IntelHex, O65Error, Prg, Relocation, SRecord, Segment,
+    Xex, XexEvent, O65,
+};
+use gdbstub_mos_arch::memory_map::{self, MemoryRegion};
+use gdbstub_mos_arch::{Bus, ImaginaryRegs, IRQ_VECTOR, NMI_VECTOR, RESET_VECTOR};
+
+const USAGE: &str = "\
+Usage: mos-gdbserver [OPTIONS] <PROGRAM>
+
+Loads PROGRAM into a 64K NMOS 6502 emulator and waits for GDB to connect with
+`target remote`. PROGRAM is an llvm-mos ELF executable, an iNES or NES 2.0
+ROM image, a Commodore .prg file, an Intel HEX (.hex, .ihx) or S-record
+(.s19, .s28, .s37, .srec, .mot) image, a relocatable .o65 file, an Atari
+.xex executable, or a raw binary.
+
+A .o65 file is relocated to the load address and GDB is told how far it
+moved, for its symbols. An iNES image's PRG-ROM is mapped read-only at $8000
+as at power-on; the mapper's bank switching isn't emulated. The INIT
+routines of a .xex file run while it loads, as under DOS, but without the
+Atari OS.
+
+Options:
+  --listen <HOST:PORT>  Address to listen on [default: 127.0.0.1:1234]
+  --load <ADDR>         Load address of a raw or .o65 PROGRAM
+                        [default: 0x0200]
+  --entry <ADDR>        Initial PC [default: the reset vector]
+  --reset <ADDR>        Set the reset vector. Unless PROGRAM covers the
+                        vector, defaults to its entry point: the ELF entry
+                        point, the SYS address of a .prg BASIC stub, the
+                        start address of a HEX or S-record image, the RUNAD
+                        address of a .xex file, or the load address
+  --irq <ADDR>          Set the IRQ/BRK vector
+  --nmi <ADDR>          Set the NMI vector
+  --rom <START-END>     Ignore CPU writes to START..=END. May be repeated
+  --machine <NAME>      Report the memory map of NAME to GDB, so that it
+                        uses hardware breakpoints in ROM: c64, nes,
+                        atari800xl, apple2, vic20 or x16
+  --exit-port <ADDR>    Exit with the byte written to ADDR as status
+  --putc-port <ADDR>    Print bytes written to ADDR to stdout
+  -h, --help            Print this help
+
+Addresses are decimal, or hexadecimal with a `0x` or `$` prefix.";
+
+struct Options {
+    program: String,
+    listen: String,
+    load: u16,
+    entry: Option<u16>,
+    reset: Option<u16>,
+    irq: Option<u16>,
+    nmi: Option<u16>,
+    rom: Vec<RangeInclusive<u16>>,
+    machine: Option<&'static [MemoryRegion]>,
+    exit_port: Option<u16>,
+    putc_port: Option<u16>,
+}
+
+fn parse_addr(arg: &str) -> Result<u16, String> {
+    let parsed = if let Some(hex) = arg.strip_prefix("0x").or_else(|| arg.strip_prefix('$')) {
+        u16::from_str_radix(hex, 16)
+    } else {
+        arg.parse()
+    };
+    parsed.map_err(|_| format!("invalid address `{}`", arg))
+}
+
+fn parse_range(arg: &str) -> Result<RangeInclusive<u16>, String> {
+    let (start, end) = arg
+        .split_once('-')
+        .ok_or_else(|| format!("invalid range `{}`, expected START-END", arg))?;
+    let (start, end) = (parse_addr(start)?, parse_addr(end)?);
+    if start > end {
+        return Err(format!("invalid range `{}`, START is after END", arg));
+    }
+    Ok(start..=end)
+}
+
+fn parse_machine(arg: &str) -> Result<&'static [MemoryRegion], String> {
+    let regions = match arg {
+        "c64" => memory_map::C64,
+        "nes" => memory_map::NES,
+        "atari800xl" => memory_map::ATARI_800XL,
+        "apple2" => memory_map::APPLE_II,
+        "vic20" => memory_map::VIC20,
+        "x16" => memory_map::X16,
+        _ => return Err(format!("unknown machine `{}`", arg)),
+    };
+    Ok(regions)
+}
+
+/// Parses the command line. Returns `Ok(None)` if help was requested.
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<Options>, String> {
+    let mut program = None;
+    let mut opts = Options {
+        program: String::new(),
+        listen: "127.0.0.1:1234".into(),
+        load: 0x0200,
+        entry: None,
+        reset: None,
+        irq: None,
+        nmi: None,
+        rom: Vec::new(),
+        machine: None,
+        exit_port: None,
+        putc_port: None,
+    };
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("`{}` needs a value", arg))
+        };
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--listen" => opts.listen = value()?,
+            "--load" => opts.load = parse_addr(&value()?)?,
+            "--entry" => opts.entry = Some(parse_addr(&value()?)?),
+            "--reset" => opts.reset = Some(parse_addr(&value()?)?),
+            "--irq" => opts.irq = Some(parse_addr(&value()?)?),
+            "--nmi" => opts.nmi = Some(parse_addr(&value()?)?),
+            "--rom" => opts.rom.push(parse_range(&value()?)?),
+            "--machine" => opts.machine = Some(parse_machine(&value()?)?),
+            "--exit-port" => opts.exit_port = Some(parse_addr(&value()?)?),
+            "--putc-port" => opts.putc_port = Some(parse_addr(&value()?)?),
+            _ if arg.starts_with('-') => return Err(format!("unknown option `{}`", arg)),
+            _ if program.is_some() => return Err(format!("unexpected argument `{}`", arg)),
+            _ => program = Some(arg),
+        }
+    }
+    opts.program = program.ok_or("missing PROGRAM")?;
+    Ok(Some(opts))
+}
+
+/// 64K of RAM with optional ROM ranges and I/O ports.
+struct Memory {
+    ram: Box<[u8; 0x10000]>,
+    rom: Vec<RangeInclusive<u16>>,
+    exit_port: Option<u16>,
+    putc_port: Option<u16>,
+    exit_status: Option<u8>,
+}
+
+impl Bus for Memory {
+    fn read(&mut self, addr: u16) -> u8 {
+        self.ram[addr as usize]
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        if Some(addr) == self.exit_port {
+            self.exit_status = Some(value);
+        } else if Some(addr) == self.putc_port {
+            let mut stdout = std::io::stdout();
+            let _ = stdout.write_all(&[value]).and_then(|_| stdout.flush());
+        } else if !self.rom.iter().any(|rom| rom.contains(&addr)) {
+            self.ram[addr as usize] = value;
+        }
+    }
+
+    /// The debugger can write anywhere, including ROM, without triggering
+    /// the I/O ports.
+    fn poke(&mut self, addr: u16, value: u8) {
+        self.ram[addr as usize] = value;
+    }
+}
+
+enum EventLoop {}
+
+impl BlockingEventLoop for EventLoop {
+    type Target = Emulator<Memory>;
+    type Connection = TcpStream;
+    type StopReason = SingleThreadStopReason<u16>;
+
+    fn wait_for_stop_reason(
+        target: &mut Self::Target,
+        conn: &mut Self::Connection,
+    ) -> Result<
+        Event<Self::StopReason>,
+        WaitForStopReasonError<std::convert::Infallible, std::io::Error>,
+    > {
+        let event = target.run_with(
+            || conn.peek().map(|b| b.is_some()).unwrap_or(true),
+            |mem| mem.exit_status.take().map(SingleThreadStopReason::Exited),
+        );
+        match event {
+            RunEvent::IncomingData => {
+                let byte = conn.read().map_err(WaitForStopReasonError::Connection)?;
+                Ok(Event::IncomingData(byte))
+            }
+            RunEvent::Stopped(reason) => Ok(Event::TargetStopped(reason)),
+        }
+    }
+
+    fn on_interrupt(
+        _target: &mut Self::Target,
+    ) -> Result<Option<Self::StopReason>, std::convert::Infallible> {
+        Ok(Some(SingleThreadStopReason::Signal(Signal::SIGINT)))
+    }
+}
+
+/// A program loaded into memory.
+struct Loaded {
+    entry: u16,
+    covers_reset: bool,
+    imaginary_regs: Option<ImaginaryRegs>,
+    relocation: Option<Relocation>,
+    /// Addresses the program can't write to, besides the `--rom` ranges.
+    rom: Option<RangeInclusive<u16>>,
+}
+
+/// Returns whether `segment` contains the high byte of the reset vector.
+fn covers_reset(segment: Segment) -> bool {
+    (segment.address..segment.end()).contains(&(RESET_VECTOR as u32 + 1))
+}
+
+/// Instructions an XEX INIT routine may execute before it's abandoned.
+const INIT_STEPS: usize = 10_000_000;
+
+/// Calls the XEX INIT routine at `addr` with a `JSR`, as DOS does, and runs
+/// it until it returns. Returns `false` if it jams or doesn't return within
+/// [`INIT_STEPS`] instructions.
+///
+/// The routine runs on `mem` like the program itself, with its ROM ranges
+/// and I/O ports. The return address is pushed through them as well, and
+/// the two stack bytes it overwrote are restored afterwards.
+fn call_init(mem: &mut Memory, addr: u16) -> bool {
+    // The routine returns to $0000, from a return address at the top of the
+    // stack.
+    let saved = [mem.read(0x1fe), mem.read(0x1ff)];
+    mem.write(0x1ff, 0xff);
+    mem.write(0x1fe, 0xff);
+    let mut cpu: Cpu = Cpu::new();
+    cpu.regs.s = 0xfd;
+    cpu.regs.pc = addr;
+    let mut returned = false;
+    for _ in 0..INIT_STEPS {
+        if cpu.regs.pc == 0 && cpu.regs.s == 0xff {
+            returned = true;
+            break;
+        }
+        cpu.step(mem);
+        if cpu.jammed {
+            break;
+        }
+    }
+    mem.poke(0x1fe, saved[0]);
+    mem.poke(0x1ff, saved[1]);
+    returned
+}
+
+/// Loads the program into `mem`, choosing the format by its contents or
+/// extension.
+fn load(opts: &Options, program: &[u8], mem: &mut Memory) -> Result<Loaded, String> {
+    let error = |e: &dyn std::fmt::Display| format!("can't load `{}`: {}", opts.program, e);
+    match Elf::parse(program) {
+        Err(ElfError::NotElf) => {}
+        elf => {
+            let elf = elf.map_err(|e| error(&e))?;
+            let entry = elf.load(&mut mem.ram).map_err(|e| error(&e))?;
+            return Ok(Loaded {
+                entry,
+                covers_reset: elf.segments().flatten().any(covers_reset),
+                imaginary_regs: elf.rc_base().map(ImaginaryRegs::new),
+                relocation: None,
+                rom: None,
+            });
+        }
+    }
+    match INes::parse(program) {
+        Err(INesError::NotINes) => {}
+        rom => {
+            let rom = rom.map_err(|e| error(&e))?;
+            rom.load(&mut mem.ram);
+            return Ok(Loaded {
+                entry: rom.reset_vector(),
+                covers_reset: true,
+                imaginary_regs: None,
+                relocation: None,
+                rom: Some(0x8000..=0xffff),
+            });
+        }
+    }
+
+    let extension = Path::new(&opts.program)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let (start, records) = match extension.as_str() {
+        "prg" => {
+            let prg = Prg::parse(program).map_err(|e| error(&e))?;
+            prg.load(&mut mem.ram);
+            return Ok(Loaded {
+                entry: prg.entry(),
+                covers_reset: covers_reset(prg.segment()),
+                imaginary_regs: None,
+                relocation: None,
+                rom: None,
+            });
+        }
+        "o65" => {
+            let o65 = O65::parse(program).map_err(|e| error(&e))?;
+            let bases = o65.bases_at(opts.load);
+            o65.load(&mut mem.ram, &bases, |_| None)
+                .map_err(|e| match e {
+                    O65Error::Unresolved(index) => {
+                        let name = o65.undefined().nth(index as usize).unwrap_or_default();
+                        error(&format!(
+                            "undefined reference to `{}`",
+                            String::from_utf8_lossy(name)
+                        ))
+                    }
+                    e => error(&e),
+                })?;
+            return Ok(Loaded {
+                entry: bases.text,
+                covers_reset: false,
+                imaginary_regs: None,
+                relocation: Some(o65.relocation(&bases)),
+                rom: None,
+            });
+        }
+        "xex" => {
+            let xex = Xex::parse(program).map_err(|e| error(&e))?;
+            let mut covers = false;
+            for event in xex.events() {
+                match event {
+                    XexEvent::Segment(segment) => {
+                        segment.load(&mut mem.ram);
+                        covers |= covers_reset(segment);
+                    }
+                    XexEvent::Init(addr) => {
+                        if !call_init(mem, addr) {
+                            eprintln!("warning: INIT routine at ${:04X} didn't return", addr);
+                        }
+                    }
+                    XexEvent::Run(_) => {}
+                }
+            }
+            // Without RUNAD, DOS starts at the first segment.
+            let first = xex.segments().next().map(|segment| segment.address as u16);
+            return Ok(Loaded {
+                entry: xex
+                    .run_address()
+                    .or(first)
+                    .ok_or_else(|| error(&"no data"))?,
+                covers_reset: covers,
+                imaginary_regs: None,
+                relocation: None,
+                rom: None,
+            });
+        }
+        "hex" | "ihx" => {
+            let hex = IntelHex::parse(program).map_err(|e| error(&e))?;
+            hex.load(&mut mem.ram);
+            (hex.start_address(), hex.records().collect::<Vec<_>>())
+        }
+        "s19" | "s28" | "s37" | "srec" | "mot" => {
+            let srec = SRecord::parse(program).map_err(|e| error(&e))?;
+            srec.load(&mut mem.ram);
+            (srec.start_address(), srec.records().collect())
+        }
+        _ => {
+            let segment = raw(opts.load, program).map_err(|_| {
+                format!(
+                    "`{}` is {} bytes and doesn't fit at ${:04X}",
+                    opts.program,
+                    program.len(),
+                    opts.load
+                )
+            })?;
+            segment.load(&mut mem.ram);
+            return Ok(Loaded {
+                entry: opts.load,
+                covers_reset: covers_reset(segment),
+                imaginary_regs: None,
+                relocation: None,
+                rom: None,
+            });
+        }
+    };
+    // Without a start address, start at the first record.
+    let entry = start
+        .or(records.first().map(|record| record.address))
+        .ok_or_else(|| error(&"no data"))?;
+    Ok(Loaded {
+        entry,
+        covers_reset: records.iter().any(|record| covers_reset(record.segment())),
+        imaginary_regs: None,
+        relocation: None,
+        rom: None,
+    })
+}
+
+fn setup(opts: &Options) -> Result<Emulator<Memory>, String> {
+    let program = std::fs::read(&opts.program)
+        .map_err(|e| format!("can't read `{}`: {}", opts.program, e))?;
+    let mut mem = Memory {
+        ram: Box::new([0; 0x10000]),
+        rom: opts.rom.clone(),
+        exit_port: opts.exit_port,
+        putc_port: opts.putc_port,
+        exit_status: None,
+    };
+    let loaded = load(opts, &program, &mut mem)?;
+    let reset = opts
+        .reset
+        .or((!loaded.covers_reset).then_some(loaded.entry));
+    for (vector, addr) in [
+        (RESET_VECTOR, reset),
+        (IRQ_VECTOR, opts.irq),
+        (NMI_VECTOR, opts.nmi),
+    ] {
+        if let Some(addr) = addr {
+            let vector = vector as usize;
+            mem.ram[vector..vector + 2].copy_from_slice(&addr.to_le_bytes());
+        }
+    }
+
+    mem.rom.extend(loaded.rom);
+    let mut emu = Emulator::new(mem);
+    emu.imaginary_regs = loaded.imaginary_regs;
+    emu.relocation = loaded.relocation;
+    emu.memory_map = opts.machine;
+    emu.reset();
+    if let Some(entry) = opts.entry {
+        emu.cpu.regs.pc = entry;
+    }
+    Ok(emu)
+}
+
+fn serve(opts: &Options) -> Result<(), String> {
+    let mut emu = setup(opts)?;
+    let listener = TcpListener::bind(&opts.listen)
+        .map_err(|e| format!("can't listen on {}: {}", opts.listen, e))?;
+    eprintln!("Listening on {}", opts.listen);
+    let (stream, peer) = listener
+        .accept()
+        .map_err(|e| format!("accept failed: {}", e))?;
+    eprintln!("Debugging connection from {}", peer);
+
+    let gdb = GdbStub::new(stream);
+    match gdb.run_blocking::<EventLoop>(&mut emu) {
+        Ok(DisconnectReason::Disconnect) => eprintln!("GDB disconnected"),
+        Ok(DisconnectReason::TargetExited(status)) => {
+            eprintln!("Program exited with status {}", status)
+        }
+        Ok(DisconnectReason::TargetTerminated(signal)) => {
+            eprintln!("Program terminated with {}", signal)
+        }
+        Ok(DisconnectReason::Kill) => eprintln!("GDB sent a kill command"),
+        Err(e) => return Err(format!("gdbstub error: {}", e)),
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let opts = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(opts)) => opts,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("mos-gdbserver: {}\n\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    match serve(&opts) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("mos-gdbserver: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,68 @@
+/// The 16-bit address space of a 6502-family CPU.
+pub trait Bus {
+    /// Reads a byte on behalf of the CPU.
+    fn read(&mut self, addr: u16) -> u8;
+
+    /// Writes a byte on behalf of the CPU.
+    fn write(&mut self, addr: u16, value: u8);
+
+    /// Reads a byte on behalf of the debugger. Implementations with I/O
+    /// registers should override this to avoid read side effects.
+    fn peek(&mut self, addr: u16) -> u8 {
+        self.read(addr)
+    }
+
+    /// Writes a byte on behalf of the debugger. Implementations may override
+    /// this to allow patching ROM.
+    fn poke(&mut self, addr: u16, value: u8) {
+        self.write(addr, value)
+    }
+}
+
+/// 64K of RAM.
+impl Bus for [u8; 0x10000] {
+    fn read(&mut self, addr: u16) -> u8 {
+        self[addr as usize]
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        self[addr as usize] = value;
+    }
+}
+
+impl<B: Bus + ?Sized> Bus for &mut B {
+    fn read(&mut self, addr: u16) -> u8 {
+        (**self).read(addr)
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        (**self).write(addr, value)
+    }
+
+    fn peek(&mut self, addr: u16) -> u8 {
+        (**self).peek(addr)
+    }
+
+    fn poke(&mut self, addr: u16, value: u8) {
+        (**self).poke(addr, value)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<B: Bus + ?Sized> Bus for Box<B> {
+    fn read(&mut self, addr: u16) -> u8 {
+        (**self).read(addr)
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        (**self).write(addr, value)
+    }
+
+    fn peek(&mut self, addr: u16) -> u8 {
+        (**self).peek(addr)
+    }
+
+    fn poke(&mut self, addr: u16, value: u8) {
+        (**self).poke(addr, value)
+    }
+}
@@ -0,0 +1,88 @@
+//! Implementations of the gdbstub 0.6 traits, enabled by the `gdbstub_06`
+//! feature. They forward to the gdbstub 0.7 implementations, so both
+//! versions see the same registers and target descriptions.
+
+use core::num::NonZeroUsize;
+
+use gdbstub_06::arch::{Arch, BreakpointKind, RegId, Registers, SingleStepGdbBehavior};
+
+use crate::{
+    HuC6280Arch, HuC6280RegId, HuC6280Regs, MOSArch, Mos45GS02Arch, Mos45GS02RegId, Mos45GS02Regs,
+    Mos65C02Arch, MosBankedArch, MosBankedRegId, MosBankedRegs, MosBreakpointKind, MosRegId,
+    MosRegs, W65816Arch, W65816RegId, W65816Regs,
+};
+
+macro_rules! impl_registers {
+    ($regs:ident) => {
+        impl<const RC: usize> Registers for $regs<RC> {
+            type ProgramCounter = <Self as gdbstub::arch::Registers>::ProgramCounter;
+
+            fn pc(&self) -> Self::ProgramCounter {
+                gdbstub::arch::Registers::pc(self)
+            }
+
+            fn gdb_serialize(&self, write_byte: impl FnMut(Option<u8>)) {
+                gdbstub::arch::Registers::gdb_serialize(self, write_byte)
+            }
+
+            fn gdb_deserialize(&mut self, bytes: &[u8]) -> Result<(), ()> {
+                gdbstub::arch::Registers::gdb_deserialize(self, bytes)
+            }
+        }
+    };
+}
+
+macro_rules! impl_reg_id {
+    ($reg_id:ident) => {
+        impl<const RC: usize> RegId for $reg_id<RC> {
+            fn from_raw_id(id: usize) -> Option<(Self, Option<NonZeroUsize>)> {
+                <Self as gdbstub::arch::RegId>::from_raw_id(id)
+            }
+        }
+    };
+}
+
+macro_rules! impl_arch {
+    ($arch:ident) => {
+        impl<const RC: usize> Arch for $arch<RC> {
+            type Usize = <Self as gdbstub::arch::Arch>::Usize;
+            type Registers = <Self as gdbstub::arch::Arch>::Registers;
+            type RegId = <Self as gdbstub::arch::Arch>::RegId;
+            type BreakpointKind = MosBreakpointKind;
+
+            fn target_description_xml() -> Option<&'static str> {
+                <Self as gdbstub::arch::Arch>::target_description_xml()
+            }
+
+            #[inline(always)]
+            fn single_step_gdb_behavior() -> SingleStepGdbBehavior {
+                SingleStepGdbBehavior::Optional
+            }
+        }
+    };
+}
+
+impl_registers!(MosRegs);
+impl_registers!(W65816Regs);
+impl_registers!(Mos45GS02Regs);
+impl_registers!(HuC6280Regs);
+impl_registers!(MosBankedRegs);
+
+impl_reg_id!(MosRegId);
+impl_reg_id!(W65816RegId);
+impl_reg_id!(Mos45GS02RegId);
+impl_reg_id!(HuC6280RegId);
+impl_reg_id!(MosBankedRegId);
+
+impl_arch!(MOSArch);
+impl_arch!(Mos65C02Arch);
+impl_arch!(W65816Arch);
+impl_arch!(Mos45GS02Arch);
+impl_arch!(HuC6280Arch);
+impl_arch!(MosBankedArch);
+
+impl BreakpointKind for MosBreakpointKind {
+    fn from_usize(kind: usize) -> Option<Self> {
+        <Self as gdbstub::arch::BreakpointKind>::from_usize(kind)
+    }
+}
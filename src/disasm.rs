@@ -0,0 +1,280 @@
+//! Instruction decoding for trace logs and monitor commands.
+//!
+//! ```
+//! use gdbstub_mos_arch::{disasm, InstructionSet};
+//!
+//! let code = [0x20, 0xd2, 0xff];
+//! let insn = disasm::decode_bytes(InstructionSet::Nmos6502, 0x0801, &code).unwrap();
+//! assert_eq!(insn.to_string(), "JSR $FFD2");
+//! let symbols = |addr| (addr == 0xffd2).then_some("CHROUT");
+//! assert_eq!(insn.display_with(symbols).to_string(), "JSR CHROUT");
+//! ```
+
+use core::fmt;
+use core::marker::PhantomData;
+
+pub use crate::opcode::AddressingMode;
+use crate::{opcode, InstructionSet};
+
+#[rustfmt::skip]
+const NMOS_MNEMONICS: [&str; 256] = [
+    "BRK", "ORA", "JAM", "SLO", "NOP", "ORA", "ASL", "SLO", "PHP", "ORA", "ASL", "ANC", "NOP", "ORA", "ASL", "SLO",
+    "BPL", "ORA", "JAM", "SLO", "NOP", "ORA", "ASL", "SLO", "CLC", "ORA", "NOP", "SLO", "NOP", "ORA", "ASL", "SLO",
+    "JSR", "AND", "JAM", "RLA", "BIT", "AND", "ROL", "RLA", "PLP", "AND", "ROL", "ANC", "BIT", "AND", "ROL", "RLA",
+    "BMI", "AND", "JAM", "RLA", "NOP", "AND", "ROL", "RLA", "SEC", "AND", "NOP", "RLA", "NOP", "AND", "ROL", "RLA",
+    "RTI", "EOR", "JAM", "SRE", "NOP", "EOR", "LSR", "SRE", "PHA", "EOR", "LSR", "ALR", "JMP", "EOR", "LSR", "SRE",
+    "BVC", "EOR", "JAM", "SRE", "NOP", "EOR", "LSR", "SRE", "CLI", "EOR", "NOP", "SRE", "NOP", "EOR", "LSR", "SRE",
+    "RTS", "ADC", "JAM", "RRA", "NOP", "ADC", "ROR", "RRA", "PLA", "ADC", "ROR", "ARR", "JMP", "ADC", "ROR", "RRA",
+    "BVS", "ADC", "JAM", "RRA", "NOP", "ADC", "ROR", "RRA", "SEI", "ADC", "NOP", "RRA", "NOP", "ADC", "ROR", "RRA",
+    "NOP", "STA", "NOP", "SAX", "STY", "STA", "STX", "SAX", "DEY", "NOP", "TXA", "ANE", "STY", "STA", "STX", "SAX",
+    "BCC", "STA", "JAM", "SHA", "STY", "STA", "STX", "SAX", "TYA", "STA", "TXS", "TAS", "SHY", "STA", "SHX", "SHA",
+    "LDY", "LDA", "LDX", "LAX", "LDY", "LDA", "LDX", "LAX", "TAY", "LDA", "TAX", "LXA", "LDY", "LDA", "LDX", "LAX",
+    "BCS", "LDA", "JAM", "LAX", "LDY", "LDA", "LDX", "LAX", "CLV", "LDA", "TSX", "LAS", "LDY", "LDA", "LDX", "LAX",
+    "CPY", "CMP", "NOP", "DCP", "CPY", "CMP", "DEC", "DCP", "INY", "CMP", "DEX", "SBX", "CPY", "CMP", "DEC", "DCP",
+    "BNE", "CMP", "JAM", "DCP", "NOP", "CMP", "DEC", "DCP", "CLD", "CMP", "NOP", "DCP", "NOP", "CMP", "DEC", "DCP",
+    "CPX", "SBC", "NOP", "ISC", "CPX", "SBC", "INC", "ISC", "INX", "SBC", "NOP", "SBC", "CPX", "SBC", "INC", "ISC",
+    "BEQ", "SBC", "JAM", "ISC", "NOP", "SBC", "INC", "ISC", "SED", "SBC", "NOP", "ISC", "NOP", "SBC", "INC", "ISC",
+];
+
+#[rustfmt::skip]
+const W65C02_MNEMONICS: [&str; 256] = [
+    "BRK", "ORA", "NOP", "NOP", "TSB", "ORA", "ASL", "RMB0", "PHP", "ORA", "ASL", "NOP", "TSB", "ORA", "ASL", "BBR0",
+    "BPL", "ORA", "ORA", "NOP", "TRB", "ORA", "ASL", "RMB1", "CLC", "ORA", "INC", "NOP", "TRB", "ORA", "ASL", "BBR1",
+    "JSR", "AND", "NOP", "NOP", "BIT", "AND", "ROL", "RMB2", "PLP", "AND", "ROL", "NOP", "BIT", "AND", "ROL", "BBR2",
+    "BMI", "AND", "AND", "NOP", "BIT", "AND", "ROL", "RMB3", "SEC", "AND", "DEC", "NOP", "BIT", "AND", "ROL", "BBR3",
+    "RTI", "EOR", "NOP", "NOP", "NOP", "EOR", "LSR", "RMB4", "PHA", "EOR", "LSR", "NOP", "JMP", "EOR", "LSR", "BBR4",
+    "BVC", "EOR", "EOR", "NOP", "NOP", "EOR", "LSR", "RMB5", "CLI", "EOR", "PHY", "NOP", "NOP", "EOR", "LSR", "BBR5",
+    "RTS", "ADC", "NOP", "NOP", "STZ", "ADC", "ROR", "RMB6", "PLA", "ADC", "ROR", "NOP", "JMP", "ADC", "ROR", "BBR6",
+    "BVS", "ADC", "ADC", "NOP", "STZ", "ADC", "ROR", "RMB7", "SEI", "ADC", "PLY", "NOP", "JMP", "ADC", "ROR", "BBR7",
+    "BRA", "STA", "NOP", "NOP", "STY", "STA", "STX", "SMB0", "DEY", "BIT", "TXA", "NOP", "STY", "STA", "STX", "BBS0",
+    "BCC", "STA", "STA", "NOP", "STY", "STA", "STX", "SMB1", "TYA", "STA", "TXS", "NOP", "STZ", "STA", "STZ", "BBS1",
+    "LDY", "LDA", "LDX", "NOP", "LDY", "LDA", "LDX", "SMB2", "TAY", "LDA", "TAX", "NOP", "LDY", "LDA", "LDX", "BBS2",
+    "BCS", "LDA", "LDA", "NOP", "LDY", "LDA", "LDX", "SMB3", "CLV", "LDA", "TSX", "NOP", "LDY", "LDA", "LDX", "BBS3",
+    "CPY", "CMP", "NOP", "NOP", "CPY", "CMP", "DEC", "SMB4", "INY", "CMP", "DEX", "WAI", "CPY", "CMP", "DEC", "BBS4",
+    "BNE", "CMP", "CMP", "NOP", "NOP", "CMP", "DEC", "SMB5", "CLD", "CMP", "PHX", "STP", "NOP", "CMP", "DEC", "BBS5",
+    "CPX", "SBC", "NOP", "NOP", "CPX", "SBC", "INC", "SMB6", "INX", "SBC", "NOP", "NOP", "CPX", "SBC", "INC", "BBS6",
+    "BEQ", "SBC", "SBC", "NOP", "NOP", "SBC", "INC", "SMB7", "SED", "SBC", "PLX", "NOP", "NOP", "SBC", "INC", "BBS7",
+];
+
+/// Mnemonics only used by undocumented NMOS opcodes.
+const NMOS_UNDOCUMENTED: [&str; 20] = [
+    "JAM", "SLO", "RLA", "SRE", "RRA", "SAX", "LAX", "DCP", "ISC", "ANC", "ALR", "ARR", "ANE",
+    "LXA", "SBX", "SHA", "SHX", "SHY", "TAS", "LAS",
+];
+
+/// Base cycle counts, two characters per opcode: the count, then `+` if a
+/// page crossing adds a cycle. Halting opcodes have a count of 0.
+const NMOS_CYCLES: &[u8; 512] = b"\
+7 6 0 8 3 3 5 5 3 2 2 2 4 4 6 6 \
+2 5+0 8 4 4 6 6 2 4+2 7 4+4+7 7 \
+6 6 0 8 3 3 5 5 4 2 2 2 4 4 6 6 \
+2 5+0 8 4 4 6 6 2 4+2 7 4+4+7 7 \
+6 6 0 8 3 3 5 5 3 2 2 2 3 4 6 6 \
+2 5+0 8 4 4 6 6 2 4+2 7 4+4+7 7 \
+6 6 0 8 3 3 5 5 4 2 2 2 5 4 6 6 \
+2 5+0 8 4 4 6 6 2 4+2 7 4+4+7 7 \
+2 6 2 6 3 3 3 3 2 2 2 2 4 4 4 4 \
+2 6 0 6 4 4 4 4 2 5 2 5 5 5 5 5 \
+2 6 2 6 3 3 3 3 2 2 2 2 4 4 4 4 \
+2 5+0 5+4 4 4 4 2 4+2 4+4+4+4+4+\
+2 6 2 8 3 3 5 5 2 2 2 2 4 4 6 6 \
+2 5+0 8 4 4 6 6 2 4+2 7 4+4+7 7 \
+2 6 2 8 3 3 5 5 2 2 2 2 4 4 6 6 \
+2 5+0 8 4 4 6 6 2 4+2 7 4+4+7 7 ";
+
+/// Like [`NMOS_CYCLES`], for the W65C02S.
+const W65C02_CYCLES: &[u8; 512] = b"\
+7 6 2 1 5 3 5 5 3 2 2 1 6 4 6 5 \
+2 5+5 1 5 4 6 5 2 4+2 1 6 4+6+5 \
+6 6 2 1 3 3 5 5 4 2 2 1 4 4 6 5 \
+2 5+5 1 4 4 6 5 2 4+2 1 4+4+6+5 \
+6 6 2 1 3 3 5 5 3 2 2 1 3 4 6 5 \
+2 5+5 1 4 4 6 5 2 4+3 1 8 4+6+5 \
+6 6 2 1 3 3 5 5 4 2 2 1 6 4 6 5 \
+2 5+5 1 4 4 6 5 2 4+4 1 6 4+6+5 \
+3 6 2 1 3 3 3 5 2 2 2 1 4 4 4 5 \
+2 6 5 1 4 4 4 5 2 5 2 1 4 5 5 5 \
+2 6 2 1 3 3 3 5 2 2 2 1 4 4 4 5 \
+2 5+5 1 4 4 4 5 2 4+2 1 4+4+4+5 \
+2 6 2 1 3 3 5 5 2 2 2 3 4 4 6 5 \
+2 5+5 1 4 4 6 5 2 4+3 3 4 4+7 5 \
+2 6 2 1 3 3 5 5 2 2 2 1 4 4 6 5 \
+2 5+5 1 4 4 6 5 2 4+4 1 4 4+7 5 ";
+
+/// A decoded instruction.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Instruction {
+    /// Address of the opcode.
+    pub address: u16,
+    pub opcode: u8,
+    /// Upper case mnemonic. The 65C02 bit instructions include the bit
+    /// number, e.g. `RMB3` and `BBS7`.
+    pub mnemonic: &'static str,
+    pub mode: AddressingMode,
+    /// Operand bytes, little endian. For [`AddressingMode::ZeroPageRelative`]
+    /// the low byte is the zero page address and the high byte the branch
+    /// offset.
+    pub operand: u16,
+    /// Base cycle count. Branches take one more cycle when taken, and one
+    /// more when they cross a page.
+    pub cycles: u8,
+    /// Set if crossing a page boundary while indexing adds a cycle.
+    pub page_cycle: bool,
+    /// Set for undocumented NMOS opcodes and the reserved 65C02 NOPs.
+    pub undocumented: bool,
+}
+
+/// Decodes the instruction at `address`, reading its bytes through `read`.
+///
+/// Undocumented opcodes are decoded with their common names and flagged in
+/// [`Instruction::undocumented`], so callers that only want documented
+/// instructions can show them as data instead.
+pub fn decode(set: InstructionSet, address: u16, mut read: impl FnMut(u16) -> u8) -> Instruction {
+    let opcode = read(address);
+    let mode = opcode::addressing_mode(set, opcode);
+    let mut operand = [0; 2];
+    for (i, byte) in operand.iter_mut().take(mode.operand_len()).enumerate() {
+        *byte = read(address.wrapping_add(1 + i as u16));
+    }
+
+    let (mnemonics, cycles) = match set {
+        InstructionSet::Nmos6502 => (&NMOS_MNEMONICS, NMOS_CYCLES),
+        InstructionSet::W65C02 => (&W65C02_MNEMONICS, W65C02_CYCLES),
+    };
+    let mnemonic = mnemonics[opcode as usize];
+    let undocumented = match set {
+        InstructionSet::Nmos6502 => {
+            NMOS_UNDOCUMENTED.contains(&mnemonic)
+                || (mnemonic == "NOP" && opcode != 0xea)
+                || opcode == 0xeb
+        }
+        InstructionSet::W65C02 => mnemonic == "NOP" && opcode != 0xea,
+    };
+
+    Instruction {
+        address,
+        opcode,
+        mnemonic,
+        mode,
+        operand: u16::from_le_bytes(operand),
+        cycles: cycles[2 * opcode as usize] - b'0',
+        page_cycle: cycles[2 * opcode as usize + 1] == b'+',
+        undocumented,
+    }
+}
+
+/// Decodes the instruction at the start of `bytes`, which are located at
+/// `address`. Returns `None` if `bytes` is shorter than the instruction.
+pub fn decode_bytes(set: InstructionSet, address: u16, bytes: &[u8]) -> Option<Instruction> {
+    let opcode = *bytes.first()?;
+    let size = opcode::addressing_mode(set, opcode).instruction_size();
+    let bytes = bytes.get(..size)?;
+    Some(decode(set, address, |addr| {
+        bytes[addr.wrapping_sub(address) as usize]
+    }))
+}
+
+impl Instruction {
+    /// Length of the instruction in bytes.
+    pub fn size(&self) -> usize {
+        self.mode.instruction_size()
+    }
+
+    /// Address of the following instruction.
+    pub fn next(&self) -> u16 {
+        self.address.wrapping_add(self.size() as u16)
+    }
+
+    /// Address the operand refers to, or `None` for implied, accumulator and
+    /// immediate operands. For branches this is the branch target, and for
+    /// indexed and indirect modes the base address.
+    pub fn target(&self) -> Option<u16> {
+        match self.mode {
+            AddressingMode::Implied | AddressingMode::Accumulator | AddressingMode::Immediate => {
+                None
+            }
+            AddressingMode::Relative => Some(self.branch_target(self.operand as u8)),
+            AddressingMode::ZeroPageRelative => Some(self.branch_target((self.operand >> 8) as u8)),
+            _ => Some(self.operand),
+        }
+    }
+
+    fn branch_target(&self, offset: u8) -> u16 {
+        self.next().wrapping_add(offset as i8 as u16)
+    }
+
+    /// Returns a formatter that prints addresses known to `symbolize` by
+    /// name.
+    pub fn display_with<'s, F>(&self, symbolize: F) -> Symbolized<'s, F>
+    where
+        F: Fn(u16) -> Option<&'s str>,
+    {
+        Symbolized {
+            insn: *self,
+            symbolize,
+            _symbols: PhantomData,
+        }
+    }
+
+    fn fmt_with<'s>(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        symbolize: &dyn Fn(u16) -> Option<&'s str>,
+    ) -> fmt::Result {
+        let addr = |f: &mut fmt::Formatter<'_>, addr: u16, zero_page: bool| match symbolize(addr) {
+            Some(name) => f.write_str(name),
+            None if zero_page => write!(f, "${:02X}", addr),
+            None => write!(f, "${:04X}", addr),
+        };
+
+        f.write_str(self.mnemonic)?;
+        let (prefix, suffix) = match self.mode {
+            AddressingMode::Implied => return Ok(()),
+            AddressingMode::Accumulator => return f.write_str(" A"),
+            AddressingMode::Immediate => return write!(f, " #${:02X}", self.operand),
+            AddressingMode::ZeroPage
+            | AddressingMode::Absolute
+            | AddressingMode::Relative
+            | AddressingMode::ZeroPageRelative => ("", ""),
+            AddressingMode::ZeroPageX | AddressingMode::AbsoluteX => ("", ",X"),
+            AddressingMode::ZeroPageY | AddressingMode::AbsoluteY => ("", ",Y"),
+            AddressingMode::Indirect | AddressingMode::ZeroPageIndirect => ("(", ")"),
+            AddressingMode::IndexedIndirect | AddressingMode::AbsoluteIndexedIndirect => {
+                ("(", ",X)")
+            }
+            AddressingMode::IndirectIndexed => ("(", "),Y"),
+        };
+
+        write!(f, " {}", prefix)?;
+        match self.mode {
+            AddressingMode::Relative => addr(f, self.target().unwrap(), false)?,
+            AddressingMode::ZeroPageRelative => {
+                addr(f, self.operand & 0xff, true)?;
+                f.write_str(",")?;
+                addr(f, self.target().unwrap(), false)?;
+            }
+            mode => addr(f, self.operand, mode.operand_len() == 1)?,
+        }
+        f.write_str(suffix)
+    }
+}
+
+impl fmt::Display for Instruction {
+    /// Formats the instruction in common assembler syntax, e.g. `LDA ($FB),Y`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_with(f, &|_| None)
+    }
+}
+
+/// Formatter returned by [`Instruction::display_with`].
+pub struct Symbolized<'s, F> {
+    insn: Instruction,
+    symbolize: F,
+    _symbols: PhantomData<&'s str>,
+}
+
+impl<'s, F: Fn(u16) -> Option<&'s str>> fmt::Display for Symbolized<'s, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.insn.fmt_with(f, &self.symbolize)
+    }
+}
@@ -0,0 +1,408 @@
+//! A reference NMOS 6502 emulator implementing gdbstub's `Target` for
+//! [`MOSArch`], enabled by the `emulator` feature.
+//!
+//! The emulator doesn't own a connection; drive it from a gdbstub event loop
+//! with [`Emulator::run`], mapping [`RunEvent`] to the loop's events.
+
+use core::convert::Infallible;
+
+use gdbstub::common::Signal;
+use gdbstub::stub::SingleThreadStopReason;
+use gdbstub::target::ext::base::single_register_access::{
+    SingleRegisterAccess, SingleRegisterAccessOps,
+};
+use gdbstub::target::ext::base::singlethread::{
+    SingleThreadBase, SingleThreadResume, SingleThreadResumeOps, SingleThreadSingleStep,
+    SingleThreadSingleStepOps,
+};
+use gdbstub::target::ext::base::BaseOps;
+use gdbstub::target::ext::breakpoints::{
+    Breakpoints, BreakpointsOps, HwBreakpoint, HwBreakpointOps, SwBreakpoint, SwBreakpointOps,
+};
+use gdbstub::target::ext::memory_map::{MemoryMap, MemoryMapOps};
+use gdbstub::target::ext::section_offsets::{Offsets, SectionOffsets, SectionOffsetsOps};
+use gdbstub::target::{Target, TargetError, TargetResult};
+
+use crate::loader::Relocation;
+use crate::memory_map::{memory_map_xml, MemoryRegion};
+use crate::{
+    Bus, ImaginaryRegs, MOSArch, MosBreakpointKind, MosRegId, MosRegs, RegAccess, DEFAULT_RC_COUNT,
+};
+
+mod cpu;
+
+pub use cpu::Cpu;
+
+/// Maximum number of breakpoints, software and hardware combined.
+pub const MAX_BREAKPOINTS: usize = 64;
+
+/// Number of instructions [`Emulator::run`] executes between polls for
+/// incoming data.
+const POLL_INTERVAL: usize = 1024;
+
+/// How a breakpoint was set: with `Z0` or `Z1`. Both stop the target the
+/// same way, but are removed and reported separately.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum BreakpointType {
+    Software,
+    Hardware,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum ExecMode {
+    Step,
+    Continue,
+}
+
+/// Result of [`Emulator::run`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum RunEvent {
+    /// The connection has data to process; execution can be resumed with
+    /// another call to [`Emulator::run`].
+    IncomingData,
+    /// The target stopped.
+    Stopped(SingleThreadStopReason<u16>),
+}
+
+/// An NMOS 6502 attached to `bus`, debuggable through gdbstub.
+///
+/// Breakpoints stop the target when the PC reaches them and don't modify
+/// memory.
+/// A `JAM` opcode stops the target with `SIGILL`.
+pub struct Emulator<B, const RC: usize = DEFAULT_RC_COUNT> {
+    pub cpu: Cpu<RC>,
+    pub bus: B,
+    /// Where the program keeps the imaginary registers. When set, GDB reads
+    /// and writes them in memory; otherwise they are `cpu.regs.rc`, which
+    /// the emulated CPU never touches.
+    pub imaginary_regs: Option<ImaginaryRegs>,
+    /// How far the program was relocated when loading, reported to GDB so it
+    /// can relocate the program's symbols.
+    pub relocation: Option<Relocation>,
+    /// Memory map reported to GDB, such as one of the machine profiles in
+    /// [`memory_map`](crate::memory_map). Without one, GDB treats all memory
+    /// as RAM.
+    pub memory_map: Option<&'static [MemoryRegion]>,
+    exec_mode: ExecMode,
+    breakpoints: [Option<(u16, BreakpointType)>; MAX_BREAKPOINTS],
+}
+
+impl<B: Bus, const RC: usize> Emulator<B, RC> {
+    /// Creates an emulator in the power-on state. Call [`Cpu::reset`] or set
+    /// `cpu.regs.pc` before running.
+    pub fn new(bus: B) -> Self {
+        Emulator {
+            cpu: Cpu::new(),
+            bus,
+            imaginary_regs: None,
+            relocation: None,
+            memory_map: None,
+            exec_mode: ExecMode::Continue,
+            breakpoints: [None; MAX_BREAKPOINTS],
+        }
+    }
+
+    /// Resets the CPU through the reset vector.
+    pub fn reset(&mut self) {
+        self.cpu.reset(&mut self.bus);
+    }
+
+    /// Returns whether a breakpoint is set at `addr`.
+    pub fn has_breakpoint(&self, addr: u16) -> bool {
+        self.breakpoint_at(addr).is_some()
+    }
+
+    fn breakpoint_at(&self, addr: u16) -> Option<BreakpointType> {
+        self.breakpoints
+            .iter()
+            .flatten()
+            .find(|(bp, _)| *bp == addr)
+            .map(|&(_, ty)| ty)
+    }
+
+    /// Executes one instruction. Returns the reason to stop, if any, ignoring
+    /// the execution mode.
+    pub fn step(&mut self) -> Option<SingleThreadStopReason<u16>> {
+        self.cpu.step(&mut self.bus);
+        if self.cpu.jammed {
+            Some(SingleThreadStopReason::Signal(Signal::SIGILL))
+        } else {
+            self.breakpoint_at(self.cpu.regs.pc).map(|ty| match ty {
+                BreakpointType::Software => SingleThreadStopReason::SwBreak(()),
+                BreakpointType::Hardware => SingleThreadStopReason::HwBreak(()),
+            })
+        }
+    }
+
+    /// Runs according to the last resume request until the target stops or
+    /// `poll_incoming_data` returns `true`.
+    pub fn run(&mut self, poll_incoming_data: impl FnMut() -> bool) -> RunEvent {
+        self.run_with(poll_incoming_data, |_| None)
+    }
+
+    /// Like [`Emulator::run`], additionally stopping when `check` returns a
+    /// stop reason. `check` is called with the bus after each instruction,
+    /// e.g. to report a write to an exit port as `Exited`.
+    pub fn run_with(
+        &mut self,
+        mut poll_incoming_data: impl FnMut() -> bool,
+        mut check: impl FnMut(&mut B) -> Option<SingleThreadStopReason<u16>>,
+    ) -> RunEvent {
+        let mut step = |emu: &mut Self| {
+            let reason = emu.step();
+            check(&mut emu.bus).or(reason)
+        };
+        if self.exec_mode == ExecMode::Step {
+            let reason = step(self).unwrap_or(SingleThreadStopReason::DoneStep);
+            return RunEvent::Stopped(reason);
+        }
+        loop {
+            for _ in 0..POLL_INTERVAL {
+                if let Some(reason) = step(self) {
+                    return RunEvent::Stopped(reason);
+                }
+            }
+            if poll_incoming_data() {
+                return RunEvent::IncomingData;
+            }
+        }
+    }
+
+    fn add_breakpoint(
+        &mut self,
+        addr: u16,
+        kind: MosBreakpointKind,
+        ty: BreakpointType,
+    ) -> TargetResult<bool, Self> {
+        // The emulator has no bank switching of its own: whatever banking
+        // the bus does is invisible to it, so it can't tell which bank is
+        // mapped in. Refusing makes the client report the breakpoint as
+        // unsupported rather than stop in the wrong bank.
+        if matches!(kind, MosBreakpointKind::Banked(_)) {
+            return Ok(false);
+        }
+        let entry = Some((addr, ty));
+        if self.breakpoints.contains(&entry) {
+            return Ok(true);
+        }
+        match self.breakpoints.iter_mut().find(|bp| bp.is_none()) {
+            Some(slot) => {
+                *slot = entry;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn remove_breakpoint(&mut self, addr: u16, ty: BreakpointType) -> TargetResult<bool, Self> {
+        match self
+            .breakpoints
+            .iter_mut()
+            .find(|bp| **bp == Some((addr, ty)))
+        {
+            Some(slot) => {
+                *slot = None;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+impl<B: Bus, const RC: usize> Target for Emulator<B, RC> {
+    type Arch = MOSArch<RC>;
+    type Error = Infallible;
+
+    #[inline(always)]
+    fn base_ops(&mut self) -> BaseOps<'_, Self::Arch, Self::Error> {
+        BaseOps::SingleThread(self)
+    }
+
+    #[inline(always)]
+    fn support_breakpoints(&mut self) -> Option<BreakpointsOps<'_, Self>> {
+        Some(self)
+    }
+
+    #[inline(always)]
+    fn support_section_offsets(&mut self) -> Option<SectionOffsetsOps<'_, Self>> {
+        match self.relocation {
+            Some(_) => Some(self),
+            None => None,
+        }
+    }
+
+    #[inline(always)]
+    fn support_memory_map(&mut self) -> Option<MemoryMapOps<'_, Self>> {
+        match self.memory_map {
+            Some(_) => Some(self),
+            None => None,
+        }
+    }
+}
+
+impl<B: Bus, const RC: usize> SingleThreadBase for Emulator<B, RC> {
+    fn read_registers(&mut self, regs: &mut MosRegs<RC>) -> TargetResult<(), Self> {
+        if let Some(imaginary) = self.imaginary_regs {
+            imaginary.load(&mut self.bus, &mut self.cpu.regs);
+        }
+        *regs = self.cpu.regs;
+        Ok(())
+    }
+
+    fn write_registers(&mut self, regs: &MosRegs<RC>) -> TargetResult<(), Self> {
+        self.cpu.regs = *regs;
+        if let Some(imaginary) = self.imaginary_regs {
+            imaginary.store(&mut self.bus, regs);
+        }
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn support_single_register_access(&mut self) -> Option<SingleRegisterAccessOps<'_, (), Self>> {
+        Some(self)
+    }
+
+    fn read_addrs(&mut self, start_addr: u16, data: &mut [u8]) -> TargetResult<usize, Self> {
+        // Reads stop at the end of the address space.
+        let len = data.len().min(0x10000 - start_addr as usize);
+        for (addr, byte) in (start_addr..=u16::MAX).zip(&mut data[..len]) {
+            *byte = self.bus.peek(addr);
+        }
+        Ok(len)
+    }
+
+    fn write_addrs(&mut self, start_addr: u16, data: &[u8]) -> TargetResult<(), Self> {
+        if data.len() > 0x10000 - start_addr as usize {
+            return Err(TargetError::NonFatal);
+        }
+        for (addr, byte) in (start_addr..=u16::MAX).zip(data) {
+            self.bus.poke(addr, *byte);
+        }
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn support_resume(&mut self) -> Option<SingleThreadResumeOps<'_, Self>> {
+        Some(self)
+    }
+}
+
+impl<B: Bus, const RC: usize> SingleRegisterAccess<()> for Emulator<B, RC> {
+    fn read_register(
+        &mut self,
+        _tid: (),
+        reg_id: MosRegId<RC>,
+        buf: &mut [u8],
+    ) -> TargetResult<usize, Self> {
+        match self.imaginary_regs {
+            Some(imaginary) => imaginary.read_reg(&mut self.bus, &mut self.cpu.regs, &reg_id, buf),
+            None => self.cpu.regs.read_reg(&reg_id, buf),
+        }
+        .ok_or(TargetError::NonFatal)
+    }
+
+    fn write_register(
+        &mut self,
+        _tid: (),
+        reg_id: MosRegId<RC>,
+        val: &[u8],
+    ) -> TargetResult<(), Self> {
+        match self.imaginary_regs {
+            Some(imaginary) => imaginary.write_reg(&mut self.bus, &mut self.cpu.regs, &reg_id, val),
+            None => self.cpu.regs.write_reg(&reg_id, val),
+        }
+        .ok_or(TargetError::NonFatal)
+    }
+}
+
+impl<B: Bus, const RC: usize> SingleThreadResume for Emulator<B, RC> {
+    /// Signals can't be delivered to a bare CPU and are ignored.
+    fn resume(&mut self, _signal: Option<Signal>) -> Result<(), Self::Error> {
+        self.exec_mode = ExecMode::Continue;
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn support_single_step(&mut self) -> Option<SingleThreadSingleStepOps<'_, Self>> {
+        Some(self)
+    }
+}
+
+impl<B: Bus, const RC: usize> SingleThreadSingleStep for Emulator<B, RC> {
+    fn step(&mut self, _signal: Option<Signal>) -> Result<(), Self::Error> {
+        self.exec_mode = ExecMode::Step;
+        Ok(())
+    }
+}
+
+impl<B: Bus, const RC: usize> Breakpoints for Emulator<B, RC> {
+    #[inline(always)]
+    fn support_sw_breakpoint(&mut self) -> Option<SwBreakpointOps<'_, Self>> {
+        Some(self)
+    }
+
+    #[inline(always)]
+    fn support_hw_breakpoint(&mut self) -> Option<HwBreakpointOps<'_, Self>> {
+        Some(self)
+    }
+}
+
+impl<B: Bus, const RC: usize> SwBreakpoint for Emulator<B, RC> {
+    fn add_sw_breakpoint(
+        &mut self,
+        addr: u16,
+        kind: MosBreakpointKind,
+    ) -> TargetResult<bool, Self> {
+        self.add_breakpoint(addr, kind, BreakpointType::Software)
+    }
+
+    fn remove_sw_breakpoint(
+        &mut self,
+        addr: u16,
+        _kind: MosBreakpointKind,
+    ) -> TargetResult<bool, Self> {
+        self.remove_breakpoint(addr, BreakpointType::Software)
+    }
+}
+
+impl<B: Bus, const RC: usize> HwBreakpoint for Emulator<B, RC> {
+    fn add_hw_breakpoint(
+        &mut self,
+        addr: u16,
+        kind: MosBreakpointKind,
+    ) -> TargetResult<bool, Self> {
+        self.add_breakpoint(addr, kind, BreakpointType::Hardware)
+    }
+
+    fn remove_hw_breakpoint(
+        &mut self,
+        addr: u16,
+        _kind: MosBreakpointKind,
+    ) -> TargetResult<bool, Self> {
+        self.remove_breakpoint(addr, BreakpointType::Hardware)
+    }
+}
+
+impl<B: Bus, const RC: usize> SectionOffsets for Emulator<B, RC> {
+    fn get_section_offsets(&mut self) -> Result<Offsets<u16>, Self::Error> {
+        let relocation = self.relocation.unwrap_or(Relocation {
+            text: 0,
+            data: 0,
+            bss: 0,
+        });
+        Ok(relocation.offsets())
+    }
+}
+
+impl<B: Bus, const RC: usize> MemoryMap for Emulator<B, RC> {
+    fn memory_map_xml(
+        &self,
+        offset: u64,
+        length: usize,
+        buf: &mut [u8],
+    ) -> TargetResult<usize, Self> {
+        let regions = self.memory_map.unwrap_or(&[]).iter().copied();
+        let len = length.min(buf.len());
+        Ok(memory_map_xml(regions, offset, &mut buf[..len]))
+    }
+}
@@ -0,0 +1,695 @@
+use crate::disasm::{self, AddressingMode, Instruction};
+use crate::{Bus, InstructionSet, MosRegs, DEFAULT_RC_COUNT, IRQ_VECTOR, NMI_VECTOR, RESET_VECTOR};
+
+const C: u8 = 0x01;
+const Z: u8 = 0x02;
+const I: u8 = 0x04;
+const D: u8 = 0x08;
+const B: u8 = 0x10;
+const U: u8 = 0x20;
+const V: u8 = 0x40;
+const N: u8 = 0x80;
+
+/// A cycle-counted NMOS 6502, including the undocumented opcodes and
+/// decimal mode.
+///
+/// Cycle counts are exact per instruction, but the bus accesses aren't: the
+/// dummy reads and writes of the real CPU are not performed.
+#[derive(Debug, Clone)]
+pub struct Cpu<const RC: usize = DEFAULT_RC_COUNT> {
+    pub regs: MosRegs<RC>,
+    /// Cycles executed since the CPU was created.
+    pub cycles: u64,
+    /// Set after executing a `JAM` opcode. Only [`Cpu::reset`] recovers.
+    pub jammed: bool,
+    nmi: bool,
+    irq: bool,
+}
+
+impl<const RC: usize> Default for Cpu<RC> {
+    fn default() -> Self {
+        Cpu {
+            regs: MosRegs {
+                flags: U | I,
+                ..MosRegs::default()
+            },
+            cycles: 0,
+            jammed: false,
+            nmi: false,
+            irq: false,
+        }
+    }
+}
+
+/// Operation performed by an opcode, named after its mnemonic.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Op {
+    Lda,
+    Ldx,
+    Ldy,
+    Lax,
+    Las,
+    Sta,
+    Stx,
+    Sty,
+    Sax,
+    Sha,
+    Shx,
+    Shy,
+    Tas,
+    Tax,
+    Tay,
+    Txa,
+    Tya,
+    Tsx,
+    Txs,
+    Inx,
+    Iny,
+    Dex,
+    Dey,
+    Pha,
+    Php,
+    Pla,
+    Plp,
+    Ora,
+    And,
+    Eor,
+    Bit,
+    Adc,
+    Sbc,
+    Cmp,
+    Cpx,
+    Cpy,
+    Asl,
+    Lsr,
+    Rol,
+    Ror,
+    Inc,
+    Dec,
+    Slo,
+    Rla,
+    Sre,
+    Rra,
+    Dcp,
+    Isc,
+    Anc,
+    Alr,
+    Arr,
+    Ane,
+    Lxa,
+    Sbx,
+    Bpl,
+    Bmi,
+    Bvc,
+    Bvs,
+    Bcc,
+    Bcs,
+    Bne,
+    Beq,
+    Jmp,
+    Jsr,
+    Rts,
+    Rti,
+    Brk,
+    Clc,
+    Sec,
+    Cli,
+    Sei,
+    Clv,
+    Cld,
+    Sed,
+    Nop,
+    Jam,
+}
+
+/// Operation of each NMOS opcode, including the undocumented ones.
+#[rustfmt::skip]
+const OPERATIONS: [Op; 256] = {
+    use Op::*;
+    [
+        Brk, Ora, Jam, Slo, Nop, Ora, Asl, Slo, Php, Ora, Asl, Anc, Nop, Ora, Asl, Slo,
+        Bpl, Ora, Jam, Slo, Nop, Ora, Asl, Slo, Clc, Ora, Nop, Slo, Nop, Ora, Asl, Slo,
+        Jsr, And, Jam, Rla, Bit, And, Rol, Rla, Plp, And, Rol, Anc, Bit, And, Rol, Rla,
+        Bmi, And, Jam, Rla, Nop, And, Rol, Rla, Sec, And, Nop, Rla, Nop, And, Rol, Rla,
+        Rti, Eor, Jam, Sre, Nop, Eor, Lsr, Sre, Pha, Eor, Lsr, Alr, Jmp, Eor, Lsr, Sre,
+        Bvc, Eor, Jam, Sre, Nop, Eor, Lsr, Sre, Cli, Eor, Nop, Sre, Nop, Eor, Lsr, Sre,
+        Rts, Adc, Jam, Rra, Nop, Adc, Ror, Rra, Pla, Adc, Ror, Arr, Jmp, Adc, Ror, Rra,
+        Bvs, Adc, Jam, Rra, Nop, Adc, Ror, Rra, Sei, Adc, Nop, Rra, Nop, Adc, Ror, Rra,
+        Nop, Sta, Nop, Sax, Sty, Sta, Stx, Sax, Dey, Nop, Txa, Ane, Sty, Sta, Stx, Sax,
+        Bcc, Sta, Jam, Sha, Sty, Sta, Stx, Sax, Tya, Sta, Txs, Tas, Shy, Sta, Shx, Sha,
+        Ldy, Lda, Ldx, Lax, Ldy, Lda, Ldx, Lax, Tay, Lda, Tax, Lxa, Ldy, Lda, Ldx, Lax,
+        Bcs, Lda, Jam, Lax, Ldy, Lda, Ldx, Lax, Clv, Lda, Tsx, Las, Ldy, Lda, Ldx, Lax,
+        Cpy, Cmp, Nop, Dcp, Cpy, Cmp, Dec, Dcp, Iny, Cmp, Dex, Sbx, Cpy, Cmp, Dec, Dcp,
+        Bne, Cmp, Jam, Dcp, Nop, Cmp, Dec, Dcp, Cld, Cmp, Nop, Dcp, Nop, Cmp, Dec, Dcp,
+        Cpx, Sbc, Nop, Isc, Cpx, Sbc, Inc, Isc, Inx, Sbc, Nop, Sbc, Cpx, Sbc, Inc, Isc,
+        Beq, Sbc, Jam, Isc, Nop, Sbc, Inc, Isc, Sed, Sbc, Nop, Isc, Nop, Sbc, Inc, Isc,
+    ]
+};
+
+/// Read-modify-write operation, also performed by the undocumented opcodes
+/// that combine one with an ALU operation.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Rmw {
+    Asl,
+    Lsr,
+    Rol,
+    Ror,
+    Inc,
+    Dec,
+}
+
+/// Operand of an instruction, after address calculation.
+#[derive(Copy, Clone)]
+enum Operand {
+    None,
+    Accumulator,
+    Immediate(u8),
+    Memory(u16),
+}
+
+impl<const RC: usize> Cpu<RC> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resets the CPU: sets I, moves S down by three as the real CPU does,
+    /// and jumps through the reset vector.
+    pub fn reset(&mut self, bus: &mut impl Bus) {
+        self.regs.s = self.regs.s.wrapping_sub(3);
+        self.regs.flags |= U | I;
+        self.regs.pc = read_word(bus, RESET_VECTOR);
+        self.jammed = false;
+        self.nmi = false;
+        self.cycles += 7;
+    }
+
+    /// Requests a non-maskable interrupt, taken before the next instruction.
+    pub fn nmi(&mut self) {
+        self.nmi = true;
+    }
+
+    /// Sets the level of the IRQ line. The interrupt is taken before the next
+    /// instruction for as long as the line is asserted and I is clear.
+    pub fn set_irq(&mut self, asserted: bool) {
+        self.irq = asserted;
+    }
+
+    /// Executes one instruction, or enters a pending interrupt handler.
+    /// Returns the number of cycles taken, which is 0 when the CPU is jammed.
+    pub fn step(&mut self, bus: &mut impl Bus) -> u32 {
+        if self.jammed {
+            return 0;
+        }
+        let cycles = if self.nmi {
+            self.nmi = false;
+            self.interrupt(bus, NMI_VECTOR, self.regs.pc, false)
+        } else if self.irq && !self.flag(I) {
+            self.interrupt(bus, IRQ_VECTOR, self.regs.pc, false)
+        } else {
+            self.execute(bus)
+        };
+        self.cycles += cycles as u64;
+        cycles
+    }
+
+    fn interrupt(&mut self, bus: &mut impl Bus, vector: u16, ret: u16, brk: bool) -> u32 {
+        let [lo, hi] = ret.to_le_bytes();
+        self.push(bus, hi);
+        self.push(bus, lo);
+        let flags = if brk {
+            self.regs.flags | B
+        } else {
+            self.regs.flags & !B
+        };
+        self.push(bus, flags | U);
+        self.regs.flags |= I;
+        self.regs.pc = read_word(bus, vector);
+        7
+    }
+
+    fn execute(&mut self, bus: &mut impl Bus) -> u32 {
+        let insn = disasm::decode(InstructionSet::Nmos6502, self.regs.pc, |addr| {
+            bus.read(addr)
+        });
+        self.regs.pc = insn.next();
+        let mut cycles = insn.cycles as u32;
+
+        let zp = insn.operand & 0xff;
+        let (operand, base) = match insn.mode {
+            AddressingMode::Implied => (Operand::None, 0),
+            AddressingMode::Accumulator => (Operand::Accumulator, 0),
+            AddressingMode::Immediate => (Operand::Immediate(insn.operand as u8), 0),
+            AddressingMode::ZeroPage => (Operand::Memory(zp), zp),
+            AddressingMode::ZeroPageX => (Operand::Memory((zp + self.regs.x as u16) & 0xff), zp),
+            AddressingMode::ZeroPageY => (Operand::Memory((zp + self.regs.y as u16) & 0xff), zp),
+            AddressingMode::Absolute | AddressingMode::Relative => {
+                (Operand::Memory(insn.operand), insn.operand)
+            }
+            AddressingMode::AbsoluteX => {
+                let addr = insn.operand.wrapping_add(self.regs.x as u16);
+                (Operand::Memory(addr), insn.operand)
+            }
+            AddressingMode::AbsoluteY => {
+                let addr = insn.operand.wrapping_add(self.regs.y as u16);
+                (Operand::Memory(addr), insn.operand)
+            }
+            AddressingMode::Indirect => {
+                // No carry into the high byte of the pointer.
+                let hi = (insn.operand & 0xff00) | (insn.operand.wrapping_add(1) & 0xff);
+                let addr = u16::from_le_bytes([bus.read(insn.operand), bus.read(hi)]);
+                (Operand::Memory(addr), insn.operand)
+            }
+            AddressingMode::IndexedIndirect => {
+                let ptr = (zp + self.regs.x as u16) & 0xff;
+                (Operand::Memory(read_zp_word(bus, ptr)), zp)
+            }
+            AddressingMode::IndirectIndexed => {
+                let base = read_zp_word(bus, zp);
+                (Operand::Memory(base.wrapping_add(self.regs.y as u16)), base)
+            }
+            AddressingMode::ZeroPageIndirect
+            | AddressingMode::AbsoluteIndexedIndirect
+            | AddressingMode::ZeroPageRelative => unreachable!("65C02 addressing mode"),
+        };
+        if let Operand::Memory(addr) = operand {
+            if insn.page_cycle && (addr ^ base) & 0xff00 != 0 {
+                cycles += 1;
+            }
+        }
+        // High byte of the base address plus one, used by SHA, SHX, SHY and
+        // TAS.
+        let h1 = ((base >> 8) as u8).wrapping_add(1);
+
+        match OPERATIONS[insn.opcode as usize] {
+            Op::Lda => {
+                self.regs.a = self.load(bus, operand);
+                self.set_nz(self.regs.a);
+            }
+            Op::Ldx => {
+                self.regs.x = self.load(bus, operand);
+                self.set_nz(self.regs.x);
+            }
+            Op::Ldy => {
+                self.regs.y = self.load(bus, operand);
+                self.set_nz(self.regs.y);
+            }
+            Op::Lax => {
+                let value = self.load(bus, operand);
+                self.regs.a = value;
+                self.regs.x = value;
+                self.set_nz(value);
+            }
+            Op::Las => {
+                let value = self.load(bus, operand) & self.regs.s;
+                self.regs.a = value;
+                self.regs.x = value;
+                self.regs.s = value;
+                self.set_nz(value);
+            }
+            Op::Sta => self.store(bus, operand, self.regs.a),
+            Op::Stx => self.store(bus, operand, self.regs.x),
+            Op::Sty => self.store(bus, operand, self.regs.y),
+            Op::Sax => self.store(bus, operand, self.regs.a & self.regs.x),
+            Op::Sha => self.store(bus, operand, self.regs.a & self.regs.x & h1),
+            Op::Shx => self.store(bus, operand, self.regs.x & h1),
+            Op::Shy => self.store(bus, operand, self.regs.y & h1),
+            Op::Tas => {
+                self.regs.s = self.regs.a & self.regs.x;
+                self.store(bus, operand, self.regs.s & h1);
+            }
+
+            Op::Tax => {
+                self.regs.x = self.regs.a;
+                self.set_nz(self.regs.x);
+            }
+            Op::Tay => {
+                self.regs.y = self.regs.a;
+                self.set_nz(self.regs.y);
+            }
+            Op::Txa => {
+                self.regs.a = self.regs.x;
+                self.set_nz(self.regs.a);
+            }
+            Op::Tya => {
+                self.regs.a = self.regs.y;
+                self.set_nz(self.regs.a);
+            }
+            Op::Tsx => {
+                self.regs.x = self.regs.s;
+                self.set_nz(self.regs.x);
+            }
+            Op::Txs => self.regs.s = self.regs.x,
+            Op::Inx => {
+                self.regs.x = self.regs.x.wrapping_add(1);
+                self.set_nz(self.regs.x);
+            }
+            Op::Iny => {
+                self.regs.y = self.regs.y.wrapping_add(1);
+                self.set_nz(self.regs.y);
+            }
+            Op::Dex => {
+                self.regs.x = self.regs.x.wrapping_sub(1);
+                self.set_nz(self.regs.x);
+            }
+            Op::Dey => {
+                self.regs.y = self.regs.y.wrapping_sub(1);
+                self.set_nz(self.regs.y);
+            }
+
+            Op::Pha => self.push(bus, self.regs.a),
+            Op::Php => self.push(bus, self.regs.flags | B | U),
+            Op::Pla => {
+                self.regs.a = self.pull(bus);
+                self.set_nz(self.regs.a);
+            }
+            Op::Plp => self.regs.flags = (self.pull(bus) & !B) | U,
+
+            Op::Ora => {
+                self.regs.a |= self.load(bus, operand);
+                self.set_nz(self.regs.a);
+            }
+            Op::And => {
+                self.regs.a &= self.load(bus, operand);
+                self.set_nz(self.regs.a);
+            }
+            Op::Eor => {
+                self.regs.a ^= self.load(bus, operand);
+                self.set_nz(self.regs.a);
+            }
+            Op::Bit => {
+                let value = self.load(bus, operand);
+                self.set_flag(Z, self.regs.a & value == 0);
+                self.regs.flags = (self.regs.flags & !(N | V)) | (value & (N | V));
+            }
+            Op::Adc => {
+                let value = self.load(bus, operand);
+                self.adc(value);
+            }
+            Op::Sbc => {
+                let value = self.load(bus, operand);
+                self.sbc(value);
+            }
+            Op::Cmp => {
+                let value = self.load(bus, operand);
+                self.compare(self.regs.a, value);
+            }
+            Op::Cpx => {
+                let value = self.load(bus, operand);
+                self.compare(self.regs.x, value);
+            }
+            Op::Cpy => {
+                let value = self.load(bus, operand);
+                self.compare(self.regs.y, value);
+            }
+
+            Op::Asl => {
+                self.modify(bus, operand, Rmw::Asl);
+            }
+            Op::Lsr => {
+                self.modify(bus, operand, Rmw::Lsr);
+            }
+            Op::Rol => {
+                self.modify(bus, operand, Rmw::Rol);
+            }
+            Op::Ror => {
+                self.modify(bus, operand, Rmw::Ror);
+            }
+            Op::Inc => {
+                self.modify(bus, operand, Rmw::Inc);
+            }
+            Op::Dec => {
+                self.modify(bus, operand, Rmw::Dec);
+            }
+            Op::Slo => {
+                self.regs.a |= self.modify(bus, operand, Rmw::Asl);
+                self.set_nz(self.regs.a);
+            }
+            Op::Rla => {
+                self.regs.a &= self.modify(bus, operand, Rmw::Rol);
+                self.set_nz(self.regs.a);
+            }
+            Op::Sre => {
+                self.regs.a ^= self.modify(bus, operand, Rmw::Lsr);
+                self.set_nz(self.regs.a);
+            }
+            Op::Rra => {
+                let value = self.modify(bus, operand, Rmw::Ror);
+                self.adc(value);
+            }
+            Op::Dcp => {
+                let value = self.modify(bus, operand, Rmw::Dec);
+                self.compare(self.regs.a, value);
+            }
+            Op::Isc => {
+                let value = self.modify(bus, operand, Rmw::Inc);
+                self.sbc(value);
+            }
+            Op::Anc => {
+                self.regs.a &= self.load(bus, operand);
+                self.set_nz(self.regs.a);
+                self.set_flag(C, self.regs.a & 0x80 != 0);
+            }
+            Op::Alr => {
+                self.regs.a &= self.load(bus, operand);
+                self.modify(bus, Operand::Accumulator, Rmw::Lsr);
+            }
+            Op::Arr => {
+                self.regs.a &= self.load(bus, operand);
+                let carry = self.flag(C) as u8;
+                self.regs.a = (self.regs.a >> 1) | (carry << 7);
+                let a = self.regs.a;
+                self.set_nz(a);
+                self.set_flag(C, a & 0x40 != 0);
+                self.set_flag(V, ((a >> 6) ^ (a >> 5)) & 1 != 0);
+            }
+            Op::Ane => {
+                self.regs.a = (self.regs.a | 0xee) & self.regs.x & self.load(bus, operand);
+                self.set_nz(self.regs.a);
+            }
+            Op::Lxa => {
+                let value = (self.regs.a | 0xee) & self.load(bus, operand);
+                self.regs.a = value;
+                self.regs.x = value;
+                self.set_nz(value);
+            }
+            Op::Sbx => {
+                let value = self.load(bus, operand);
+                let ax = self.regs.a & self.regs.x;
+                self.compare(ax, value);
+                self.regs.x = ax.wrapping_sub(value);
+            }
+
+            Op::Bpl => cycles += self.branch(&insn, N, false),
+            Op::Bmi => cycles += self.branch(&insn, N, true),
+            Op::Bvc => cycles += self.branch(&insn, V, false),
+            Op::Bvs => cycles += self.branch(&insn, V, true),
+            Op::Bcc => cycles += self.branch(&insn, C, false),
+            Op::Bcs => cycles += self.branch(&insn, C, true),
+            Op::Bne => cycles += self.branch(&insn, Z, false),
+            Op::Beq => cycles += self.branch(&insn, Z, true),
+            Op::Jmp => {
+                if let Operand::Memory(addr) = operand {
+                    self.regs.pc = addr;
+                }
+            }
+            Op::Jsr => {
+                let [lo, hi] = self.regs.pc.wrapping_sub(1).to_le_bytes();
+                self.push(bus, hi);
+                self.push(bus, lo);
+                self.regs.pc = insn.operand;
+            }
+            Op::Rts => {
+                let lo = self.pull(bus);
+                let hi = self.pull(bus);
+                self.regs.pc = u16::from_le_bytes([lo, hi]).wrapping_add(1);
+            }
+            Op::Rti => {
+                self.regs.flags = (self.pull(bus) & !B) | U;
+                let lo = self.pull(bus);
+                let hi = self.pull(bus);
+                self.regs.pc = u16::from_le_bytes([lo, hi]);
+            }
+            Op::Brk => {
+                // BRK skips the byte following the opcode.
+                let ret = insn.address.wrapping_add(2);
+                self.interrupt(bus, IRQ_VECTOR, ret, true);
+            }
+
+            Op::Clc => self.regs.flags &= !C,
+            Op::Sec => self.regs.flags |= C,
+            Op::Cli => self.regs.flags &= !I,
+            Op::Sei => self.regs.flags |= I,
+            Op::Clv => self.regs.flags &= !V,
+            Op::Cld => self.regs.flags &= !D,
+            Op::Sed => self.regs.flags |= D,
+            Op::Nop => {
+                if let Operand::Memory(_) = operand {
+                    self.load(bus, operand);
+                }
+            }
+            Op::Jam => {
+                self.regs.pc = insn.address;
+                self.jammed = true;
+                return 0;
+            }
+        }
+        cycles
+    }
+
+    fn load(&mut self, bus: &mut impl Bus, operand: Operand) -> u8 {
+        match operand {
+            Operand::None => 0,
+            Operand::Accumulator => self.regs.a,
+            Operand::Immediate(value) => value,
+            Operand::Memory(addr) => bus.read(addr),
+        }
+    }
+
+    fn store(&mut self, bus: &mut impl Bus, operand: Operand, value: u8) {
+        match operand {
+            Operand::Accumulator => self.regs.a = value,
+            Operand::Memory(addr) => bus.write(addr, value),
+            Operand::None | Operand::Immediate(_) => {}
+        }
+    }
+
+    /// Takes the branch `insn` if the flag `mask` is `set`, returning the
+    /// cycles this adds.
+    fn branch(&mut self, insn: &Instruction, mask: u8, set: bool) -> u32 {
+        if self.flag(mask) != set {
+            return 0;
+        }
+        let target = insn.target().unwrap();
+        let cycles = 1 + ((target ^ self.regs.pc) & 0xff00 != 0) as u32;
+        self.regs.pc = target;
+        cycles
+    }
+
+    /// Performs a read-modify-write operation, returning the new value.
+    fn modify(&mut self, bus: &mut impl Bus, operand: Operand, op: Rmw) -> u8 {
+        let value = self.load(bus, operand);
+        let carry = self.regs.flags & C;
+        let result = match op {
+            Rmw::Asl => {
+                self.set_flag(C, value & 0x80 != 0);
+                value << 1
+            }
+            Rmw::Lsr => {
+                self.set_flag(C, value & 0x01 != 0);
+                value >> 1
+            }
+            Rmw::Rol => {
+                self.set_flag(C, value & 0x80 != 0);
+                (value << 1) | carry
+            }
+            Rmw::Ror => {
+                self.set_flag(C, value & 0x01 != 0);
+                (value >> 1) | (carry << 7)
+            }
+            Rmw::Inc => value.wrapping_add(1),
+            Rmw::Dec => value.wrapping_sub(1),
+        };
+        self.set_nz(result);
+        self.store(bus, operand, result);
+        result
+    }
+
+    fn adc(&mut self, value: u8) {
+        let a = self.regs.a;
+        let carry = (self.regs.flags & C) as u16;
+        let binary = a as u16 + value as u16 + carry;
+        if !self.flag(D) {
+            self.set_flag(C, binary > 0xff);
+            self.set_flag(V, (!(a ^ value) & (a ^ binary as u8)) & 0x80 != 0);
+            self.regs.a = binary as u8;
+            self.set_nz(self.regs.a);
+            return;
+        }
+
+        // Decimal mode. Z comes from the binary sum; N and V from the sum
+        // after adjusting the low digit only.
+        let mut lo = (a & 0x0f) as u16 + (value & 0x0f) as u16 + carry;
+        let mut hi = (a >> 4) as u16 + (value >> 4) as u16;
+        if lo > 9 {
+            lo += 6;
+        }
+        if lo > 0x0f {
+            hi += 1;
+        }
+        let partial = (hi << 4) as u8;
+        self.set_flag(Z, binary as u8 == 0);
+        self.set_flag(N, partial & 0x80 != 0);
+        self.set_flag(V, (!(a ^ value) & (a ^ partial)) & 0x80 != 0);
+        if hi > 9 {
+            hi += 6;
+        }
+        self.set_flag(C, hi > 0x0f);
+        self.regs.a = ((hi << 4) as u8) | (lo as u8 & 0x0f);
+    }
+
+    fn sbc(&mut self, value: u8) {
+        let a = self.regs.a;
+        let borrow = 1 - (self.regs.flags & C) as i16;
+        let binary = a as i16 - value as i16 - borrow;
+        self.set_flag(C, binary >= 0);
+        self.set_flag(V, ((a ^ value) & (a ^ binary as u8)) & 0x80 != 0);
+        self.set_nz(binary as u8);
+        if !self.flag(D) {
+            self.regs.a = binary as u8;
+            return;
+        }
+
+        // Decimal mode. The flags are those of the binary subtraction.
+        let mut lo = (a & 0x0f) as i16 - (value & 0x0f) as i16 - borrow;
+        let mut hi = (a >> 4) as i16 - (value >> 4) as i16;
+        if lo < 0 {
+            lo -= 6;
+            hi -= 1;
+        }
+        if hi < 0 {
+            hi -= 6;
+        }
+        self.regs.a = ((hi << 4) as u8) | (lo as u8 & 0x0f);
+    }
+
+    fn compare(&mut self, reg: u8, value: u8) {
+        self.set_flag(C, reg >= value);
+        self.set_nz(reg.wrapping_sub(value));
+    }
+
+    fn push(&mut self, bus: &mut impl Bus, value: u8) {
+        bus.write(0x100 | self.regs.s as u16, value);
+        self.regs.s = self.regs.s.wrapping_sub(1);
+    }
+
+    fn pull(&mut self, bus: &mut impl Bus) -> u8 {
+        self.regs.s = self.regs.s.wrapping_add(1);
+        bus.read(0x100 | self.regs.s as u16)
+    }
+
+    fn flag(&self, mask: u8) -> bool {
+        self.regs.flags & mask != 0
+    }
+
+    fn set_flag(&mut self, mask: u8, set: bool) {
+        if set {
+            self.regs.flags |= mask;
+        } else {
+            self.regs.flags &= !mask;
+        }
+    }
+
+    fn set_nz(&mut self, value: u8) {
+        self.set_flag(Z, value == 0);
+        self.set_flag(N, value & 0x80 != 0);
+    }
+}
+
+fn read_word(bus: &mut impl Bus, addr: u16) -> u16 {
+    u16::from_le_bytes([bus.read(addr), bus.read(addr.wrapping_add(1))])
+}
+
+/// Reads a pointer from the zero page, wrapping within it.
+fn read_zp_word(bus: &mut impl Bus, addr: u16) -> u16 {
+    u16::from_le_bytes([bus.read(addr), bus.read((addr + 1) & 0xff)])
+}
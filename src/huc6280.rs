@@ -0,0 +1,162 @@
+use gdbstub::arch::{Arch, Registers};
+
+use crate::{
+    reg_info, xml, MosBreakpointKind, MosRegId, MosRegs, RegAccess, RegInfo, DEFAULT_RC_COUNT,
+};
+
+/// Implements `Arch` for the Hudson HuC6280 (PC Engine / TurboGrafx-16) with
+/// `RC` llvm-mos imaginary registers.
+///
+/// Addresses are logical addresses, as translated by the MPRs at the time of
+/// the access.
+///
+/// Unlike the other 6502 variants, it doesn't implement
+/// [`MosVariant`](crate::MosVariant): none of the [`InstructionSet`]s
+/// decodes the HuC6280's own instructions, such as the block transfers,
+/// `TAM`/`TMA`, `ST0`–`ST2` or `BSR`, so stepping and disassembly would get
+/// their lengths and targets wrong.
+///
+/// [`InstructionSet`]: crate::InstructionSet
+pub enum HuC6280Arch<const RC: usize = DEFAULT_RC_COUNT> {}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct HuC6280Regs<const RC: usize = DEFAULT_RC_COUNT> {
+    /// Registers shared with the 6502. Bit 5 of `flags` is the T flag, which
+    /// makes ALU instructions operate on the zero page byte at X instead of A.
+    pub base: MosRegs<RC>,
+    /// Memory mapping registers. MPR`n` selects the 8K physical page mapped
+    /// at logical address `n * 0x2000`.
+    pub mpr: [u8; 8],
+    /// Set after `CSH` (7.16 MHz), clear after `CSL` (1.79 MHz).
+    pub high_speed: bool,
+}
+
+impl<const RC: usize> Default for HuC6280Regs<RC> {
+    fn default() -> Self {
+        HuC6280Regs {
+            base: MosRegs::default(),
+            mpr: [0; 8],
+            high_speed: false,
+        }
+    }
+}
+
+impl<const RC: usize> HuC6280Regs<RC> {
+    /// Translates a logical address to a 21-bit physical address through the
+    /// MPRs.
+    pub fn physical_address(&self, addr: u16) -> u32 {
+        let page = self.mpr[(addr >> 13) as usize] as u32;
+        (page << 13) | (addr & 0x1fff) as u32
+    }
+}
+
+impl<const RC: usize> RegAccess for HuC6280Regs<RC> {
+    type RegId = HuC6280RegId<RC>;
+    type Value = u16;
+
+    fn get_reg(&self, reg: &HuC6280RegId<RC>) -> Option<u16> {
+        match *reg {
+            HuC6280RegId::Mos(ref reg) => self.base.get_reg(reg),
+            HuC6280RegId::MPR(n) => self.mpr.get(n).map(|v| *v as u16),
+            HuC6280RegId::Speed => Some(self.high_speed as u16),
+        }
+    }
+
+    fn set_reg(&mut self, reg: &HuC6280RegId<RC>, value: u16) -> Option<()> {
+        match *reg {
+            HuC6280RegId::Mos(ref reg) => return self.base.set_reg(reg, value),
+            HuC6280RegId::MPR(n) => *self.mpr.get_mut(n)? = u8::try_from(value).ok()?,
+            HuC6280RegId::Speed => match value {
+                0 | 1 => self.high_speed = value != 0,
+                _ => return None,
+            },
+        }
+        Some(())
+    }
+}
+
+impl<const RC: usize> Registers for HuC6280Regs<RC> {
+    type ProgramCounter = u16;
+
+    fn pc(&self) -> Self::ProgramCounter {
+        self.base.pc
+    }
+
+    fn gdb_serialize(&self, write_byte: impl FnMut(Option<u8>)) {
+        reg_info::serialize(self, write_byte)
+    }
+
+    fn gdb_deserialize(&mut self, bytes: &[u8]) -> Result<(), ()> {
+        reg_info::deserialize(self, bytes)
+    }
+}
+
+/// Register ids of [`HuC6280Arch`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum HuC6280RegId<const RC: usize = DEFAULT_RC_COUNT> {
+    /// A register shared with the 6502. [`MosRegId::U`] is the T flag.
+    Mos(MosRegId<RC>),
+    /// Memory mapping register MPR0–MPR7.
+    MPR(usize),
+    /// CPU speed flag, set in high speed mode.
+    Speed,
+}
+
+impl<const RC: usize> HuC6280RegId<RC> {
+    /// Registers following the 6502 register set.
+    const TAIL: [(Self, RegInfo); 9] = [
+        (HuC6280RegId::MPR(0), RegInfo::new("MPR", 8).indexed(0)),
+        (HuC6280RegId::MPR(1), RegInfo::new("MPR", 8).indexed(1)),
+        (HuC6280RegId::MPR(2), RegInfo::new("MPR", 8).indexed(2)),
+        (HuC6280RegId::MPR(3), RegInfo::new("MPR", 8).indexed(3)),
+        (HuC6280RegId::MPR(4), RegInfo::new("MPR", 8).indexed(4)),
+        (HuC6280RegId::MPR(5), RegInfo::new("MPR", 8).indexed(5)),
+        (HuC6280RegId::MPR(6), RegInfo::new("MPR", 8).indexed(6)),
+        (HuC6280RegId::MPR(7), RegInfo::new("MPR", 8).indexed(7)),
+        (HuC6280RegId::Speed, RegInfo::new("SPEED", 1)),
+    ];
+
+    /// Number of registers in the target description.
+    pub const COUNT: usize = MosRegId::<RC>::COUNT + Self::TAIL.len();
+
+    /// Size of a complete `g` packet payload in bytes.
+    pub const PACKET_SIZE: usize =
+        MosRegId::<RC>::PACKET_SIZE + reg_info::packed_size(&Self::TAIL, Self::TAIL.len());
+
+    /// Returns the register with register number `regnum` along with its
+    /// description.
+    ///
+    /// The 6502 registers keep their numbers and offsets, with the U bit of
+    /// the 6502 named `T`.
+    pub const fn lookup(regnum: usize) -> Option<(Self, RegInfo)> {
+        if let Some((reg, mut info)) = MosRegId::<RC>::lookup(regnum) {
+            if matches!(reg, MosRegId::U) {
+                info.name = "T";
+            }
+            Some((HuC6280RegId::Mos(reg), info))
+        } else if regnum < Self::COUNT {
+            let first = MosRegId::<RC>::COUNT;
+            let base = MosRegId::<RC>::PACKET_SIZE;
+            Some(reg_info::place(&Self::TAIL, regnum - first, first, base))
+        } else {
+            None
+        }
+    }
+}
+
+reg_info::reg_table!(HuC6280RegId);
+
+impl<const RC: usize> HuC6280Arch<RC> {
+    xml::target_xml!(huc6280_target_xml());
+}
+
+impl<const RC: usize> Arch for HuC6280Arch<RC> {
+    type Usize = u16;
+    type Registers = HuC6280Regs<RC>;
+    type RegId = HuC6280RegId<RC>;
+    type BreakpointKind = MosBreakpointKind;
+
+    fn target_description_xml() -> Option<&'static str> {
+        Some(Self::TARGET_XML_STR)
+    }
+}
@@ -0,0 +1,88 @@
+use crate::{Bus, MosRegId, MosRegs, RegAccess, RegTable};
+
+/// Location of the llvm-mos imaginary registers in target memory.
+///
+/// On llvm-mos, RC0–RC`n` are ordinary zero page bytes. A target that
+/// routes register accesses through `ImaginaryRegs` reads them from memory
+/// when GDB reads registers and writes them to memory when GDB writes
+/// registers, so the register and memory views of the bytes always agree.
+/// `MosRegs::rc` then only holds a copy of the memory contents.
+///
+/// The base address usually comes from the `__rc0` symbol, see
+/// [`Elf::rc_base`](crate::loader::Elf::rc_base).
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct ImaginaryRegs {
+    /// Address of RC0. RC`n` is at `base + n`, wrapping at $FFFF.
+    pub base: u16,
+}
+
+impl ImaginaryRegs {
+    pub const fn new(base: u16) -> Self {
+        ImaginaryRegs { base }
+    }
+
+    /// Returns the address of the first byte of `reg`, or `None` if it isn't
+    /// an imaginary register.
+    pub fn address<const RC: usize>(&self, reg: &MosRegId<RC>) -> Option<u16> {
+        let n = match *reg {
+            MosRegId::RC(n) if n < RC => n,
+            MosRegId::RS(n) if n < RC / 2 => 2 * n,
+            _ => return None,
+        };
+        Some(self.base.wrapping_add(n as u16))
+    }
+
+    /// Copies the imaginary registers from memory into `regs.rc`.
+    pub fn load<const RC: usize>(&self, bus: &mut impl Bus, regs: &mut MosRegs<RC>) {
+        for (addr, byte) in self.addresses().zip(&mut regs.rc) {
+            *byte = bus.peek(addr);
+        }
+    }
+
+    /// Copies `regs.rc` to the imaginary registers in memory.
+    pub fn store<const RC: usize>(&self, bus: &mut impl Bus, regs: &MosRegs<RC>) {
+        for (addr, byte) in self.addresses().zip(&regs.rc) {
+            bus.poke(addr, *byte);
+        }
+    }
+
+    /// Like [`MosRegs::read_reg`], reading imaginary registers from memory.
+    /// `regs.rc` is refreshed from memory.
+    pub fn read_reg<const RC: usize>(
+        &self,
+        bus: &mut impl Bus,
+        regs: &mut MosRegs<RC>,
+        reg: &MosRegId<RC>,
+        buf: &mut [u8],
+    ) -> Option<usize> {
+        if self.address(reg).is_some() {
+            self.load(bus, regs);
+        }
+        regs.read_reg(reg, buf)
+    }
+
+    /// Like [`MosRegs::write_reg`], writing imaginary registers to memory.
+    /// `regs.rc` is updated along with memory.
+    pub fn write_reg<const RC: usize>(
+        &self,
+        bus: &mut impl Bus,
+        regs: &mut MosRegs<RC>,
+        reg: &MosRegId<RC>,
+        val: &[u8],
+    ) -> Option<()> {
+        regs.write_reg(reg, val)?;
+        if let Some(addr) = self.address(reg) {
+            let first = addr.wrapping_sub(self.base) as usize;
+            let bytes = &regs.rc[first..first + reg.info()?.size()];
+            for (addr, byte) in self.addresses().skip(first).zip(bytes) {
+                bus.poke(addr, *byte);
+            }
+        }
+        Some(())
+    }
+
+    /// Addresses of RC0, RC1, ... in order.
+    fn addresses(&self) -> impl Iterator<Item = u16> {
+        (self.base..=u16::MAX).chain(0..)
+    }
+}
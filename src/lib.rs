@@ -1,13 +1,66 @@
-use core::num::NonZeroUsize;
+#![cfg_attr(not(feature = "std"), no_std)]
 
-use gdbstub::arch::{Arch, RegId, Registers, SingleStepGdbBehavior};
+use gdbstub::arch::{Arch, Registers};
 
-/// Implements `Arch` for ARMv4T
-pub enum MOSArch {}
+mod banked;
+mod bus;
+#[cfg(feature = "gdbstub_06")]
+mod compat_06;
+pub mod disasm;
+#[cfg(feature = "emulator")]
+pub mod emulator;
+mod huc6280;
+mod imaginary;
+pub mod loader;
+mod m45gs02;
+pub mod memory_map;
+mod opcode;
+mod reg_info;
+mod step;
+mod w65816;
+mod w65c02;
+mod xml;
 
-#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
-pub struct MosRegs {
-    pub rc: [u8; 32],
+pub use banked::{BankWindow, BankedAddress, MosBankedArch, MosBankedRegId, MosBankedRegs};
+pub use bus::Bus;
+pub use huc6280::{HuC6280Arch, HuC6280RegId, HuC6280Regs};
+pub use imaginary::ImaginaryRegs;
+pub use m45gs02::{M45GS02Address, Mos45GS02Arch, Mos45GS02RegId, Mos45GS02Regs};
+pub use reg_info::{RegAccess, RegInfo, RegTable};
+pub use step::{next_pcs, next_pcs_for, NextPcs, IRQ_VECTOR, NMI_VECTOR, RESET_VECTOR};
+pub use w65816::{W65816Arch, W65816RegId, W65816Regs};
+pub use w65c02::Mos65C02Arch;
+
+/// Number of llvm-mos imaginary registers (RC0–RC31) used by default.
+pub const DEFAULT_RC_COUNT: usize = 32;
+
+/// Instruction set of a 6502-family CPU, for tooling that decodes
+/// instructions such as single-stepping and disassembly.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum InstructionSet {
+    /// The original NMOS 6502.
+    Nmos6502,
+    /// The WDC W65C02S: the CMOS additions (BRA, PHX/PLX, PHY/PLY, STZ,
+    /// TRB/TSB, ...), the Rockwell bit instructions (RMB/SMB/BBR/BBS) and
+    /// WAI/STP.
+    W65C02,
+}
+
+/// Implemented by the `Arch` types of 6502-family CPUs, giving generic code
+/// such as [`next_pcs_for`] their instruction set.
+pub trait MosVariant: Arch {
+    /// Instruction set executed by the CPU.
+    const INSTRUCTION_SET: InstructionSet;
+}
+
+/// Implements `Arch` for the MOS 6502 with `RC` llvm-mos imaginary registers.
+///
+/// `RC` must be even, as every pair of RC registers forms one RS register.
+pub enum MOSArch<const RC: usize = DEFAULT_RC_COUNT> {}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct MosRegs<const RC: usize = DEFAULT_RC_COUNT> {
+    pub rc: [u8; RC],
     pub pc: u16,
     pub a: u8,
     pub x: u8,
@@ -16,51 +69,110 @@ pub struct MosRegs {
     pub flags: u8,
 }
 
-impl Registers for MosRegs {
-    type ProgramCounter = u16;
+impl<const RC: usize> Default for MosRegs<RC> {
+    fn default() -> Self {
+        MosRegs {
+            rc: [0; RC],
+            pc: 0,
+            a: 0,
+            x: 0,
+            y: 0,
+            s: 0,
+            flags: 0,
+        }
+    }
+}
 
-    fn pc(&self) -> Self::ProgramCounter {
-        self.pc
+impl<const RC: usize> MosRegs<RC> {
+    /// Returns the 16-bit imaginary register RS`n`, composed of RC`2n` (low
+    /// byte) and RC`2n+1` (high byte).
+    pub fn rs(&self, n: usize) -> u16 {
+        u16::from_le_bytes([self.rc[2 * n], self.rc[2 * n + 1]])
     }
 
-    fn gdb_serialize(&self, mut write_byte: impl FnMut(Option<u8>)) {
-        macro_rules! write_bytes {
-            ($bytes:expr) => {
-                for b in $bytes {
-                    write_byte(Some(*b))
+    /// Sets the 16-bit imaginary register RS`n`, updating RC`2n` and
+    /// RC`2n+1`.
+    pub fn set_rs(&mut self, n: usize, value: u16) {
+        let [lo, hi] = value.to_le_bytes();
+        self.rc[2 * n] = lo;
+        self.rc[2 * n + 1] = hi;
+    }
+}
+
+impl<const RC: usize> RegAccess for MosRegs<RC> {
+    type RegId = MosRegId<RC>;
+    type Value = u16;
+
+    fn get_reg(&self, reg: &MosRegId<RC>) -> Option<u16> {
+        if let Some(bit) = reg.flag_bit() {
+            return Some(((self.flags >> bit) & 1) as u16);
+        }
+        let value = match *reg {
+            MosRegId::PC => self.pc,
+            MosRegId::A => self.a as u16,
+            MosRegId::X => self.x as u16,
+            MosRegId::Y => self.y as u16,
+            MosRegId::S => self.s as u16,
+            MosRegId::RC(n) => *self.rc.get(n)? as u16,
+            MosRegId::RS(n) if n < self.rc.len() / 2 => self.rs(n),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    fn set_reg(&mut self, reg: &MosRegId<RC>, value: u16) -> Option<()> {
+        if let Some(bit) = reg.flag_bit() {
+            if value > 1 {
+                return None;
+            }
+            self.flags = (self.flags & !(1 << bit)) | ((value as u8) << bit);
+            return Some(());
+        }
+        match *reg {
+            MosRegId::PC => self.pc = value,
+            MosRegId::RS(n) if n < self.rc.len() / 2 => self.set_rs(n, value),
+            _ => {
+                let byte = u8::try_from(value).ok()?;
+                match *reg {
+                    MosRegId::A => self.a = byte,
+                    MosRegId::X => self.x = byte,
+                    MosRegId::Y => self.y = byte,
+                    MosRegId::S => self.s = byte,
+                    MosRegId::RC(n) => *self.rc.get_mut(n)? = byte,
+                    _ => return None,
                 }
-            };
+            }
         }
-        write_bytes!(&self.pc.to_le_bytes());
-        write_bytes!(&self.a.to_le_bytes());
-        write_bytes!(&self.x.to_le_bytes());
-        write_bytes!(&self.y.to_le_bytes());
-        write_bytes!(&self.s.to_le_bytes());
-        write_bytes!(&(self.flags & 1).to_le_bytes());
-        write_bytes!(&((self.flags >> 1) & 1).to_le_bytes());
-        write_bytes!(&((self.flags >> 6) & 1).to_le_bytes());
-        write_bytes!(&((self.flags >> 7) & 1).to_le_bytes());
-
-        self.rc.iter().for_each(|v| write_byte(Some(*v)));
+        Some(())
     }
+}
 
-    fn gdb_deserialize(&mut self, bytes: &[u8]) -> Result<(), ()> {
-        self.pc = bytes[0] as u16 + bytes[1] as u16 * 256;
-        self.a = bytes[2];
-        self.x = bytes[3];
-        self.y = bytes[4];
-        self.s = bytes[5];
+impl<const RC: usize> Registers for MosRegs<RC> {
+    type ProgramCounter = u16;
 
-        self.flags &= 0b00111100;
-        self.flags |= bytes[6] | bytes[7] * 2 | bytes[8] * 64 + bytes[9] * 128;
+    fn pc(&self) -> Self::ProgramCounter {
+        self.pc
+    }
 
-        self.rc.iter_mut().enumerate().for_each(|(i, v)| *v = bytes[10 + i]);
-        Ok(())
+    fn gdb_serialize(&self, write_byte: impl FnMut(Option<u8>)) {
+        reg_info::serialize(self, write_byte)
+    }
+
+    fn gdb_deserialize(&mut self, bytes: &[u8]) -> Result<(), ()> {
+        // Older clients don't know about the registers after the imaginary
+        // registers and stop before them; keep their current values then.
+        let legacy_size = MosRegId::<RC>::PACKET_SIZE - MosRegId::<RC>::TAIL_SIZE;
+        if bytes.len() != MosRegId::<RC>::PACKET_SIZE && bytes.len() != legacy_size {
+            return Err(());
+        }
+        reg_info::update(self, bytes)
     }
 }
 
-#[derive(Debug)]
-pub enum MosRegId {
+/// Register ids of [`MOSArch`]. The imaginary registers that exist depend on
+/// `RC`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MosRegId<const RC: usize = DEFAULT_RC_COUNT> {
     RC(usize),
     RS(usize),
     PC,
@@ -72,127 +184,168 @@ pub enum MosRegId {
     Z,
     N,
     V,
+    /// Decimal mode flag.
+    D,
+    /// Interrupt disable flag.
+    I,
+    /// Break flag, only meaningful in a copy of P pushed to the stack.
+    B,
+    /// Unused bit 5 of P, reads as 1 when pushed.
+    U,
 }
 
-impl RegId for MosRegId {
-    fn from_raw_id(id: usize) -> Option<(Self, Option<NonZeroUsize>)> {
-        let (reg, size) = match id {
-            0 => (MosRegId::PC, 2),
-            1 => (MosRegId::A, 1),
-            2 => (MosRegId::X, 1),
-            3 => (MosRegId::Y, 1),
-            4 => (MosRegId::S, 1),
-            5 => (MosRegId::C, 1),
-            6 => (MosRegId::Z, 1),
-            7 => (MosRegId::N, 1),
-            8 => (MosRegId::V, 1),
-            9..=40 => (MosRegId::RC(id-9), 1),
-            41..=56 => (MosRegId::RS(id-41), 2),
-            _ => return None,
-        };
-        return Some((reg, Some(NonZeroUsize::new(size).unwrap())));
+impl<const RC: usize> MosRegId<RC> {
+    /// Registers preceding the imaginary registers, in register number and
+    /// `g` packet order.
+    const HEAD: [(Self, RegInfo); 9] = [
+        (MosRegId::PC, RegInfo::new("PC", 16).generic("pc")),
+        (MosRegId::A, RegInfo::new("A", 8).dwarf(0)),
+        (MosRegId::X, RegInfo::new("X", 8).dwarf(2)),
+        (MosRegId::Y, RegInfo::new("Y", 8).dwarf(4)),
+        (MosRegId::S, RegInfo::new("S", 8)),
+        (MosRegId::C, RegInfo::new("C", 1)),
+        (MosRegId::Z, RegInfo::new("Z", 1)),
+        (MosRegId::V, RegInfo::new("V", 1)),
+        (MosRegId::N, RegInfo::new("N", 1)),
+    ];
+
+    /// Registers following the imaginary registers. They were added after
+    /// the original register set, so they come last to keep the layout older
+    /// clients expect.
+    const TAIL: [(Self, RegInfo); 4] = [
+        (MosRegId::D, RegInfo::new("D", 1)),
+        (MosRegId::I, RegInfo::new("I", 1)),
+        (MosRegId::B, RegInfo::new("B", 1)),
+        (MosRegId::U, RegInfo::new("U", 1)),
+    ];
+
+    const HEAD_SIZE: usize = reg_info::packed_size(&Self::HEAD, Self::HEAD.len());
+    const TAIL_SIZE: usize = reg_info::packed_size(&Self::TAIL, Self::TAIL.len());
+
+    /// Number of registers in the target description.
+    pub const COUNT: usize = Self::HEAD.len() + RC + RC / 2 + Self::TAIL.len();
+
+    /// Size of a complete `g` packet payload in bytes.
+    pub const PACKET_SIZE: usize = Self::HEAD_SIZE + RC + Self::TAIL_SIZE;
+
+    /// Returns the register with register number `regnum` along with its
+    /// description.
+    pub const fn lookup(regnum: usize) -> Option<(Self, RegInfo)> {
+        assert!(
+            RC.is_multiple_of(2),
+            "number of imaginary registers must be even"
+        );
+        let rc = Self::HEAD.len();
+        let rs = rc + RC;
+        let tail = rs + RC / 2;
+        if regnum < rc {
+            Some(reg_info::place(&Self::HEAD, regnum, 0, 0))
+        } else if regnum < rs {
+            let i = regnum - rc;
+            let info = reg_info::rc_info(i).at(regnum, Self::HEAD_SIZE + i);
+            Some((MosRegId::RC(i), info))
+        } else if regnum < tail {
+            let i = regnum - rs;
+            let info = reg_info::rs_info(i).at(regnum, Self::HEAD_SIZE + 2 * i);
+            Some((MosRegId::RS(i), info))
+        } else if regnum < Self::COUNT {
+            let base = Self::HEAD_SIZE + RC;
+            Some(reg_info::place(&Self::TAIL, regnum - tail, tail, base))
+        } else {
+            None
+        }
+    }
+
+    /// Bit position within P, for registers that are status flags.
+    fn flag_bit(&self) -> Option<u8> {
+        match self {
+            MosRegId::C => Some(0),
+            MosRegId::Z => Some(1),
+            MosRegId::I => Some(2),
+            MosRegId::D => Some(3),
+            MosRegId::B => Some(4),
+            MosRegId::U => Some(5),
+            MosRegId::V => Some(6),
+            MosRegId::N => Some(7),
+            _ => None,
+        }
     }
 }
 
-#[derive(Debug)]
+reg_info::reg_table!(MosRegId);
+
+/// Breakpoint kinds understood by the 6502-family architectures.
+///
+/// The kind is the number sent by the client in the `Z0`/`Z1` packets:
+///
+/// | kind              | variant                     |
+/// |-------------------|-----------------------------|
+/// | `0`, `1`          | [`Software`](Self::Software) |
+/// | `2`               | [`Hardware`](Self::Hardware) |
+/// | `0x100 + bank`    | [`Banked`](Self::Banked), for `bank` in `0..=0xffff` |
+///
+/// `1` is the length of `BRK`, which is what clients send by default.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum MosBreakpointKind {
-    /// 16-bit Thumb mode breakpoint.
-    Regular,
+    /// Breakpoint implemented by patching a `BRK` opcode into memory.
+    Software,
+    /// Breakpoint implemented by the emulator or debug hardware, without
+    /// touching memory. Use this for code in ROM.
+    Hardware,
+    /// Breakpoint that only triggers when the address is reached with the
+    /// given memory bank mapped in. Like [`Hardware`](Self::Hardware), it
+    /// doesn't touch memory.
+    Banked(u16),
+}
+
+impl MosBreakpointKind {
+    /// Kind number of the first bank-qualified breakpoint.
+    pub const BANKED_BASE: usize = 0x100;
+
+    /// Returns the kind number a client sends for this breakpoint.
+    pub fn to_usize(self) -> usize {
+        match self {
+            MosBreakpointKind::Software => 1,
+            MosBreakpointKind::Hardware => 2,
+            MosBreakpointKind::Banked(bank) => Self::BANKED_BASE + bank as usize,
+        }
+    }
+
+    /// Whether the breakpoint is implemented by writing to target memory.
+    pub fn patches_memory(self) -> bool {
+        self == MosBreakpointKind::Software
+    }
 }
 
 impl gdbstub::arch::BreakpointKind for MosBreakpointKind {
-    fn from_usize(_kind: usize) -> Option<Self> {
-        Some(MosBreakpointKind::Regular)
+    fn from_usize(kind: usize) -> Option<Self> {
+        let kind = match kind {
+            0 | 1 => MosBreakpointKind::Software,
+            2 => MosBreakpointKind::Hardware,
+            _ => {
+                let bank = kind.checked_sub(Self::BANKED_BASE)?;
+                MosBreakpointKind::Banked(u16::try_from(bank).ok()?)
+            }
+        };
+        Some(kind)
     }
 }
 
-impl Arch for MOSArch {
+impl<const RC: usize> MOSArch<RC> {
+    xml::target_xml!(mos_target_xml("mos"));
+}
+
+impl<const RC: usize> Arch for MOSArch<RC> {
     type Usize = u16;
-    type Registers = MosRegs;
-    type RegId = MosRegId;
+    type Registers = MosRegs<RC>;
+    type RegId = MosRegId<RC>;
     type BreakpointKind = MosBreakpointKind;
 
     fn target_description_xml() -> Option<&'static str> {
-        Some(r#"
-        <?xml version="1.0"?>
-        <!DOCTYPE target SYSTEM "gdb-target.dtd">
-        <target version="1.0">
-            <architecture>mos</architecture>
-            <flags id="flags" size="1">
-                <field name="C" start="0" end="0" type="bool" />
-                <field name="Z" start="1" end="1" type="bool" />
-                <field name="V" start="6" end="6" type="bool" />
-                <field name="N" start="7" end="7" type="bool" />
-            </flags>
-            <groups>
-                <group id="1" name="imaginary, 8-bit"></group>
-                <group id="2" name="imaginary, 16-bit"></group>
-            </groups>
-            <feature name="org.gnu.gdb.mos">
-                <reg name="PC" bitsize="16" offset="0" regnum="0" generic="pc" />
-                <reg name="A" bitsize="8" offset="2" regnum="1" dwarf_regnum="0" />
-                <reg name="X" bitsize="8" offset="3" regnum="2" dwarf_regnum="2" />
-                <reg name="Y" bitsize="8" offset="4" regnum="3" dwarf_regnum="4" />
-                <reg name="S" bitsize="8" offset="5" regnum="4" />
-                <reg name="C" bitsize="1" offset="6" regnum="5" />
-                <reg name="Z" bitsize="1" offset="7" regnum="6" />
-                <reg name="V" bitsize="1" offset="8" regnum="7" />
-                <reg name="N" bitsize="1" offset="9" regnum="8" />
-                <reg name="RC0" group_id="1" bitsize="8" offset="10" regnum="9" dwarf_regnum="16" />
-                <reg name="RC1" group_id="1" bitsize="8" offset="11" regnum="10" dwarf_regnum="18" />
-                <reg name="RC2" group_id="1" bitsize="8" offset="12" regnum="11" dwarf_regnum="20" />
-                <reg name="RC3" group_id="1" bitsize="8" offset="13" regnum="12" dwarf_regnum="22" />
-                <reg name="RC4" group_id="1" bitsize="8" offset="14" regnum="13" dwarf_regnum="24" />
-                <reg name="RC5" group_id="1" bitsize="8" offset="15" regnum="14" dwarf_regnum="26" />
-                <reg name="RC6" group_id="1" bitsize="8" offset="16" regnum="15" dwarf_regnum="28" />
-                <reg name="RC7" group_id="1" bitsize="8" offset="17" regnum="16" dwarf_regnum="30" />
-                <reg name="RC8" group_id="1" bitsize="8" offset="18" regnum="17" dwarf_regnum="32" />
-                <reg name="RC9" group_id="1" bitsize="8" offset="19" regnum="18" dwarf_regnum="34" />
-                <reg name="RC10" group_id="1" bitsize="8" offset="20" regnum="19" dwarf_regnum="36" />
-                <reg name="RC11" group_id="1" bitsize="8" offset="21" regnum="20" dwarf_regnum="38" />
-                <reg name="RC12" group_id="1" bitsize="8" offset="22" regnum="21" dwarf_regnum="40" />
-                <reg name="RC13" group_id="1" bitsize="8" offset="23" regnum="22" dwarf_regnum="42" />
-                <reg name="RC14" group_id="1" bitsize="8" offset="24" regnum="23" dwarf_regnum="44" />
-                <reg name="RC15" group_id="1" bitsize="8" offset="25" regnum="24" dwarf_regnum="46" />
-                <reg name="RC16" group_id="1" bitsize="8" offset="26" regnum="25" dwarf_regnum="48" />
-                <reg name="RC17" group_id="1" bitsize="8" offset="27" regnum="26" dwarf_regnum="50" />
-                <reg name="RC18" group_id="1" bitsize="8" offset="28" regnum="27" dwarf_regnum="52" />
-                <reg name="RC19" group_id="1" bitsize="8" offset="29" regnum="28" dwarf_regnum="54" />
-                <reg name="RC20" group_id="1" bitsize="8" offset="30" regnum="29" dwarf_regnum="56" />
-                <reg name="RC21" group_id="1" bitsize="8" offset="31" regnum="30" dwarf_regnum="58" />
-                <reg name="RC22" group_id="1" bitsize="8" offset="32" regnum="31" dwarf_regnum="60" />
-                <reg name="RC23" group_id="1" bitsize="8" offset="33" regnum="32" dwarf_regnum="62" />
-                <reg name="RC24" group_id="1" bitsize="8" offset="34" regnum="33" dwarf_regnum="64" />
-                <reg name="RC25" group_id="1" bitsize="8" offset="35" regnum="34" dwarf_regnum="66" />
-                <reg name="RC26" group_id="1" bitsize="8" offset="36" regnum="35" dwarf_regnum="68" />
-                <reg name="RC27" group_id="1" bitsize="8" offset="37" regnum="36" dwarf_regnum="70" />
-                <reg name="RC28" group_id="1" bitsize="8" offset="38" regnum="37" dwarf_regnum="72" />
-                <reg name="RC29" group_id="1" bitsize="8" offset="39" regnum="38" dwarf_regnum="74" />
-                <reg name="RC30" group_id="1" bitsize="8" offset="40" regnum="39" dwarf_regnum="76" />
-                <reg name="RC31" group_id="1" bitsize="8" offset="41" regnum="40" dwarf_regnum="78" />
-                <reg name="RS0" group_id="2" bitsize="16" offset="10" regnum="41" dwarf_regnum="528" />
-                <reg name="RS1" group_id="2" bitsize="16" offset="12" regnum="42" dwarf_regnum="529" />
-                <reg name="RS2" group_id="2" bitsize="16" offset="14" regnum="43" dwarf_regnum="530" />
-                <reg name="RS3" group_id="2" bitsize="16" offset="16" regnum="44" dwarf_regnum="531" />
-                <reg name="RS4" group_id="2" bitsize="16" offset="18" regnum="45" dwarf_regnum="532" />
-                <reg name="RS5" group_id="2" bitsize="16" offset="20" regnum="46" dwarf_regnum="533" />
-                <reg name="RS6" group_id="2" bitsize="16" offset="22" regnum="47" dwarf_regnum="534" />
-                <reg name="RS7" group_id="2" bitsize="16" offset="24" regnum="48" dwarf_regnum="535" />
-                <reg name="RS8" group_id="2" bitsize="16" offset="26" regnum="49" dwarf_regnum="536" />
-                <reg name="RS9" group_id="2" bitsize="16" offset="28" regnum="50" dwarf_regnum="537" />
-                <reg name="RS10" group_id="2" bitsize="16" offset="30" regnum="51" dwarf_regnum="538" />
-                <reg name="RS11" group_id="2" bitsize="16" offset="32" regnum="52" dwarf_regnum="539" />
-                <reg name="RS12" group_id="2" bitsize="16" offset="34" regnum="53" dwarf_regnum="540" />
-                <reg name="RS13" group_id="2" bitsize="16" offset="36" regnum="54" dwarf_regnum="541" />
-                <reg name="RS14" group_id="2" bitsize="16" offset="38" regnum="55" dwarf_regnum="542" />
-                <reg name="RS15" group_id="2" bitsize="16" offset="40" regnum="56" dwarf_regnum="543" />
-            </feature>
-        </target>
-        "#)
+        Some(Self::TARGET_XML_STR)
     }
+}
 
-    #[inline(always)]
-    fn single_step_gdb_behavior() -> SingleStepGdbBehavior {
-        SingleStepGdbBehavior::Optional
-    }
+impl<const RC: usize> MosVariant for MOSArch<RC> {
+    const INSTRUCTION_SET: InstructionSet = InstructionSet::Nmos6502;
 }
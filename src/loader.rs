@@ -0,0 +1,62 @@
+//! Loaders for 6502 executable formats. They parse borrowed bytes without
+//! allocating, and either yield the segments to place or write them to a
+//! [`Bus`] directly.
+
+use crate::Bus;
+
+mod elf;
+mod ihex;
+mod image;
+mod ines;
+mod o65;
+mod prg;
+mod srec;
+mod xex;
+
+pub use elf::{Elf, ElfError};
+pub use ihex::IntelHex;
+pub use image::{raw, ImageError, Record};
+pub use ines::{INes, INesError, Mirroring, CHR_BANK_SIZE, PRG_BANK_SIZE};
+pub use o65::{O65Bases, O65Error, Relocation, O65};
+pub use prg::{Prg, PrgError, BASIC_START};
+pub use srec::SRecord;
+pub use xex::{Xex, XexError, XexEvent, XexEvents, INITAD, RUNAD};
+
+/// A contiguous block of a program image.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Segment<'a> {
+    /// Address of the first byte.
+    pub address: u32,
+    pub data: &'a [u8],
+}
+
+impl Segment<'_> {
+    /// Address one past the last byte.
+    pub fn end(&self) -> u32 {
+        self.address.saturating_add(self.data.len() as u32)
+    }
+
+    /// Writes the segment to `bus` with [`Bus::poke`]. Returns `None`
+    /// without writing anything if it doesn't fit below $10000.
+    pub fn load(&self, bus: &mut impl Bus) -> Option<()> {
+        if self.end() > 0x10000 {
+            return None;
+        }
+        for (addr, byte) in (self.address as u16..=u16::MAX).zip(self.data) {
+            bus.poke(addr, *byte);
+        }
+        Some(())
+    }
+}
+
+/// Reads a little-endian `u16` at `offset`.
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset.checked_add(2)?)?;
+    Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+/// Reads a little-endian `u32` at `offset`.
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
@@ -0,0 +1,269 @@
+use core::fmt;
+
+use super::{u16_at, u32_at, Segment};
+use crate::Bus;
+
+/// ELF machine number of the 6502 family.
+const EM_MOS: u16 = 6502;
+
+const PT_LOAD: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const SHT_NOBITS: u32 = 8;
+const SHF_ALLOC: u32 = 2;
+
+const EHDR_SIZE: usize = 52;
+const PHDR_SIZE: usize = 32;
+const SHDR_SIZE: usize = 40;
+const SYM_SIZE: usize = 16;
+
+/// Error returned by [`Elf::parse`] and [`Elf::load`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ElfError {
+    /// The file doesn't start with the ELF magic.
+    NotElf,
+    /// The file isn't a 32-bit little-endian ELF file.
+    UnsupportedClass,
+    /// The file is for another machine; holds `e_machine`.
+    WrongMachine(u16),
+    /// A header or segment extends past the end of the file.
+    Truncated,
+    /// A segment doesn't fit in the 16-bit address space; holds its address.
+    OutOfRange(u32),
+}
+
+impl fmt::Display for ElfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ElfError::NotElf => f.write_str("not an ELF file"),
+            ElfError::UnsupportedClass => f.write_str("not a 32-bit little-endian ELF file"),
+            ElfError::WrongMachine(machine) => {
+                write!(f, "ELF file for machine {}, not MOS", machine)
+            }
+            ElfError::Truncated => f.write_str("truncated ELF file"),
+            ElfError::OutOfRange(addr) => {
+                write!(
+                    f,
+                    "segment at ${:X} is outside the 16-bit address space",
+                    addr
+                )
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ElfError {}
+
+/// A section header, as far as the loader is concerned.
+struct Section<'a> {
+    name: &'a [u8],
+    kind: u32,
+    flags: u32,
+    addr: u32,
+    offset: u32,
+    size: u32,
+    link: u32,
+}
+
+/// An llvm-mos ELF executable.
+///
+/// ```no_run
+/// # use gdbstub_mos_arch::loader::Elf;
+/// # let (bytes, mut mem) = (&[][..], [0u8; 0x10000]);
+/// let elf = Elf::parse(bytes)?;
+/// let entry = elf.load(&mut mem)?;
+/// let rc_base = elf.rc_base();
+/// # Ok::<(), gdbstub_mos_arch::loader::ElfError>(())
+/// ```
+#[derive(Debug, Copy, Clone)]
+pub struct Elf<'a> {
+    data: &'a [u8],
+    entry: u32,
+    phoff: usize,
+    phnum: usize,
+    shoff: usize,
+    shnum: usize,
+    shstrndx: usize,
+}
+
+impl<'a> Elf<'a> {
+    /// Parses the ELF header and checks that the file is a MOS executable.
+    pub fn parse(data: &'a [u8]) -> Result<Self, ElfError> {
+        if !data.starts_with(b"\x7fELF") {
+            return Err(ElfError::NotElf);
+        }
+        if data.len() < EHDR_SIZE {
+            return Err(ElfError::Truncated);
+        }
+        if data[4] != 1 || data[5] != 1 {
+            return Err(ElfError::UnsupportedClass);
+        }
+        let half = |offset| u16_at(data, offset).unwrap() as usize;
+        let word = |offset| u32_at(data, offset).unwrap();
+        let machine = half(18) as u16;
+        if machine != EM_MOS {
+            return Err(ElfError::WrongMachine(machine));
+        }
+        if (half(44) != 0 && half(42) != PHDR_SIZE) || (half(48) != 0 && half(46) != SHDR_SIZE) {
+            return Err(ElfError::UnsupportedClass);
+        }
+
+        let elf = Elf {
+            data,
+            entry: word(24),
+            phoff: word(28) as usize,
+            phnum: half(44),
+            shoff: word(32) as usize,
+            shnum: half(48),
+            shstrndx: half(50),
+        };
+        let table_fits = |offset: usize, count: usize, size: usize| {
+            offset
+                .checked_add(count * size)
+                .is_some_and(|end| end <= data.len())
+        };
+        if !table_fits(elf.phoff, elf.phnum, PHDR_SIZE)
+            || !table_fits(elf.shoff, elf.shnum, SHDR_SIZE)
+        {
+            return Err(ElfError::Truncated);
+        }
+        Ok(elf)
+    }
+
+    /// Entry point, usually `_start`.
+    pub fn entry(&self) -> u32 {
+        self.entry
+    }
+
+    /// Iterates over the contents of the loadable segments, at their load
+    /// addresses. Addresses above $FFFF are banked, in a target-specific
+    /// encoding. Zero-initialized memory isn't included.
+    pub fn segments(&self) -> impl Iterator<Item = Result<Segment<'a>, ElfError>> + 'a {
+        let Elf {
+            data, phoff, phnum, ..
+        } = *self;
+        (0..phnum).filter_map(move |i| {
+            let phdr = &data[phoff + i * PHDR_SIZE..][..PHDR_SIZE];
+            let word = |offset| u32_at(phdr, offset).unwrap();
+            if word(0) != PT_LOAD || word(16) == 0 {
+                return None;
+            }
+            let (offset, size) = (word(4) as usize, word(16) as usize);
+            let segment = offset
+                .checked_add(size)
+                .and_then(|end| data.get(offset..end))
+                .map(|data| Segment {
+                    address: word(12),
+                    data,
+                })
+                .ok_or(ElfError::Truncated);
+            Some(segment)
+        })
+    }
+
+    /// Writes the loadable segments to `bus` with [`Bus::poke`] and clears
+    /// `.bss` and `.zp.bss`, leaving `.noinit` alone. Returns the entry
+    /// point.
+    ///
+    /// Fails without writing anything if a segment is truncated or lies
+    /// outside the 16-bit address space.
+    pub fn load(&self, bus: &mut impl Bus) -> Result<u16, ElfError> {
+        for segment in self.segments() {
+            let segment = segment?;
+            if segment.address as u64 + segment.data.len() as u64 > 0x10000 {
+                return Err(ElfError::OutOfRange(segment.address));
+            }
+        }
+        let bss = || {
+            self.sections().filter(|s| {
+                s.kind == SHT_NOBITS
+                    && s.flags & SHF_ALLOC != 0
+                    && !s.name.starts_with(b".noinit")
+                    && !s.name.starts_with(b".zp.noinit")
+            })
+        };
+        if let Some(s) = bss().find(|s| s.addr as u64 + s.size as u64 > 0x10000) {
+            return Err(ElfError::OutOfRange(s.addr));
+        }
+        let entry = u16::try_from(self.entry).map_err(|_| ElfError::OutOfRange(self.entry))?;
+
+        for segment in self.segments() {
+            segment?.load(bus);
+        }
+        for s in bss() {
+            for addr in s.addr..s.addr + s.size {
+                bus.poke(addr as u16, 0);
+            }
+        }
+        Ok(entry)
+    }
+
+    /// Returns the value of the symbol `name` from the symbol table.
+    pub fn symbol(&self, name: &str) -> Option<u32> {
+        let symtab = self.sections().find(|s| s.kind == SHT_SYMTAB)?;
+        let strtab = self.section(symtab.link as usize)?;
+        let strings = self.contents(&strtab)?;
+        let symbols = self.contents(&symtab)?;
+        symbols
+            .chunks_exact(SYM_SIZE)
+            .find(|sym| {
+                let name_offset = u32_at(sym, 0).unwrap() as usize;
+                strings.get(name_offset..).map(cstr) == Some(name.as_bytes())
+            })
+            .map(|sym| u32_at(sym, 4).unwrap())
+    }
+
+    /// Zero page address of the imaginary register RC0, from the `__rc0`
+    /// symbol. RC`n` is at `rc_base() + n`.
+    pub fn rc_base(&self) -> Option<u16> {
+        self.symbol("__rc0")
+            .and_then(|addr| u16::try_from(addr).ok())
+    }
+
+    fn section(&self, index: usize) -> Option<Section<'a>> {
+        if index >= self.shnum {
+            return None;
+        }
+        let shdr = &self.data[self.shoff + index * SHDR_SIZE..][..SHDR_SIZE];
+        let word = |offset| u32_at(shdr, offset).unwrap();
+        let name = self
+            .section_name_table()
+            .and_then(|names| names.get(word(0) as usize..))
+            .map(cstr)
+            .unwrap_or_default();
+        Some(Section {
+            name,
+            kind: word(4),
+            flags: word(8),
+            addr: word(12),
+            offset: word(16),
+            size: word(20),
+            link: word(24),
+        })
+    }
+
+    fn section_name_table(&self) -> Option<&'a [u8]> {
+        if self.shstrndx >= self.shnum {
+            return None;
+        }
+        let shdr = &self.data[self.shoff + self.shstrndx * SHDR_SIZE..][..SHDR_SIZE];
+        let (offset, size) = (u32_at(shdr, 16)? as usize, u32_at(shdr, 20)? as usize);
+        self.data.get(offset..offset.checked_add(size)?)
+    }
+
+    fn sections(&self) -> impl Iterator<Item = Section<'a>> + '_ {
+        (0..self.shnum).filter_map(|i| self.section(i))
+    }
+
+    fn contents(&self, section: &Section) -> Option<&'a [u8]> {
+        let offset = section.offset as usize;
+        self.data
+            .get(offset..offset.checked_add(section.size as usize)?)
+    }
+}
+
+/// Returns the NUL-terminated string at the start of `bytes`.
+fn cstr(bytes: &[u8]) -> &[u8] {
+    let len = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    &bytes[..len]
+}
@@ -0,0 +1,87 @@
+use super::image::{self, Decoder, ImageError, Line, Record};
+use crate::Bus;
+
+/// An Intel HEX image (`.hex`, `.ihx`).
+///
+/// Extended segment and extended linear address records are supported as
+/// long as all data ends up below $10000. The image must end with an
+/// end-of-file record; anything after it is ignored.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct IntelHex<'a> {
+    text: &'a [u8],
+    start: Option<u16>,
+}
+
+impl<'a> IntelHex<'a> {
+    /// Parses and validates the image: record syntax, checksums, addresses
+    /// and overlapping data.
+    pub fn parse(text: &'a [u8]) -> Result<Self, ImageError> {
+        let start = image::validate::<IntelHexDecoder>(text)?;
+        Ok(IntelHex { text, start })
+    }
+
+    /// Iterates over the data records in file order.
+    pub fn records(&self) -> impl Iterator<Item = Record> + 'a {
+        image::records::<IntelHexDecoder>(self.text)
+    }
+
+    /// Start address from a start segment or start linear address record.
+    pub fn start_address(&self) -> Option<u16> {
+        self.start
+    }
+
+    /// Writes the data to `bus` with [`Bus::poke`].
+    pub fn load(&self, bus: &mut impl Bus) {
+        image::load::<IntelHexDecoder>(self.text, bus)
+    }
+}
+
+#[derive(Default)]
+struct IntelHexDecoder {
+    /// Address added to record addresses, from the last extended address
+    /// record.
+    base: u32,
+}
+
+impl Decoder for IntelHexDecoder {
+    fn decode(
+        &mut self,
+        line: &[u8],
+        number: usize,
+        record: &mut Record,
+    ) -> Result<Line, ImageError> {
+        let mut buf = [0; 5 + 255];
+        let bytes = line
+            .strip_prefix(b":")
+            .and_then(|hex| image::hex_bytes(hex, &mut buf))
+            .filter(|bytes| bytes.len() >= 5 && bytes.len() == bytes[0] as usize + 5)
+            .ok_or(ImageError::Syntax(number))?;
+        if bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)) != 0 {
+            return Err(ImageError::Checksum(number));
+        }
+        let offset = u16::from_be_bytes([bytes[1], bytes[2]]);
+        let data = &bytes[4..bytes.len() - 1];
+        let word = || u16::from_be_bytes([data[0], data[1]]) as u32;
+        let line = match (bytes[3], data.len()) {
+            (0x00, 0) => Line::Skip,
+            (0x00, _) => record.set(self.base + offset as u32, data)?,
+            (0x01, _) => Line::End(None),
+            (0x02, 2) => {
+                self.base = word() << 4;
+                Line::Skip
+            }
+            (0x03, 4) => {
+                let ip = u16::from_be_bytes([data[2], data[3]]) as u32;
+                Line::Start((word() << 4) + ip)
+            }
+            (0x04, 2) => {
+                self.base = word() << 16;
+                Line::Skip
+            }
+            (0x05, 4) => Line::Start(u32::from_be_bytes([data[0], data[1], data[2], data[3]])),
+            (0x02..=0x05, _) => return Err(ImageError::Syntax(number)),
+            _ => return Err(ImageError::UnsupportedRecord(number)),
+        };
+        Ok(line)
+    }
+}
@@ -0,0 +1,226 @@
+use core::fmt;
+
+use super::Segment;
+use crate::Bus;
+
+/// Maximum number of data bytes in a record.
+const MAX_RECORD_DATA: usize = 255;
+
+/// Error returned by the Intel HEX, S-record and raw binary loaders.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ImageError {
+    /// The line with this number (starting at 1) isn't a well-formed record.
+    Syntax(usize),
+    /// The record on this line has a bad checksum.
+    Checksum(usize),
+    /// The record on this line has an unknown or unsupported type.
+    UnsupportedRecord(usize),
+    /// Data or a start address lies outside the 16-bit address space; holds
+    /// the address.
+    OutOfRange(u32),
+    /// Data overwrites earlier data; holds the first address written twice.
+    Overlap(u16),
+    /// The image ends without an end-of-file or termination record.
+    MissingEnd,
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageError::Syntax(line) => write!(f, "line {}: malformed record", line),
+            ImageError::Checksum(line) => write!(f, "line {}: checksum mismatch", line),
+            ImageError::UnsupportedRecord(line) => {
+                write!(f, "line {}: unsupported record type", line)
+            }
+            ImageError::OutOfRange(addr) => {
+                write!(f, "address ${:X} is outside the 16-bit address space", addr)
+            }
+            ImageError::Overlap(addr) => write!(f, "data at ${:04X} is loaded twice", addr),
+            ImageError::MissingEnd => f.write_str("missing end-of-file record"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ImageError {}
+
+/// Data decoded from one line of a text image.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Record {
+    pub address: u16,
+    len: u8,
+    data: [u8; MAX_RECORD_DATA],
+}
+
+impl Record {
+    /// Sets the record to `data` at `address`, which must not extend past
+    /// $FFFF. `data` must fit in a record.
+    pub(super) fn set(&mut self, address: u32, data: &[u8]) -> Result<Line, ImageError> {
+        if address as u64 + data.len() as u64 > 0x10000 {
+            return Err(ImageError::OutOfRange(address));
+        }
+        self.address = address as u16;
+        self.len = data.len() as u8;
+        self.data[..data.len()].copy_from_slice(data);
+        Ok(Line::Data)
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data[..self.len as usize]
+    }
+
+    pub fn segment(&self) -> Segment<'_> {
+        Segment {
+            address: self.address as u32,
+            data: self.data(),
+        }
+    }
+}
+
+/// What a line of a text image holds.
+pub(super) enum Line {
+    /// A data record, decoded into the record passed to the decoder.
+    Data,
+    Start(u32),
+    /// End of the image, with the start address for formats that put it in
+    /// the termination record.
+    End(Option<u32>),
+    Skip,
+}
+
+/// Per-format line decoder. Decoders may keep state between lines, such as
+/// Intel HEX extended addresses.
+pub(super) trait Decoder: Default {
+    /// Decodes a line without its line terminator and surrounding
+    /// whitespace into `record` if it holds data. `number` is for error
+    /// reporting.
+    fn decode(
+        &mut self,
+        line: &[u8],
+        number: usize,
+        record: &mut Record,
+    ) -> Result<Line, ImageError>;
+}
+
+/// Decodes pairs of hex digits from `text` into `buf`, returning the bytes
+/// decoded.
+pub(super) fn hex_bytes<'b>(text: &[u8], buf: &'b mut [u8]) -> Option<&'b [u8]> {
+    if !text.len().is_multiple_of(2) || text.len() / 2 > buf.len() {
+        return None;
+    }
+    let digit = |c: u8| (c as char).to_digit(16).map(|d| d as u8);
+    for (byte, pair) in buf.iter_mut().zip(text.chunks_exact(2)) {
+        *byte = digit(pair[0])? << 4 | digit(pair[1])?;
+    }
+    Some(&buf[..text.len() / 2])
+}
+
+/// Lines of `text` up to the end record, decoded with `D`.
+struct Lines<'a, D> {
+    rest: Option<&'a [u8]>,
+    number: usize,
+    decoder: D,
+    /// The last data record decoded.
+    record: Record,
+}
+
+impl<'a, D: Decoder> Lines<'a, D> {
+    fn new(text: &'a [u8]) -> Self {
+        Lines {
+            rest: Some(text),
+            number: 0,
+            decoder: D::default(),
+            record: Record {
+                address: 0,
+                len: 0,
+                data: [0; MAX_RECORD_DATA],
+            },
+        }
+    }
+
+    /// Decodes the next non-empty line. Returns `None` after the end record
+    /// or an error.
+    fn next(&mut self) -> Option<Result<Line, ImageError>> {
+        while let Some(text) = self.rest {
+            let (line, rest) = match text.iter().position(|b| *b == b'\n') {
+                Some(i) => (&text[..i], Some(&text[i + 1..])),
+                None => (text, None),
+            };
+            self.rest = rest;
+            self.number += 1;
+            let line = line.trim_ascii();
+            if line.is_empty() {
+                continue;
+            }
+            let decoded = self.decoder.decode(line, self.number, &mut self.record);
+            if matches!(decoded, Ok(Line::End(_)) | Err(_)) {
+                self.rest = None;
+            }
+            return Some(decoded);
+        }
+        None
+    }
+}
+
+/// Checks a whole image, returning its start address.
+pub(super) fn validate<D: Decoder>(text: &[u8]) -> Result<Option<u16>, ImageError> {
+    // One bit per address loaded so far.
+    let mut loaded = [0u8; 0x10000 / 8];
+    let mut start = None;
+    let mut lines = Lines::<D>::new(text);
+    loop {
+        match lines.next().ok_or(ImageError::MissingEnd)?? {
+            Line::Data => {
+                let record = &lines.record;
+                for addr in (record.address..=u16::MAX).take(record.len as usize) {
+                    let (byte, bit) = (addr as usize / 8, 1 << (addr % 8));
+                    if loaded[byte] & bit != 0 {
+                        return Err(ImageError::Overlap(addr));
+                    }
+                    loaded[byte] |= bit;
+                }
+            }
+            Line::Start(addr) => start = Some(addr),
+            Line::End(end) => {
+                return end
+                    .or(start)
+                    .map(|addr| u16::try_from(addr).map_err(|_| ImageError::OutOfRange(addr)))
+                    .transpose();
+            }
+            Line::Skip => {}
+        }
+    }
+}
+
+/// Iterates over the data records of an image that passed [`validate`].
+pub(super) fn records<'a, D: Decoder + 'a>(text: &'a [u8]) -> impl Iterator<Item = Record> + 'a {
+    let mut lines = Lines::<D>::new(text);
+    core::iter::from_fn(move || loop {
+        if let Line::Data = lines.next()?.ok()? {
+            return Some(lines.record);
+        }
+    })
+}
+
+/// Writes the data records of an image to `bus` with [`Bus::poke`].
+pub(super) fn load<D: Decoder>(text: &[u8], bus: &mut impl Bus) {
+    let mut lines = Lines::<D>::new(text);
+    while let Some(Ok(line)) = lines.next() {
+        if let Line::Data = line {
+            lines.record.segment().load(bus);
+        }
+    }
+}
+
+/// Returns a raw binary loaded at `base` as a segment, checking that it fits
+/// below $10000.
+pub fn raw(base: u16, data: &[u8]) -> Result<Segment<'_>, ImageError> {
+    let segment = Segment {
+        address: base as u32,
+        data,
+    };
+    if segment.end() > 0x10000 {
+        return Err(ImageError::OutOfRange(base as u32));
+    }
+    Ok(segment)
+}
@@ -0,0 +1,235 @@
+use core::fmt;
+
+use super::u16_at;
+use crate::{Bus, RESET_VECTOR};
+
+/// Size of the units iNES counts PRG-ROM in, and of the PRG-ROM windows at
+/// $8000 and $C000 most mappers start with.
+pub const PRG_BANK_SIZE: usize = 0x4000;
+
+/// Size of the units iNES counts CHR-ROM in.
+pub const CHR_BANK_SIZE: usize = 0x2000;
+
+const HEADER_SIZE: usize = 16;
+const TRAINER_SIZE: usize = 512;
+
+/// Error returned by [`INes::parse`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum INesError {
+    /// The file doesn't start with the iNES magic.
+    NotINes,
+    /// The file is shorter than the ROM sizes in its header.
+    Truncated,
+}
+
+impl fmt::Display for INesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            INesError::NotINes => f.write_str("not an iNES file"),
+            INesError::Truncated => f.write_str("iNES file is shorter than its header says"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for INesError {}
+
+/// Nametable arrangement of a cartridge with hard-wired mirroring.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Mirroring {
+    /// Horizontal mirroring, used by vertically scrolling games.
+    Horizontal,
+    /// Vertical mirroring, used by horizontally scrolling games.
+    Vertical,
+    /// The cartridge provides its own nametable RAM.
+    FourScreen,
+}
+
+/// An NES cartridge image in iNES or NES 2.0 format.
+///
+/// The CPU sees PRG-ROM at $8000–$FFFF through the cartridge's mapper.
+/// [`INes::load`] sets up the mapping most mappers start with; a target
+/// emulating bank switching calls [`INes::map_prg`] when the program writes
+/// to the mapper.
+///
+/// ```no_run
+/// # use gdbstub_mos_arch::loader::INes;
+/// # use gdbstub_mos_arch::MosRegs;
+/// # let (bytes, mut mem, mut regs) = (&[][..], [0u8; 0x10000], MosRegs::<32>::default());
+/// let rom = INes::parse(bytes)?;
+/// rom.load(&mut mem);
+/// regs.pc = rom.reset_vector();
+/// # Ok::<(), gdbstub_mos_arch::loader::INesError>(())
+/// ```
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct INes<'a> {
+    /// Mapper number, up to 12 bits with NES 2.0.
+    pub mapper: u16,
+    /// NES 2.0 submapper number; 0 for iNES.
+    pub submapper: u8,
+    pub mirroring: Mirroring,
+    /// The cartridge has battery-backed PRG-RAM at $6000–$7FFF.
+    pub battery: bool,
+    /// The header is in NES 2.0 format.
+    pub nes2: bool,
+    trainer: Option<&'a [u8]>,
+    prg_rom: &'a [u8],
+    chr_rom: &'a [u8],
+}
+
+impl<'a> INes<'a> {
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, INesError> {
+        if !bytes.starts_with(b"NES\x1a") {
+            return Err(INesError::NotINes);
+        }
+        let header = bytes.get(..HEADER_SIZE).ok_or(INesError::Truncated)?;
+        let (flags6, flags7) = (header[6], header[7]);
+        let nes2 = flags7 & 0x0c == 0x08;
+
+        let mut mapper = (flags6 >> 4) as u16;
+        let mut submapper = 0;
+        let (prg_size, chr_size);
+        if nes2 {
+            mapper |= (flags7 & 0xf0) as u16 | ((header[8] & 0x0f) as u16) << 8;
+            submapper = header[8] >> 4;
+            prg_size = nes2_rom_size(header[4], header[9] & 0x0f, PRG_BANK_SIZE);
+            chr_size = nes2_rom_size(header[5], header[9] >> 4, CHR_BANK_SIZE);
+        } else {
+            // Old tools wrote garbage such as "DiskDude!" from byte 7 on, so
+            // only trust the upper mapper nibble if the padding is clear.
+            if header[12..].iter().all(|b| *b == 0) {
+                mapper |= (flags7 & 0xf0) as u16;
+            }
+            prg_size = Some(header[4] as usize * PRG_BANK_SIZE);
+            chr_size = Some(header[5] as usize * CHR_BANK_SIZE);
+        }
+
+        let mut rest = &bytes[HEADER_SIZE..];
+        let mut take = |size: Option<usize>| {
+            let size = size.filter(|size| *size <= rest.len());
+            let (data, tail) = rest.split_at(size.ok_or(INesError::Truncated)?);
+            rest = tail;
+            Ok(data)
+        };
+        let trainer = if flags6 & 0x04 != 0 {
+            Some(take(Some(TRAINER_SIZE))?)
+        } else {
+            None
+        };
+        let prg_rom = take(prg_size)?;
+        let chr_rom = take(chr_size)?;
+
+        let mirroring = if flags6 & 0x08 != 0 {
+            Mirroring::FourScreen
+        } else if flags6 & 0x01 != 0 {
+            Mirroring::Vertical
+        } else {
+            Mirroring::Horizontal
+        };
+        Ok(INes {
+            mapper,
+            submapper,
+            mirroring,
+            battery: flags6 & 0x02 != 0,
+            nes2,
+            trainer,
+            prg_rom,
+            chr_rom,
+        })
+    }
+
+    pub fn prg_rom(&self) -> &'a [u8] {
+        self.prg_rom
+    }
+
+    /// CHR-ROM, empty for cartridges with CHR-RAM.
+    pub fn chr_rom(&self) -> &'a [u8] {
+        self.chr_rom
+    }
+
+    /// 512 bytes the cartridge maps at $7000–$71FF, if present.
+    pub fn trainer(&self) -> Option<&'a [u8]> {
+        self.trainer
+    }
+
+    /// Returns PRG-ROM bank `n` of `size` bytes. The bank number wraps
+    /// around like on hardware, where unused bank bits are ignored.
+    pub fn prg_bank(&self, size: usize, n: usize) -> Option<&'a [u8]> {
+        bank(self.prg_rom, size, n)
+    }
+
+    /// Returns CHR-ROM bank `n` of `size` bytes, wrapping like
+    /// [`INes::prg_bank`].
+    pub fn chr_bank(&self, size: usize, n: usize) -> Option<&'a [u8]> {
+        bank(self.chr_rom, size, n)
+    }
+
+    /// Number of PRG-ROM banks of `size` bytes.
+    pub fn prg_banks(&self, size: usize) -> usize {
+        self.prg_rom.len().checked_div(size).unwrap_or(0)
+    }
+
+    /// Writes PRG-ROM bank `n` of `size` bytes to `addr` with [`Bus::poke`].
+    /// Returns `None` if there is no such bank or it doesn't fit below
+    /// $10000.
+    pub fn map_prg(&self, bus: &mut impl Bus, addr: u16, size: usize, n: usize) -> Option<()> {
+        let bank = self.prg_bank(size, n)?;
+        if size > 0x10000 - addr as usize {
+            return None;
+        }
+        for (addr, byte) in (addr..=u16::MAX).zip(bank) {
+            bus.poke(addr, *byte);
+        }
+        Some(())
+    }
+
+    /// Writes PRG-ROM to $8000–$FFFF as mapped at power-on by NROM, UxROM
+    /// and most other mappers: the first 16K bank at $8000 and the last at
+    /// $C000. With a single bank, it appears at both. The trainer, if any, is
+    /// written to $7000.
+    pub fn load(&self, bus: &mut impl Bus) {
+        let last = self.prg_banks(PRG_BANK_SIZE).saturating_sub(1);
+        self.map_prg(bus, 0x8000, PRG_BANK_SIZE, 0);
+        self.map_prg(bus, 0xc000, PRG_BANK_SIZE, last);
+        if let Some(trainer) = self.trainer {
+            for (addr, byte) in (0x7000..).zip(trainer) {
+                bus.poke(addr, *byte);
+            }
+        }
+    }
+
+    /// Returns the vector at `addr` ($FFFA–$FFFF) in the power-on mapping of
+    /// [`INes::load`].
+    pub fn vector(&self, addr: u16) -> u16 {
+        let last = self.prg_banks(PRG_BANK_SIZE).saturating_sub(1);
+        self.prg_bank(PRG_BANK_SIZE, last)
+            .and_then(|bank| u16_at(bank, addr as usize % PRG_BANK_SIZE))
+            .unwrap_or(0)
+    }
+
+    /// Initial PC, from the reset vector at $FFFC.
+    pub fn reset_vector(&self) -> u16 {
+        self.vector(RESET_VECTOR)
+    }
+}
+
+/// Returns bank `n` of `size` bytes of `rom`, wrapping around.
+fn bank(rom: &[u8], size: usize, n: usize) -> Option<&[u8]> {
+    let banks = rom.len().checked_div(size).filter(|banks| *banks > 0)?;
+    let start = (n % banks) * size;
+    Some(&rom[start..start + size])
+}
+
+/// Decodes a NES 2.0 ROM size from its low byte and high nibble. A high
+/// nibble of $F selects the exponent-multiplier notation,
+/// 2^E × (MM × 2 + 1) for a low byte of EEEEEEMM.
+fn nes2_rom_size(lsb: u8, msb: u8, unit: usize) -> Option<usize> {
+    if msb == 0x0f {
+        let multiplier = (lsb & 0x03) as usize * 2 + 1;
+        1usize
+            .checked_shl((lsb >> 2) as u32)?
+            .checked_mul(multiplier)
+    } else {
+        ((msb as usize) << 8 | lsb as usize).checked_mul(unit)
+    }
+}
@@ -0,0 +1,560 @@
+use core::fmt;
+
+use gdbstub::target::ext::section_offsets::Offsets;
+
+use super::{u16_at, Segment};
+use crate::Bus;
+
+const MAGIC: &[u8] = b"\x01\x00o65\x00";
+
+const MODE_65816: u16 = 0x8000;
+const MODE_PAGE_RELOC: u16 = 0x4000;
+const MODE_32BIT: u16 = 0x2000;
+const MODE_OBJECT: u16 = 0x1000;
+const MODE_CHAIN: u16 = 0x0400;
+const MODE_BSS_ZERO: u16 = 0x0200;
+
+const SEG_UNDEFINED: u8 = 0;
+const SEG_TEXT: u8 = 2;
+const SEG_DATA: u8 = 3;
+const SEG_BSS: u8 = 4;
+const SEG_ZERO: u8 = 5;
+
+const RELOC_WORD: u8 = 0x80;
+const RELOC_HIGH: u8 = 0x40;
+const RELOC_LOW: u8 = 0x20;
+
+/// Error returned by [`O65::parse`] and [`O65::load`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum O65Error {
+    /// The file doesn't start with the o65 magic.
+    NotO65,
+    /// The file uses 32-bit addresses.
+    Unsupported,
+    /// A header, segment or table is cut off.
+    Truncated,
+    /// A relocation entry is malformed, of an unsupported type such as the
+    /// 65816 segment relocations, or points outside its segment; holds the
+    /// offset of the entry in the file.
+    BadRelocation(usize),
+    /// No value was provided for the undefined reference with this index.
+    Unresolved(u16),
+    /// The file only allows page-wise relocation and a base isn't page
+    /// aligned.
+    Misaligned,
+    /// A segment doesn't fit below $10000; holds its base.
+    OutOfRange(u16),
+}
+
+impl fmt::Display for O65Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            O65Error::NotO65 => f.write_str("not an o65 file"),
+            O65Error::Unsupported => f.write_str("unsupported o65 file mode"),
+            O65Error::Truncated => f.write_str("truncated o65 file"),
+            O65Error::BadRelocation(offset) => {
+                write!(f, "bad relocation entry at offset {}", offset)
+            }
+            O65Error::Unresolved(index) => write!(f, "unresolved reference #{}", index),
+            O65Error::Misaligned => f.write_str("o65 file must be relocated by whole pages"),
+            O65Error::OutOfRange(base) => {
+                write!(f, "segment at ${:04X} extends past $FFFF", base)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for O65Error {}
+
+/// Base addresses of the four o65 segments.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct O65Bases {
+    pub text: u16,
+    pub data: u16,
+    pub bss: u16,
+    pub zero: u16,
+}
+
+/// How far a program was moved from the addresses it was assembled for.
+///
+/// GDB adds these to the addresses of the symbols it loads when the target
+/// reports them with `qOffsets`; see [`Relocation::offsets`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Relocation {
+    pub text: u16,
+    pub data: u16,
+    pub bss: u16,
+}
+
+impl Relocation {
+    /// Returns the offsets for implementing gdbstub's `SectionOffsets`.
+    pub fn offsets(&self) -> Offsets<u16> {
+        Offsets::Sections {
+            text: self.text,
+            data: self.data,
+            bss: Some(self.bss),
+        }
+    }
+}
+
+/// A relocatable o65 file, as produced by xa and ld65.
+///
+/// The file is assembled for the bases in its header, [`O65::bases`], and
+/// can be loaded at any others with [`O65::load`], which applies its
+/// relocation tables. Files with 32-bit addresses aren't supported; with
+/// chained files, only the first is loaded.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct O65<'a> {
+    mode: u16,
+    bases: O65Bases,
+    bss_len: u16,
+    zero_len: u16,
+    stack_len: u16,
+    options: &'a [u8],
+    text: &'a [u8],
+    data: &'a [u8],
+    undefined_count: u16,
+    undefined: &'a [u8],
+    text_relocs: &'a [u8],
+    data_relocs: &'a [u8],
+    exports_count: u16,
+    exports: &'a [u8],
+}
+
+/// Consumes bytes from the front of an o65 file.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], O65Error> {
+        let bytes = self
+            .bytes
+            .get(self.pos..self.pos + len)
+            .ok_or(O65Error::Truncated)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, O65Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn word(&mut self) -> Result<u16, O65Error> {
+        Ok(u16_at(self.take(2)?, 0).unwrap())
+    }
+
+    /// Takes a NUL-terminated string, without the NUL.
+    fn name(&mut self) -> Result<&'a [u8], O65Error> {
+        let rest = self.bytes.get(self.pos..).unwrap_or_default();
+        let len = rest
+            .iter()
+            .position(|b| *b == 0)
+            .ok_or(O65Error::Truncated)?;
+        self.pos += len + 1;
+        Ok(&rest[..len])
+    }
+}
+
+/// A decoded relocation table entry.
+struct Reloc {
+    /// Offset into the segment of the byte or word to relocate.
+    offset: usize,
+    kind: u8,
+    segment: u8,
+    /// Index of the undefined reference, for `SEG_UNDEFINED`.
+    undefined: u16,
+    /// Low byte of the address, for `RELOC_HIGH` with byte-wise relocation.
+    low: u8,
+}
+
+/// Iterates over a relocation table.
+struct Relocs<'a> {
+    reader: Reader<'a>,
+    offset: usize,
+    page_reloc: bool,
+}
+
+impl Iterator for Relocs<'_> {
+    type Item = Result<Reloc, O65Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.reader.pos;
+        let reloc = (|| {
+            loop {
+                match self.reader.byte()? {
+                    0 => return Ok(None),
+                    255 => self.offset += 254,
+                    n => {
+                        self.offset += n as usize;
+                        break;
+                    }
+                }
+            }
+            let type_byte = self.reader.byte()?;
+            let (kind, segment) = (type_byte & 0xe0, type_byte & 0x1f);
+            if !matches!(kind, RELOC_WORD | RELOC_HIGH | RELOC_LOW) || segment > SEG_ZERO {
+                return Err(O65Error::BadRelocation(entry));
+            }
+            let undefined = match segment {
+                SEG_UNDEFINED => self.reader.word()?,
+                _ => 0,
+            };
+            let low = match kind {
+                RELOC_HIGH if !self.page_reloc => self.reader.byte()?,
+                _ => 0,
+            };
+            Ok(Some(Reloc {
+                // Offsets count from the byte before the segment.
+                offset: self.offset - 1,
+                kind,
+                segment,
+                undefined,
+                low,
+            }))
+        })();
+        reloc.transpose()
+    }
+}
+
+impl<'a> O65<'a> {
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, O65Error> {
+        if !bytes.starts_with(MAGIC) {
+            return Err(O65Error::NotO65);
+        }
+        let mut reader = Reader { bytes, pos: 6 };
+        let mode = reader.word()?;
+        if mode & MODE_32BIT != 0 {
+            return Err(O65Error::Unsupported);
+        }
+        let mut header = [0; 9];
+        for word in &mut header {
+            *word = reader.word()?;
+        }
+        let [text_base, text_len, data_base, data_len, bss_base, bss_len, zero_base, zero_len, stack_len] =
+            header;
+
+        let options_start = reader.pos;
+        loop {
+            match reader.byte()? {
+                0 => break,
+                1 => return Err(O65Error::Truncated),
+                len => {
+                    reader.take(len as usize - 1)?;
+                }
+            }
+        }
+        let options = &bytes[options_start..reader.pos - 1];
+        let text = reader.take(text_len as usize)?;
+        let data = reader.take(data_len as usize)?;
+
+        let undefined_count = reader.word()?;
+        let undefined_start = reader.pos;
+        for _ in 0..undefined_count {
+            reader.name()?;
+        }
+        let undefined = &bytes[undefined_start..reader.pos];
+
+        let page_reloc = mode & MODE_PAGE_RELOC != 0;
+        let text_relocs = skip_relocs(&mut reader, text.len(), page_reloc, undefined_count)?;
+        let data_relocs = skip_relocs(&mut reader, data.len(), page_reloc, undefined_count)?;
+
+        let exports_count = reader.word()?;
+        let exports_start = reader.pos;
+        for _ in 0..exports_count {
+            reader.name()?;
+            reader.take(3)?;
+        }
+        let exports = &bytes[exports_start..reader.pos];
+
+        Ok(O65 {
+            mode,
+            bases: O65Bases {
+                text: text_base,
+                data: data_base,
+                bss: bss_base,
+                zero: zero_base,
+            },
+            bss_len,
+            zero_len,
+            stack_len,
+            options,
+            text,
+            data,
+            undefined_count,
+            undefined,
+            text_relocs,
+            data_relocs,
+            exports_count,
+            exports,
+        })
+    }
+
+    /// Whether the file is for the 65816.
+    pub fn is_65816(&self) -> bool {
+        self.mode & MODE_65816 != 0
+    }
+
+    /// Whether the file is an object file to be linked, rather than an
+    /// executable.
+    pub fn is_object(&self) -> bool {
+        self.mode & MODE_OBJECT != 0
+    }
+
+    /// Whether another o65 file follows this one.
+    pub fn is_chained(&self) -> bool {
+        self.mode & MODE_CHAIN != 0
+    }
+
+    /// Whether the file may only be relocated by whole pages.
+    pub fn page_relocation(&self) -> bool {
+        self.mode & MODE_PAGE_RELOC != 0
+    }
+
+    /// Bases the file was assembled for.
+    pub fn bases(&self) -> O65Bases {
+        self.bases
+    }
+
+    /// Bases that place the text segment at `text` and the data and bss
+    /// segments right after it, rounded up to whole pages if the file
+    /// requires it. The zero page segment stays where it was assembled.
+    pub fn bases_at(&self, text: u16) -> O65Bases {
+        let align = |addr: u16| match self.page_relocation() {
+            true => addr.wrapping_add(0xff) & 0xff00,
+            false => addr,
+        };
+        let data = align(text.wrapping_add(self.text.len() as u16));
+        let bss = align(data.wrapping_add(self.data.len() as u16));
+        O65Bases {
+            text,
+            data,
+            bss,
+            zero: self.bases.zero,
+        }
+    }
+
+    pub fn text(&self) -> &'a [u8] {
+        self.text
+    }
+
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn bss_len(&self) -> u16 {
+        self.bss_len
+    }
+
+    pub fn zero_len(&self) -> u16 {
+        self.zero_len
+    }
+
+    /// Stack space the program needs, 0 if unknown.
+    pub fn stack_len(&self) -> u16 {
+        self.stack_len
+    }
+
+    /// Iterates over the header options as (type, data) pairs, e.g. type 0
+    /// for the file name.
+    pub fn options(&self) -> impl Iterator<Item = (u8, &'a [u8])> {
+        let mut rest = self.options;
+        core::iter::from_fn(move || {
+            let (&len, tail) = rest.split_first()?;
+            let (option, tail) = tail.split_at(len as usize - 1);
+            rest = tail;
+            Some((option[0], &option[1..]))
+        })
+    }
+
+    /// Iterates over the names of the undefined references, in index order.
+    pub fn undefined(&self) -> impl Iterator<Item = &'a [u8]> {
+        self.undefined
+            .split(|b| *b == 0)
+            .take(self.undefined_count as usize)
+    }
+
+    /// Iterates over the exported symbols as (name, address) pairs, with the
+    /// addresses relocated to `bases`.
+    pub fn exports(&self, bases: &O65Bases) -> impl Iterator<Item = (&'a [u8], u16)> + '_ {
+        let mut reader = Reader {
+            bytes: self.exports,
+            pos: 0,
+        };
+        let bases = *bases;
+        (0..self.exports_count).map(move |_| {
+            // `parse` checked the list.
+            let name = reader.name().unwrap();
+            let segment = reader.byte().unwrap();
+            let value = reader.word().unwrap();
+            (name, value.wrapping_add(self.delta(&bases, segment)))
+        })
+    }
+
+    /// Returns the address of the exported symbol `name`, relocated to
+    /// `bases`.
+    pub fn export(&self, name: &str, bases: &O65Bases) -> Option<u16> {
+        self.exports(bases)
+            .find(|(export, _)| *export == name.as_bytes())
+            .map(|(_, addr)| addr)
+    }
+
+    /// How far loading at `bases` moves the program from its assembled
+    /// addresses.
+    pub fn relocation(&self, bases: &O65Bases) -> Relocation {
+        Relocation {
+            text: self.delta(bases, SEG_TEXT),
+            data: self.delta(bases, SEG_DATA),
+            bss: self.delta(bases, SEG_BSS),
+        }
+    }
+
+    /// Writes the text and data segments to `bus` at `bases` with
+    /// [`Bus::poke`] and relocates them. Clears the bss segment if the file
+    /// asks for it. `resolve` provides the values of undefined references by
+    /// name, such as the entry points of an OS kernel.
+    ///
+    /// Fails without writing anything if a segment doesn't fit, a reference
+    /// can't be resolved or the bases aren't aligned as required.
+    pub fn load(
+        &self,
+        bus: &mut impl Bus,
+        bases: &O65Bases,
+        mut resolve: impl FnMut(&str) -> Option<u16>,
+    ) -> Result<(), O65Error> {
+        for (base, len) in [
+            (bases.text, self.text.len()),
+            (bases.data, self.data.len()),
+            (bases.bss, self.bss_len as usize),
+            (bases.zero, self.zero_len as usize),
+        ] {
+            if len > 0x10000 - base as usize {
+                return Err(O65Error::OutOfRange(base));
+            }
+        }
+        if self.page_relocation() {
+            let delta = self.relocation(bases);
+            let zero = self.delta(bases, SEG_ZERO);
+            if [delta.text, delta.data, delta.bss, zero]
+                .iter()
+                .any(|delta| delta & 0xff != 0)
+            {
+                return Err(O65Error::Misaligned);
+            }
+        }
+        // Check the references before writing anything.
+        let mut resolved = |index: u16| {
+            let name = self.undefined().nth(index as usize)?;
+            resolve(core::str::from_utf8(name).ok()?)
+        };
+        for reloc in self
+            .relocs(self.text_relocs)
+            .chain(self.relocs(self.data_relocs))
+        {
+            let reloc = reloc?;
+            if reloc.segment == SEG_UNDEFINED && resolved(reloc.undefined).is_none() {
+                return Err(O65Error::Unresolved(reloc.undefined));
+            }
+        }
+
+        for (base, segment, relocs) in [
+            (bases.text, self.text, self.text_relocs),
+            (bases.data, self.data, self.data_relocs),
+        ] {
+            Segment {
+                address: base as u32,
+                data: segment,
+            }
+            .load(bus);
+            for reloc in self.relocs(relocs) {
+                let reloc = reloc?;
+                let delta = match reloc.segment {
+                    SEG_UNDEFINED => resolved(reloc.undefined).unwrap(),
+                    segment => self.delta(bases, segment),
+                };
+                let addr = base.wrapping_add(reloc.offset as u16);
+                match reloc.kind {
+                    RELOC_WORD => {
+                        let value = u16::from_le_bytes([bus.peek(addr), bus.peek(addr + 1)]);
+                        let [lo, hi] = value.wrapping_add(delta).to_le_bytes();
+                        bus.poke(addr, lo);
+                        bus.poke(addr + 1, hi);
+                    }
+                    RELOC_HIGH => {
+                        let value = u16::from_le_bytes([reloc.low, bus.peek(addr)]);
+                        bus.poke(addr, (value.wrapping_add(delta) >> 8) as u8);
+                    }
+                    _ => {
+                        let value = bus.peek(addr);
+                        bus.poke(addr, value.wrapping_add(delta as u8));
+                    }
+                }
+            }
+        }
+        if self.mode & MODE_BSS_ZERO != 0 {
+            for addr in (bases.bss..=u16::MAX).take(self.bss_len as usize) {
+                bus.poke(addr, 0);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns how far `segment` moves when loaded at `bases`.
+    fn delta(&self, bases: &O65Bases, segment: u8) -> u16 {
+        let (new, old) = match segment {
+            SEG_TEXT => (bases.text, self.bases.text),
+            SEG_DATA => (bases.data, self.bases.data),
+            SEG_BSS => (bases.bss, self.bases.bss),
+            SEG_ZERO => (bases.zero, self.bases.zero),
+            _ => return 0,
+        };
+        new.wrapping_sub(old)
+    }
+
+    /// Iterates over a relocation table that passed [`skip_relocs`].
+    fn relocs(&self, table: &'a [u8]) -> Relocs<'a> {
+        Relocs {
+            reader: Reader {
+                bytes: table,
+                pos: 0,
+            },
+            offset: 0,
+            page_reloc: self.page_relocation(),
+        }
+    }
+}
+
+/// Checks the relocation table at the reader's position, for a segment of
+/// `len` bytes, and returns it.
+fn skip_relocs<'a>(
+    reader: &mut Reader<'a>,
+    len: usize,
+    page_reloc: bool,
+    undefined_count: u16,
+) -> Result<&'a [u8], O65Error> {
+    let start = reader.pos;
+    let mut relocs = Relocs {
+        reader: Reader {
+            bytes: reader.bytes,
+            pos: start,
+        },
+        offset: 0,
+        page_reloc,
+    };
+    loop {
+        let entry = relocs.reader.pos;
+        let Some(reloc) = relocs.next().transpose()? else {
+            break;
+        };
+        let size = if reloc.kind == RELOC_WORD { 2 } else { 1 };
+        let fits = reloc.offset + size <= len;
+        let defined = reloc.segment != SEG_UNDEFINED || reloc.undefined < undefined_count;
+        if !fits || !defined {
+            return Err(O65Error::BadRelocation(entry));
+        }
+    }
+    reader.pos = relocs.reader.pos;
+    Ok(&reader.bytes[start..reader.pos])
+}
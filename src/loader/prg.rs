@@ -0,0 +1,106 @@
+use core::fmt;
+
+use super::{u16_at, Segment};
+use crate::Bus;
+
+/// Start of BASIC program text on the C64.
+pub const BASIC_START: u16 = 0x0801;
+
+/// BASIC token of `SYS`.
+const SYS_TOKEN: u8 = 0x9e;
+
+/// Error returned by [`Prg::parse`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum PrgError {
+    /// The file is shorter than its 2-byte load address.
+    Truncated,
+    /// The data extends past $FFFF; holds the load address.
+    OutOfRange(u16),
+}
+
+impl fmt::Display for PrgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrgError::Truncated => f.write_str("PRG file has no load address"),
+            PrgError::OutOfRange(addr) => {
+                write!(f, "PRG file loaded at ${:04X} extends past $FFFF", addr)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PrgError {}
+
+/// A Commodore program file: a little-endian load address followed by the
+/// data to load there.
+///
+/// Machine code programs loaded at [`BASIC_START`] usually begin with a BASIC
+/// line like `10 SYS 2061` so they can be started with `RUN`; [`Prg::entry`]
+/// finds the address such a stub jumps to.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Prg<'a> {
+    address: u16,
+    data: &'a [u8],
+}
+
+impl<'a> Prg<'a> {
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, PrgError> {
+        let address = u16_at(bytes, 0).ok_or(PrgError::Truncated)?;
+        let data = &bytes[2..];
+        if data.len() > 0x10000 - address as usize {
+            return Err(PrgError::OutOfRange(address));
+        }
+        Ok(Prg { address, data })
+    }
+
+    /// Load address.
+    pub fn address(&self) -> u16 {
+        self.address
+    }
+
+    /// The program without its load address.
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn segment(&self) -> Segment<'a> {
+        Segment {
+            address: self.address as u32,
+            data: self.data,
+        }
+    }
+
+    /// Writes the program to `bus` with [`Bus::poke`].
+    pub fn load(&self, bus: &mut impl Bus) {
+        // `parse` checked that the program fits.
+        self.segment().load(bus);
+    }
+
+    /// Returns the address passed to `SYS` in the first BASIC line, if the
+    /// program is loaded at [`BASIC_START`] and starts with such a line.
+    pub fn sys_address(&self) -> Option<u16> {
+        if self.address != BASIC_START {
+            return None;
+        }
+        // Skip the link to the next line and the line number.
+        let line = self.data.get(4..)?;
+        let line = &line[..line.iter().position(|b| *b == 0)?];
+        let sys = line.iter().position(|b| *b == SYS_TOKEN)?;
+        // The address may be in parentheses, as in `SYS(2061)`.
+        let args = line[sys + 1..].trim_ascii_start();
+        let args = args.strip_prefix(b"(").unwrap_or(args).trim_ascii_start();
+        let mut address = None::<u16>;
+        for digit in args.iter().take_while(|b| b.is_ascii_digit()) {
+            let value = address.unwrap_or(0);
+            address = Some(value.checked_mul(10)?.checked_add((digit - b'0') as u16)?);
+        }
+        address
+    }
+
+    /// Where execution starts: the `SYS` address of a BASIC stub, otherwise
+    /// the load address.
+    pub fn entry(&self) -> u16 {
+        self.sys_address().unwrap_or(self.address)
+    }
+}
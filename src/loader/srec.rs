@@ -0,0 +1,79 @@
+use super::image::{self, Decoder, ImageError, Line, Record};
+use crate::Bus;
+
+/// A Motorola S-record image (`.s19`, `.s28`, `.s37`, `.srec`).
+///
+/// Data may use 16, 24 or 32-bit addresses as long as it ends up below
+/// $10000. The image must end with an S7, S8 or S9 termination record;
+/// anything after it is ignored.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct SRecord<'a> {
+    text: &'a [u8],
+    start: Option<u16>,
+}
+
+impl<'a> SRecord<'a> {
+    /// Parses and validates the image: record syntax, checksums, addresses
+    /// and overlapping data.
+    pub fn parse(text: &'a [u8]) -> Result<Self, ImageError> {
+        let start = image::validate::<SRecordDecoder>(text)?;
+        Ok(SRecord { text, start })
+    }
+
+    /// Iterates over the data records in file order.
+    pub fn records(&self) -> impl Iterator<Item = Record> + 'a {
+        image::records::<SRecordDecoder>(self.text)
+    }
+
+    /// Start address from the termination record. Tools write an address
+    /// of 0 when there is no start address, so that reads as `None`.
+    pub fn start_address(&self) -> Option<u16> {
+        self.start
+    }
+
+    /// Writes the data to `bus` with [`Bus::poke`].
+    pub fn load(&self, bus: &mut impl Bus) {
+        image::load::<SRecordDecoder>(self.text, bus)
+    }
+}
+
+#[derive(Default)]
+struct SRecordDecoder;
+
+impl Decoder for SRecordDecoder {
+    fn decode(
+        &mut self,
+        line: &[u8],
+        number: usize,
+        record: &mut Record,
+    ) -> Result<Line, ImageError> {
+        let (kind, hex) = match line {
+            [b'S', kind @ b'0'..=b'9', hex @ ..] => (kind - b'0', hex),
+            _ => return Err(ImageError::Syntax(number)),
+        };
+        let address_size = match kind {
+            0 | 1 | 5 | 9 => 2,
+            2 | 6 | 8 => 3,
+            3 | 7 => 4,
+            _ => return Err(ImageError::UnsupportedRecord(number)),
+        };
+        let mut buf = [0; 256];
+        let bytes = image::hex_bytes(hex, &mut buf)
+            .filter(|bytes| bytes.len() >= address_size + 2 && bytes.len() == bytes[0] as usize + 1)
+            .ok_or(ImageError::Syntax(number))?;
+        if bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)) != 0xff {
+            return Err(ImageError::Checksum(number));
+        }
+        let address = bytes[1..=address_size]
+            .iter()
+            .fold(0u32, |addr, b| addr << 8 | *b as u32);
+        let data = &bytes[address_size + 1..bytes.len() - 1];
+        let line = match kind {
+            1..=3 if data.is_empty() => Line::Skip,
+            1..=3 => record.set(address, data)?,
+            7..=9 => Line::End(Some(address).filter(|addr| *addr != 0)),
+            _ => Line::Skip,
+        };
+        Ok(line)
+    }
+}
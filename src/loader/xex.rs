@@ -0,0 +1,189 @@
+use core::fmt;
+
+use super::{u16_at, Segment};
+
+/// Address of RUNAD, the vector DOS jumps through once loading finishes.
+pub const RUNAD: u16 = 0x02e0;
+
+/// Address of INITAD, the vector DOS calls through after each segment that
+/// writes it.
+pub const INITAD: u16 = 0x02e2;
+
+/// Error returned by [`Xex::parse`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum XexError {
+    /// The file doesn't start with $FFFF.
+    NotXex,
+    /// A segment header or its data is cut off.
+    Truncated,
+    /// A segment ends before it starts; holds the start address.
+    BadSegment(u16),
+}
+
+impl fmt::Display for XexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            XexError::NotXex => f.write_str("not an Atari executable"),
+            XexError::Truncated => f.write_str("truncated Atari executable"),
+            XexError::BadSegment(start) => {
+                write!(f, "segment at ${:04X} ends before it starts", start)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for XexError {}
+
+/// A step of loading an Atari executable, as performed by DOS.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum XexEvent<'a> {
+    /// Write a segment to memory.
+    Segment(Segment<'a>),
+    /// Call the routine at this address, which returns with `RTS`, then
+    /// continue loading. Yielded after each segment that completes INITAD,
+    /// once both of its bytes have been written since the previous call.
+    Init(u16),
+    /// Loading is complete; start the program at this address. Yielded last
+    /// if both bytes of RUNAD were written.
+    Run(u16),
+}
+
+/// An Atari 8-bit executable (`.xex`): a sequence of segments, each with a
+/// start and an inclusive end address, and the first one preceded by
+/// $FFFF.
+///
+/// Segments writing INITAD and RUNAD make DOS run code during and after
+/// loading. [`Xex::events`] reports those calls so a target can replay
+/// them, e.g. stopping at each INIT routine under the debugger:
+///
+/// ```no_run
+/// # use gdbstub_mos_arch::loader::{Xex, XexEvent};
+/// # let (bytes, mut mem) = (&[][..], [0u8; 0x10000]);
+/// for event in Xex::parse(bytes)?.events() {
+///     match event {
+///         XexEvent::Segment(segment) => segment.load(&mut mem).unwrap(),
+///         XexEvent::Init(addr) => { /* run `JSR addr` to completion */ }
+///         XexEvent::Run(addr) => { /* set the PC to `addr` */ }
+///     }
+/// }
+/// # Ok::<(), gdbstub_mos_arch::loader::XexError>(())
+/// ```
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Xex<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Xex<'a> {
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, XexError> {
+        if !bytes.starts_with(&[0xff, 0xff]) {
+            return Err(XexError::NotXex);
+        }
+        let mut rest = bytes;
+        while !rest.is_empty() {
+            rest = next_segment(rest)?.1;
+        }
+        Ok(Xex { data: bytes })
+    }
+
+    /// Iterates over the segments in file order.
+    pub fn segments(&self) -> impl Iterator<Item = Segment<'a>> {
+        self.events().filter_map(|event| match event {
+            XexEvent::Segment(segment) => Some(segment),
+            _ => None,
+        })
+    }
+
+    /// Iterates over the steps DOS takes to load and start the program.
+    pub fn events(&self) -> XexEvents<'a> {
+        XexEvents {
+            rest: self.data,
+            vectors: [0; 4],
+            written: 0,
+            init: None,
+            done: false,
+        }
+    }
+
+    /// The start address from RUNAD, if the program sets it.
+    pub fn run_address(&self) -> Option<u16> {
+        self.events().find_map(|event| match event {
+            XexEvent::Run(addr) => Some(addr),
+            _ => None,
+        })
+    }
+}
+
+/// Bits of `XexEvents::written` for RUNAD.
+const RUNAD_WRITTEN: u8 = 0b0011;
+/// Bits of `XexEvents::written` for INITAD.
+const INITAD_WRITTEN: u8 = 0b1100;
+
+/// Iterator returned by [`Xex::events`].
+#[derive(Debug, Clone)]
+pub struct XexEvents<'a> {
+    rest: &'a [u8],
+    /// RUNAD and INITAD as written so far.
+    vectors: [u8; 4],
+    /// Bit `n` is set once `vectors[n]` has been written. The INITAD bits
+    /// are cleared again by each call.
+    written: u8,
+    init: Option<u16>,
+    done: bool,
+}
+
+impl<'a> Iterator for XexEvents<'a> {
+    type Item = XexEvent<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(addr) = self.init.take() {
+            return Some(XexEvent::Init(addr));
+        }
+        if !self.rest.is_empty() {
+            // `Xex::parse` checked every segment.
+            let (segment, rest) = next_segment(self.rest).ok()?;
+            self.rest = rest;
+            for (addr, byte) in (segment.address..).zip(segment.data) {
+                if let Some(i) = addr.checked_sub(RUNAD as u32).filter(|i| *i < 4) {
+                    self.vectors[i as usize] = *byte;
+                    self.written |= 1 << i;
+                }
+            }
+            // DOS points INITAD at an `RTS` before each segment, so a call
+            // needs both bytes written since the previous one.
+            if self.written & INITAD_WRITTEN == INITAD_WRITTEN {
+                self.written &= !INITAD_WRITTEN;
+                self.init = u16_at(&self.vectors, 2);
+            }
+            return Some(XexEvent::Segment(segment));
+        }
+        if self.done {
+            return None;
+        }
+        self.done = true;
+        if self.written & RUNAD_WRITTEN == RUNAD_WRITTEN {
+            return u16_at(&self.vectors, 0).map(XexEvent::Run);
+        }
+        None
+    }
+}
+
+/// Splits off the segment at the start of `bytes`, skipping an optional
+/// $FFFF marker.
+fn next_segment(bytes: &[u8]) -> Result<(Segment<'_>, &[u8]), XexError> {
+    let bytes = bytes.strip_prefix(&[0xff, 0xff]).unwrap_or(bytes);
+    let (start, end) = match (u16_at(bytes, 0), u16_at(bytes, 2)) {
+        (Some(start), Some(end)) => (start, end),
+        _ => return Err(XexError::Truncated),
+    };
+    if end < start {
+        return Err(XexError::BadSegment(start));
+    }
+    let len = (end - start) as usize + 1;
+    let data = bytes.get(4..4 + len).ok_or(XexError::Truncated)?;
+    let segment = Segment {
+        address: start as u32,
+        data,
+    };
+    Ok((segment, &bytes[4 + len..]))
+}
@@ -0,0 +1,220 @@
+use gdbstub::arch::{Arch, Registers};
+
+use crate::{
+    reg_info, xml, MosBreakpointKind, MosRegId, MosRegs, RegAccess, RegInfo, DEFAULT_RC_COUNT,
+};
+
+/// Implements `Arch` for the MEGA65 45GS02 with `RC` llvm-mos imaginary
+/// registers.
+///
+/// Addresses are encoded with [`M45GS02Address`], so that GDB can reach both
+/// the CPU's current 64K view and the 28-bit flat address space.
+pub enum Mos45GS02Arch<const RC: usize = DEFAULT_RC_COUNT> {}
+
+/// A decoded [`Mos45GS02Arch`] address.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum M45GS02Address {
+    /// Address in the CPU's 64K view, as mapped by `MAP` at the time of the
+    /// access. Encoded as-is.
+    Cpu(u16),
+    /// 28-bit flat address, as used by 32-bit indirect addressing. Encoded
+    /// with [`M45GS02Address::FLAT_TAG`] set.
+    Flat(u32),
+}
+
+impl M45GS02Address {
+    /// Bit marking an encoded address as flat.
+    pub const FLAT_TAG: u32 = 0x8000_0000;
+
+    /// Mask of the 28-bit flat address space.
+    pub const FLAT_MASK: u32 = 0x0fff_ffff;
+
+    /// Decodes a GDB address. Returns `None` for addresses that are neither a
+    /// 16-bit CPU address nor a tagged 28-bit flat address.
+    pub fn decode(addr: u32) -> Option<Self> {
+        if addr & Self::FLAT_TAG != 0 {
+            let flat = addr & !Self::FLAT_TAG;
+            (flat <= Self::FLAT_MASK).then_some(M45GS02Address::Flat(flat))
+        } else {
+            u16::try_from(addr).ok().map(M45GS02Address::Cpu)
+        }
+    }
+
+    /// Encodes the address for GDB.
+    pub fn encode(self) -> u32 {
+        match self {
+            M45GS02Address::Cpu(addr) => addr as u32,
+            M45GS02Address::Flat(addr) => Self::FLAT_TAG | (addr & Self::FLAT_MASK),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Mos45GS02Regs<const RC: usize = DEFAULT_RC_COUNT> {
+    /// Registers shared with the 6502. Bit 5 of `flags` is the E flag, which
+    /// selects the 8-bit stack when set.
+    pub base: MosRegs<RC>,
+    pub z: u8,
+    /// Base page register, the high byte of zero page addresses.
+    pub b: u8,
+    /// High byte of the stack pointer, used when E is clear.
+    pub sph: u8,
+}
+
+impl<const RC: usize> Default for Mos45GS02Regs<RC> {
+    fn default() -> Self {
+        Mos45GS02Regs {
+            base: MosRegs::default(),
+            z: 0,
+            b: 0,
+            sph: 0,
+        }
+    }
+}
+
+impl<const RC: usize> Mos45GS02Regs<RC> {
+    /// Returns the 32-bit quad register Q, made of A (lowest byte), X, Y and
+    /// Z (highest byte).
+    pub fn q(&self) -> u32 {
+        u32::from_le_bytes([self.base.a, self.base.x, self.base.y, self.z])
+    }
+
+    /// Sets the 32-bit quad register Q, updating A, X, Y and Z.
+    pub fn set_q(&mut self, value: u32) {
+        let [a, x, y, z] = value.to_le_bytes();
+        self.base.a = a;
+        self.base.x = x;
+        self.base.y = y;
+        self.z = z;
+    }
+}
+
+impl<const RC: usize> RegAccess for Mos45GS02Regs<RC> {
+    type RegId = Mos45GS02RegId<RC>;
+    type Value = u32;
+
+    fn get_reg(&self, reg: &Mos45GS02RegId<RC>) -> Option<u32> {
+        let value = match reg {
+            Mos45GS02RegId::Mos(reg) => self.base.get_reg(reg)? as u32,
+            Mos45GS02RegId::Z => self.z as u32,
+            Mos45GS02RegId::B => self.b as u32,
+            Mos45GS02RegId::SPH => self.sph as u32,
+            Mos45GS02RegId::Q => self.q(),
+        };
+        Some(value)
+    }
+
+    fn set_reg(&mut self, reg: &Mos45GS02RegId<RC>, value: u32) -> Option<()> {
+        let slot = match reg {
+            Mos45GS02RegId::Mos(reg) => {
+                return self.base.set_reg(reg, u16::try_from(value).ok()?);
+            }
+            Mos45GS02RegId::Z => &mut self.z,
+            Mos45GS02RegId::B => &mut self.b,
+            Mos45GS02RegId::SPH => &mut self.sph,
+            Mos45GS02RegId::Q => {
+                self.set_q(value);
+                return Some(());
+            }
+        };
+        *slot = u8::try_from(value).ok()?;
+        Some(())
+    }
+}
+
+impl<const RC: usize> Registers for Mos45GS02Regs<RC> {
+    type ProgramCounter = u32;
+
+    fn pc(&self) -> Self::ProgramCounter {
+        self.base.pc as u32
+    }
+
+    fn gdb_serialize(&self, write_byte: impl FnMut(Option<u8>)) {
+        reg_info::serialize(self, write_byte)
+    }
+
+    fn gdb_deserialize(&mut self, bytes: &[u8]) -> Result<(), ()> {
+        reg_info::deserialize(self, bytes)
+    }
+}
+
+/// Register ids of [`Mos45GS02Arch`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Mos45GS02RegId<const RC: usize = DEFAULT_RC_COUNT> {
+    /// A register shared with the 6502. [`MosRegId::U`] is the E flag.
+    Mos(MosRegId<RC>),
+    Z,
+    /// Base page register.
+    B,
+    /// Stack pointer high byte.
+    SPH,
+    /// 32-bit quad register, an alias of A (lowest byte), X, Y and Z.
+    Q,
+}
+
+impl<const RC: usize> Mos45GS02RegId<RC> {
+    /// Registers following the 6502 register set.
+    const TAIL: [(Self, RegInfo); 4] = [
+        (Mos45GS02RegId::Z, RegInfo::new("Z", 8)),
+        (Mos45GS02RegId::B, RegInfo::new("BP", 8)),
+        (Mos45GS02RegId::SPH, RegInfo::new("SPH", 8)),
+        (
+            Mos45GS02RegId::Q,
+            RegInfo::new("Q", 32).alias_of(Self::A_OFFSET, &Self::Q_PARTS),
+        ),
+    ];
+
+    /// Offset of A in the `g` packet, after PC.
+    const A_OFFSET: usize = 2;
+
+    /// Register numbers of A, X, Y and Z, the bytes of Q.
+    const Q_PARTS: [usize; 4] = [1, 2, 3, MosRegId::<RC>::COUNT];
+
+    /// Number of registers in the target description.
+    pub const COUNT: usize = MosRegId::<RC>::COUNT + Self::TAIL.len();
+
+    /// Size of a complete `g` packet payload in bytes.
+    pub const PACKET_SIZE: usize =
+        MosRegId::<RC>::PACKET_SIZE + reg_info::packed_size(&Self::TAIL, Self::TAIL.len());
+
+    /// Returns the register with register number `regnum` along with its
+    /// description.
+    ///
+    /// The 6502 registers keep their numbers and offsets. Names that would
+    /// clash are adjusted: the zero flag is `ZF` next to the Z register, and
+    /// the base page register is `BP` next to the break flag `B`. The U bit
+    /// of the 6502 is named `E`.
+    pub const fn lookup(regnum: usize) -> Option<(Self, RegInfo)> {
+        if let Some((reg, mut info)) = MosRegId::<RC>::lookup(regnum) {
+            if matches!(reg, MosRegId::Z) {
+                info.name = "ZF";
+            } else if matches!(reg, MosRegId::U) {
+                info.name = "E";
+            }
+            Some((Mos45GS02RegId::Mos(reg), info))
+        } else if regnum < Self::COUNT {
+            let first = MosRegId::<RC>::COUNT;
+            let base = MosRegId::<RC>::PACKET_SIZE;
+            Some(reg_info::place(&Self::TAIL, regnum - first, first, base))
+        } else {
+            None
+        }
+    }
+}
+
+reg_info::reg_table!(Mos45GS02RegId);
+
+impl<const RC: usize> Mos45GS02Arch<RC> {
+    xml::target_xml!(m45gs02_target_xml());
+}
+
+impl<const RC: usize> Arch for Mos45GS02Arch<RC> {
+    type Usize = u32;
+    type Registers = Mos45GS02Regs<RC>;
+    type RegId = Mos45GS02RegId<RC>;
+    type BreakpointKind = MosBreakpointKind;
+
+    fn target_description_xml() -> Option<&'static str> {
+        Some(Self::TARGET_XML_STR)
+    }
+}
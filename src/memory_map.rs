@@ -0,0 +1,211 @@
+//! GDB memory maps, as read with `qXfer:memory-map:read`.
+//!
+//! The memory map tells the debugger which addresses are writable, so it can
+//! refuse writes to ROM and use hardware breakpoints there. Once a target
+//! provides one, GDB treats addresses outside every region as inaccessible,
+//! so the regions should cover the whole address space.
+//!
+//! Profiles of common machines describe the CPU's view in their default
+//! configuration. I/O areas are listed as RAM, so that GDB still reads and
+//! writes them.
+//!
+//! Where a machine has RAM under a ROM, CPU writes go to the RAM but reads,
+//! including instruction fetches, still come from the ROM. Such areas are
+//! listed as ROM: a software breakpoint written there would land in the RAM
+//! and never be executed, so GDB has to use hardware breakpoints instead.
+
+use core::fmt::{self, Write};
+
+use crate::BankWindow;
+
+/// Kind of memory in a region.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MemoryKind {
+    Ram,
+    /// Read-only memory; GDB uses hardware breakpoints here.
+    Rom,
+    /// Flash memory, erased in blocks of `block_size` bytes.
+    Flash {
+        block_size: u32,
+    },
+}
+
+/// A region of the memory map.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct MemoryRegion {
+    pub kind: MemoryKind,
+    pub start: u32,
+    pub length: u32,
+}
+
+impl MemoryRegion {
+    pub const fn ram(start: u32, length: u32) -> Self {
+        MemoryRegion {
+            kind: MemoryKind::Ram,
+            start,
+            length,
+        }
+    }
+
+    pub const fn rom(start: u32, length: u32) -> Self {
+        MemoryRegion {
+            kind: MemoryKind::Rom,
+            start,
+            length,
+        }
+    }
+
+    /// Address one past the end of the region.
+    pub const fn end(&self) -> u64 {
+        self.start as u64 + self.length as u64
+    }
+}
+
+/// Commodore 64 with BASIC, I/O and the KERNAL mapped in. BASIC and the
+/// KERNAL have RAM underneath, which is reached by banking the ROMs out.
+pub const C64: &[MemoryRegion] = &[
+    MemoryRegion::ram(0x0000, 0xa000),
+    // BASIC
+    MemoryRegion::rom(0xa000, 0x2000),
+    MemoryRegion::ram(0xc000, 0x2000),
+    // KERNAL
+    MemoryRegion::rom(0xe000, 0x2000),
+];
+
+/// NES, with PRG-RAM at $6000 and PRG-ROM from $8000.
+pub const NES: &[MemoryRegion] = &[
+    MemoryRegion::ram(0x0000, 0x8000),
+    MemoryRegion::rom(0x8000, 0x8000),
+];
+
+/// Atari 800XL with BASIC enabled.
+pub const ATARI_800XL: &[MemoryRegion] = &[
+    MemoryRegion::ram(0x0000, 0xa000),
+    // BASIC, then the OS
+    MemoryRegion::rom(0xa000, 0x3000),
+    MemoryRegion::ram(0xd000, 0x0800),
+    // Floating point package and OS
+    MemoryRegion::rom(0xd800, 0x2800),
+];
+
+/// Apple II with 48K of RAM and the monitor and BASIC ROMs.
+pub const APPLE_II: &[MemoryRegion] = &[
+    MemoryRegion::ram(0x0000, 0xd000),
+    MemoryRegion::rom(0xd000, 0x3000),
+];
+
+/// VIC-20 with RAM in every expansion block.
+pub const VIC20: &[MemoryRegion] = &[
+    MemoryRegion::ram(0x0000, 0x8000),
+    // Character ROM
+    MemoryRegion::rom(0x8000, 0x1000),
+    MemoryRegion::ram(0x9000, 0x3000),
+    // BASIC and KERNAL
+    MemoryRegion::rom(0xc000, 0x4000),
+];
+
+/// Commander X16, with the current RAM bank at $A000 and ROM bank at $C000.
+/// There is no RAM under the ROM window.
+pub const X16: &[MemoryRegion] = &[
+    MemoryRegion::ram(0x0000, 0xc000),
+    MemoryRegion::rom(0xc000, 0x4000),
+];
+
+/// The Commander X16's 8K RAM banks, mapped at $A000 by the register at $0000.
+pub const X16_RAM_BANKS: BankWindow = BankWindow {
+    kind: MemoryKind::Ram,
+    start: 0xa000,
+    length: 0x2000,
+    first_bank: 0,
+    banks: 256,
+};
+
+/// The Commander X16's 16K ROM banks, mapped at $C000 by the register at
+/// $0001.
+pub const X16_ROM_BANKS: BankWindow = BankWindow {
+    kind: MemoryKind::Rom,
+    start: 0xc000,
+    length: 0x4000,
+    first_bank: 0,
+    banks: 32,
+};
+
+/// Writes the part of the memory map XML for `regions` that starts at byte
+/// `offset` into `buf`, returning the number of bytes written. The document
+/// is generated on the fly, so that it can be served in pieces without
+/// allocating. Suitable for implementing `MemoryMap::memory_map_xml`:
+///
+/// ```
+/// # use gdbstub_mos_arch::memory_map::{memory_map_xml, C64};
+/// # let (offset, mut buf) = (0, [0; 64]);
+/// let len = memory_map_xml(C64.iter().copied(), offset, &mut buf);
+/// assert!(buf[..len].starts_with(b"<?xml"));
+/// ```
+pub fn memory_map_xml(
+    regions: impl IntoIterator<Item = MemoryRegion>,
+    offset: u64,
+    buf: &mut [u8],
+) -> usize {
+    let mut window = Window {
+        skip: offset,
+        buf,
+        len: 0,
+    };
+    // `Window` only fails once `buf` is full, when the rest isn't needed.
+    let _ = write_xml(&mut window, regions);
+    window.len
+}
+
+fn write_xml(out: &mut Window<'_>, regions: impl IntoIterator<Item = MemoryRegion>) -> fmt::Result {
+    out.write_str(concat!(
+        "<?xml version=\"1.0\"?>\n",
+        "<!DOCTYPE memory-map PUBLIC \"+//IDN gnu.org//DTD GDB Memory Map V1.0//EN\"\n",
+        "    \"http://sourceware.org/gdb/gdb-memory-map.dtd\">\n",
+        "<memory-map>\n",
+    ))?;
+    for region in regions {
+        let kind = match region.kind {
+            MemoryKind::Ram => "ram",
+            MemoryKind::Rom => "rom",
+            MemoryKind::Flash { .. } => "flash",
+        };
+        write!(
+            out,
+            "  <memory type=\"{}\" start=\"{:#x}\" length=\"{:#x}\"",
+            kind, region.start, region.length
+        )?;
+        match region.kind {
+            MemoryKind::Flash { block_size } => write!(
+                out,
+                ">\n    <property name=\"blocksize\">{:#x}</property>\n  </memory>\n",
+                block_size
+            )?,
+            _ => out.write_str("/>\n")?,
+        }
+    }
+    out.write_str("</memory-map>\n")
+}
+
+/// Writer keeping the bytes of a document that fall in a window of it.
+struct Window<'a> {
+    /// Bytes still to be skipped before the window.
+    skip: u64,
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl Write for Window<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut bytes = s.as_bytes();
+        let skipped = self.skip.min(bytes.len() as u64);
+        self.skip -= skipped;
+        bytes = &bytes[skipped as usize..];
+        let count = bytes.len().min(self.buf.len() - self.len);
+        self.buf[self.len..self.len + count].copy_from_slice(&bytes[..count]);
+        self.len += count;
+        if self.len == self.buf.len() {
+            return Err(fmt::Error);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,133 @@
+//! Opcode decoding shared by single-stepping and disassembly.
+
+use crate::InstructionSet;
+
+/// Addressing mode of an instruction.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum AddressingMode {
+    Implied,
+    /// Operates on A, e.g. `ASL A`.
+    Accumulator,
+    /// `#$nn`
+    Immediate,
+    /// `$nn`
+    ZeroPage,
+    /// `$nn,X`
+    ZeroPageX,
+    /// `$nn,Y`
+    ZeroPageY,
+    /// `$nnnn`
+    Absolute,
+    /// `$nnnn,X`
+    AbsoluteX,
+    /// `$nnnn,Y`
+    AbsoluteY,
+    /// `($nnnn)`, only used by `JMP`.
+    Indirect,
+    /// `($nn,X)`
+    IndexedIndirect,
+    /// `($nn),Y`
+    IndirectIndexed,
+    /// `($nn)`, 65C02 only.
+    ZeroPageIndirect,
+    /// `($nnnn,X)`, only used by the 65C02 `JMP`.
+    AbsoluteIndexedIndirect,
+    /// Branch target, encoded as a signed offset from the next instruction.
+    Relative,
+    /// `$nn,target`, used by the 65C02 `BBR`/`BBS`.
+    ZeroPageRelative,
+}
+
+impl AddressingMode {
+    /// Number of operand bytes following the opcode.
+    pub const fn operand_len(self) -> usize {
+        match self {
+            AddressingMode::Implied | AddressingMode::Accumulator => 0,
+            AddressingMode::Immediate
+            | AddressingMode::ZeroPage
+            | AddressingMode::ZeroPageX
+            | AddressingMode::ZeroPageY
+            | AddressingMode::IndexedIndirect
+            | AddressingMode::IndirectIndexed
+            | AddressingMode::ZeroPageIndirect
+            | AddressingMode::Relative => 1,
+            AddressingMode::Absolute
+            | AddressingMode::AbsoluteX
+            | AddressingMode::AbsoluteY
+            | AddressingMode::Indirect
+            | AddressingMode::AbsoluteIndexedIndirect
+            | AddressingMode::ZeroPageRelative => 2,
+        }
+    }
+
+    /// Length of the instruction in bytes, including the opcode.
+    pub const fn instruction_size(self) -> usize {
+        1 + self.operand_len()
+    }
+}
+
+/// Returns the addressing mode of `opcode`, including the undocumented NMOS
+/// opcodes and the 65C02 NOPs.
+pub(crate) const fn addressing_mode(set: InstructionSet, opcode: u8) -> AddressingMode {
+    use AddressingMode::*;
+
+    if let InstructionSet::W65C02 = set {
+        match opcode {
+            0x02 | 0x22 | 0x42 | 0x62 => return Immediate,
+            0x14 | 0x64 => return ZeroPage,
+            0x1c | 0x5c | 0x9c | 0xdc | 0xfc => return Absolute,
+            0x7c => return AbsoluteIndexedIndirect,
+            0x80 => return Relative,
+            0x9e => return AbsoluteX,
+            0x1a | 0x3a => return Accumulator,
+            _ => {}
+        }
+        match opcode & 0x1f {
+            0x12 => return ZeroPageIndirect,
+            0x07 | 0x17 => return ZeroPage,
+            0x0f | 0x1f => return ZeroPageRelative,
+            0x03 | 0x13 | 0x0b | 0x1b => return Implied,
+            _ => {}
+        }
+    }
+
+    let aaa = opcode >> 5;
+    let bbb = (opcode >> 2) & 7;
+    match (opcode & 3, bbb) {
+        (0, 0) => match aaa {
+            1 => Absolute,
+            0 | 2 | 3 => Implied,
+            _ => Immediate,
+        },
+        (0, 3) if opcode == 0x6c => Indirect,
+        (0, 4) => Relative,
+        (0, 2) | (0, 6) => Implied,
+        (2, 0) if opcode == 0xa2 || aaa >= 4 => Immediate,
+        (2, 0) | (2, 4) | (2, 6) => Implied,
+        (2, 2) if aaa < 4 => Accumulator,
+        (2, 2) => Implied,
+        (2, 5) if aaa == 4 || aaa == 5 => ZeroPageY,
+        (2, 7) if aaa == 4 || aaa == 5 => AbsoluteY,
+        (3, 5) if aaa == 4 || aaa == 5 => ZeroPageY,
+        (3, 7) if aaa == 4 || aaa == 5 => AbsoluteY,
+        (_, 0) => IndexedIndirect,
+        (_, 1) => ZeroPage,
+        (_, 2) => Immediate,
+        (_, 3) => Absolute,
+        (_, 4) => IndirectIndexed,
+        (_, 5) => ZeroPageX,
+        (_, 6) => AbsoluteY,
+        _ => AbsoluteX,
+    }
+}
+
+/// Returns whether `opcode` halts the CPU: the NMOS `JAM` opcodes and the
+/// 65C02 `STP`.
+pub(crate) const fn halts(set: InstructionSet, opcode: u8) -> bool {
+    match set {
+        InstructionSet::Nmos6502 => {
+            opcode & 0x1f == 0x12 || matches!(opcode, 0x02 | 0x22 | 0x42 | 0x62)
+        }
+        InstructionSet::W65C02 => opcode == 0xdb,
+    }
+}
@@ -0,0 +1,303 @@
+//! Declarative register descriptions. A single table per architecture drives
+//! the target description XML, raw register id mapping and `g`/`G` packet
+//! (de)serialization, so that they can't drift apart.
+
+/// Description of a single register as reported to the debugger.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct RegInfo {
+    /// Register name, or name prefix when `index` is set.
+    pub name: &'static str,
+    /// Index appended to `name`, e.g. `RC` and `Some(3)` make `RC3`.
+    pub index: Option<usize>,
+    pub bitsize: usize,
+    /// Byte offset of the register in the `g` packet.
+    pub offset: usize,
+    /// Register number, as used in `p`/`P` packets.
+    pub regnum: usize,
+    pub dwarf_regnum: Option<usize>,
+    pub group_id: Option<usize>,
+    /// Generic role of the register, e.g. `pc`.
+    pub generic: Option<&'static str>,
+    /// Set for registers made of bytes of other registers, such as the RS
+    /// pairs. These are not transferred separately in the `g` packet.
+    pub alias: bool,
+    /// Register numbers of the registers an alias is made of, lowest byte
+    /// first, for aliases whose bytes aren't contiguous in the `g` packet.
+    pub value_regnums: Option<&'static [usize]>,
+}
+
+impl RegInfo {
+    pub(crate) const fn new(name: &'static str, bitsize: usize) -> Self {
+        RegInfo {
+            name,
+            index: None,
+            bitsize,
+            offset: 0,
+            regnum: 0,
+            dwarf_regnum: None,
+            group_id: None,
+            generic: None,
+            alias: false,
+            value_regnums: None,
+        }
+    }
+
+    pub(crate) const fn dwarf(mut self, dwarf_regnum: usize) -> Self {
+        self.dwarf_regnum = Some(dwarf_regnum);
+        self
+    }
+
+    pub(crate) const fn generic(mut self, generic: &'static str) -> Self {
+        self.generic = Some(generic);
+        self
+    }
+
+    pub(crate) const fn indexed(mut self, index: usize) -> Self {
+        self.index = Some(index);
+        self
+    }
+
+    pub(crate) const fn group(mut self, group_id: usize) -> Self {
+        self.group_id = Some(group_id);
+        self
+    }
+
+    pub(crate) const fn alias(mut self) -> Self {
+        self.alias = true;
+        self
+    }
+
+    /// Marks the register as an alias of the bytes at `offset`, relative to
+    /// the start of its table.
+    pub(crate) const fn alias_at(mut self, offset: usize) -> Self {
+        self.alias = true;
+        self.offset = offset;
+        self
+    }
+
+    /// Marks the register as an alias made of the registers numbered
+    /// `value_regnums`, the first of which is at byte `offset` of the `g`
+    /// packet.
+    pub(crate) const fn alias_of(mut self, offset: usize, value_regnums: &'static [usize]) -> Self {
+        self.alias = true;
+        self.offset = offset;
+        self.value_regnums = Some(value_regnums);
+        self
+    }
+
+    pub(crate) const fn at(mut self, regnum: usize, offset: usize) -> Self {
+        self.regnum = regnum;
+        self.offset = offset;
+        self
+    }
+
+    /// Number of bytes the register occupies on the wire.
+    pub const fn size(&self) -> usize {
+        self.bitsize.div_ceil(8)
+    }
+}
+
+/// DWARF register number of RC0; RC`n` is `RC0_DWARF_REGNUM + 2 * n`.
+const RC0_DWARF_REGNUM: usize = 16;
+/// DWARF register number of RS0; RS`n` is `RS0_DWARF_REGNUM + n`.
+const RS0_DWARF_REGNUM: usize = 528;
+
+/// Describes the imaginary register RC`i`.
+pub(crate) const fn rc_info(i: usize) -> RegInfo {
+    RegInfo::new("RC", 8)
+        .indexed(i)
+        .group(1)
+        .dwarf(RC0_DWARF_REGNUM + 2 * i)
+}
+
+/// Describes the imaginary register RS`i`, an alias of RC`2i` and RC`2i+1`.
+pub(crate) const fn rs_info(i: usize) -> RegInfo {
+    RegInfo::new("RS", 16)
+        .indexed(i)
+        .group(2)
+        .dwarf(RS0_DWARF_REGNUM + i)
+        .alias()
+}
+
+/// Total wire size of the first `count` entries of `table`, not counting
+/// aliases.
+pub(crate) const fn packed_size<T>(table: &[(T, RegInfo)], count: usize) -> usize {
+    let mut size = 0;
+    let mut i = 0;
+    while i < count {
+        if !table[i].1.alias {
+            size += table[i].1.size();
+        }
+        i += 1;
+    }
+    size
+}
+
+/// Returns entry `i` of a table whose first register has number
+/// `first_regnum` and starts at byte `base` of the `g` packet. Aliases made
+/// of other registers keep their offset, which is absolute.
+pub(crate) const fn place<T: Copy>(
+    table: &[(T, RegInfo)],
+    i: usize,
+    first_regnum: usize,
+    base: usize,
+) -> (T, RegInfo) {
+    let (reg, info) = table[i];
+    let offset = if info.value_regnums.is_some() {
+        info.offset
+    } else if info.alias {
+        base + info.offset
+    } else {
+        base + packed_size(table, i)
+    };
+    (reg, info.at(first_regnum + i, offset))
+}
+
+/// Register ids of an architecture, described by its [`RegInfo`] table.
+pub trait RegTable: Copy + Eq {
+    /// Number of registers in the target description.
+    const COUNT: usize;
+
+    /// Size of a complete `g` packet payload in bytes.
+    const PACKET_SIZE: usize;
+
+    /// Returns the register with register number `regnum` along with its
+    /// description.
+    fn lookup(regnum: usize) -> Option<(Self, RegInfo)>;
+
+    /// Iterates over all registers in register number order.
+    fn iter() -> impl Iterator<Item = (Self, RegInfo)> {
+        (0..Self::COUNT).filter_map(Self::lookup)
+    }
+
+    /// Returns the description of this register, or `None` if it doesn't
+    /// exist with the architecture's number of imaginary registers.
+    fn info(&self) -> Option<RegInfo> {
+        Self::iter()
+            .find(|(reg, _)| reg == self)
+            .map(|(_, info)| info)
+    }
+}
+
+/// Register values of an architecture, accessed by [`RegTable`] id.
+pub trait RegAccess: Copy {
+    type RegId: RegTable;
+
+    /// Type of register values, wide enough for the widest register.
+    type Value: Copy + Into<u32> + TryFrom<u32>;
+
+    /// Returns the value of a single register. Status flags read as 0 or 1.
+    fn get_reg(&self, reg: &Self::RegId) -> Option<Self::Value>;
+
+    /// Sets the value of a single register. Returns `None` if the register
+    /// doesn't exist or `value` doesn't fit in it.
+    fn set_reg(&mut self, reg: &Self::RegId, value: Self::Value) -> Option<()>;
+
+    /// Reads a single register into `buf` in target byte order, returning the
+    /// number of bytes written. Suitable for implementing
+    /// `SingleRegisterAccess::read_register`.
+    fn read_reg(&self, reg: &Self::RegId, buf: &mut [u8]) -> Option<usize> {
+        let size = reg.info()?.size();
+        let bytes = self.get_reg(reg)?.into().to_le_bytes();
+        buf.get_mut(..size)?.copy_from_slice(&bytes[..size]);
+        Some(size)
+    }
+
+    /// Writes a single register from `val` in target byte order. `val` must
+    /// be exactly as wide as the register, as GDB always sends it. Suitable
+    /// for implementing `SingleRegisterAccess::write_register`.
+    fn write_reg(&mut self, reg: &Self::RegId, val: &[u8]) -> Option<()> {
+        if val.len() != reg.info()?.size() {
+            return None;
+        }
+        self.set_reg(reg, Self::Value::try_from(from_le(val)).ok()?)
+    }
+}
+
+/// Little-endian value of up to four bytes.
+fn from_le(bytes: &[u8]) -> u32 {
+    let mut le = [0; 4];
+    le[..bytes.len()].copy_from_slice(bytes);
+    u32::from_le_bytes(le)
+}
+
+/// Writes the `g` packet payload of `regs`: every register that isn't an
+/// alias, in register number order.
+pub(crate) fn serialize<T: RegAccess>(regs: &T, mut write_byte: impl FnMut(Option<u8>)) {
+    for (reg, info) in T::RegId::iter() {
+        if info.alias {
+            continue;
+        }
+        let value = regs.get_reg(&reg).unwrap().into().to_le_bytes();
+        value[..info.size()]
+            .iter()
+            .for_each(|b| write_byte(Some(*b)));
+    }
+}
+
+/// Calls `set` with each register in the `g` packet payload `bytes`, in
+/// register number order. Aliases and registers past the end of `bytes` are
+/// skipped.
+pub(crate) fn decode<R: RegTable>(
+    bytes: &[u8],
+    mut set: impl FnMut(&R, u32) -> Option<()>,
+) -> Result<(), ()> {
+    for (reg, info) in R::iter() {
+        let Some(value) = bytes.get(info.offset..info.offset + info.size()) else {
+            continue;
+        };
+        if !info.alias {
+            set(&reg, from_le(value)).ok_or(())?;
+        }
+    }
+    Ok(())
+}
+
+/// Sets the registers contained in the `g` packet payload `bytes`, keeping
+/// the current value of those past its end. `regs` is left unchanged if a
+/// value doesn't fit its register.
+pub(crate) fn update<T: RegAccess>(regs: &mut T, bytes: &[u8]) -> Result<(), ()> {
+    let mut new = *regs;
+    decode(bytes, |reg, value| {
+        new.set_reg(reg, T::Value::try_from(value).ok()?)
+    })?;
+    *regs = new;
+    Ok(())
+}
+
+/// Sets all registers from a complete `g` packet payload.
+pub(crate) fn deserialize<T: RegAccess>(regs: &mut T, bytes: &[u8]) -> Result<(), ()> {
+    if bytes.len() != T::RegId::PACKET_SIZE {
+        return Err(());
+    }
+    update(regs, bytes)
+}
+
+/// Implements [`RegTable`] and `RegId` for a register id type generic over
+/// the number of imaginary registers, from its inherent `COUNT`,
+/// `PACKET_SIZE` and `lookup`, which stay usable in const contexts.
+macro_rules! reg_table {
+    ($reg_id:ident) => {
+        impl<const RC: usize> $crate::reg_info::RegTable for $reg_id<RC> {
+            const COUNT: usize = $reg_id::<RC>::COUNT;
+            const PACKET_SIZE: usize = $reg_id::<RC>::PACKET_SIZE;
+
+            fn lookup(regnum: usize) -> Option<(Self, $crate::RegInfo)> {
+                $reg_id::<RC>::lookup(regnum)
+            }
+        }
+
+        impl<const RC: usize> gdbstub::arch::RegId for $reg_id<RC> {
+            fn from_raw_id(id: usize) -> Option<(Self, Option<core::num::NonZeroUsize>)> {
+                $reg_id::<RC>::lookup(id)
+                    .map(|(reg, info)| (reg, core::num::NonZeroUsize::new(info.size())))
+            }
+
+            fn to_raw_id(&self) -> Option<usize> {
+                $crate::reg_info::RegTable::info(self).map(|info| info.regnum)
+            }
+        }
+    };
+}
+
+pub(crate) use reg_table;
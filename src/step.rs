@@ -0,0 +1,155 @@
+//! Software single-stepping: computes where the instruction at PC can go, so
+//! that targets without hardware stepping can step with temporary
+//! breakpoints.
+
+use crate::opcode::{self, AddressingMode};
+use crate::{InstructionSet, MosRegs, MosVariant};
+
+/// Address of the NMI vector.
+pub const NMI_VECTOR: u16 = 0xfffa;
+/// Address of the reset vector.
+pub const RESET_VECTOR: u16 = 0xfffc;
+/// Address of the IRQ/BRK vector.
+pub const IRQ_VECTOR: u16 = 0xfffe;
+
+/// Possible addresses of the next instruction, as returned by [`next_pcs`].
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct NextPcs {
+    pcs: [u16; 2],
+    len: usize,
+}
+
+impl NextPcs {
+    fn push(&mut self, pc: u16) {
+        if !self.contains(pc) {
+            self.pcs[self.len] = pc;
+            self.len += 1;
+        }
+    }
+
+    /// The addresses, without duplicates. Empty if the instruction halts the
+    /// CPU.
+    pub fn as_slice(&self) -> &[u16] {
+        &self.pcs[..self.len]
+    }
+
+    pub fn contains(&self, pc: u16) -> bool {
+        self.as_slice().contains(&pc)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl IntoIterator for NextPcs {
+    type Item = u16;
+    type IntoIter = core::iter::Take<core::array::IntoIter<u16, 2>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.pcs.into_iter().take(self.len)
+    }
+}
+
+/// Returns the possible addresses of the instruction executed after the one
+/// at `regs.pc`, reading code, pointers and the stack through `read`.
+///
+/// Conditional branches report both the target and the following
+/// instruction, so the result holds even if the flags change before the
+/// branch executes (e.g. in an interrupt handler). `BRK` goes through
+/// [`IRQ_VECTOR`], and so does the 65C02 `WAI` when interrupts are enabled.
+/// Hardware interrupts taken during the step are not included; a breakpoint
+/// on the returned addresses is still hit once the handler returns.
+pub fn next_pcs<const RC: usize>(
+    set: InstructionSet,
+    regs: &MosRegs<RC>,
+    mut read: impl FnMut(u16) -> u8,
+) -> NextPcs {
+    let pc = regs.pc;
+    let opcode = read(pc);
+    let mut pcs = NextPcs::default();
+    if opcode::halts(set, opcode) {
+        return pcs;
+    }
+
+    let mode = opcode::addressing_mode(set, opcode);
+    let next = pc.wrapping_add(mode.instruction_size() as u16);
+    let operand = match mode.operand_len() {
+        0 => 0,
+        1 => read(pc.wrapping_add(1)) as u16,
+        _ => u16::from_le_bytes([read(pc.wrapping_add(1)), read(pc.wrapping_add(2))]),
+    };
+    let mut read_word = |addr: u16, hi_addr: u16| u16::from_le_bytes([read(addr), read(hi_addr)]);
+    let stack = |offset: u8| 0x100 | regs.s.wrapping_add(offset) as u16;
+
+    match (opcode, mode) {
+        // BRK
+        (0x00, _) => pcs.push(read_word(IRQ_VECTOR, IRQ_VECTOR + 1)),
+        // JSR, JMP abs
+        (0x20 | 0x4c, _) => pcs.push(operand),
+        // RTI
+        (0x40, _) => pcs.push(read_word(stack(2), stack(3))),
+        // RTS
+        (0x60, _) => pcs.push(read_word(stack(1), stack(2)).wrapping_add(1)),
+        // JMP (abs). The NMOS 6502 doesn't carry into the high byte of the
+        // pointer, so JMP ($xxFF) takes the high byte from $xx00.
+        (_, AddressingMode::Indirect) => {
+            let hi = match set {
+                InstructionSet::Nmos6502 => (operand & 0xff00) | (operand.wrapping_add(1) & 0xff),
+                InstructionSet::W65C02 => operand.wrapping_add(1),
+            };
+            pcs.push(read_word(operand, hi));
+        }
+        // JMP (abs,X)
+        (_, AddressingMode::AbsoluteIndexedIndirect) => {
+            let ptr = operand.wrapping_add(regs.x as u16);
+            pcs.push(read_word(ptr, ptr.wrapping_add(1)));
+        }
+        // BRA always branches.
+        (0x80, AddressingMode::Relative) => pcs.push(branch_target(next, operand as u8)),
+        (_, AddressingMode::Relative) => {
+            pcs.push(next);
+            pcs.push(branch_target(next, operand as u8));
+        }
+        // BBR/BBS
+        (_, AddressingMode::ZeroPageRelative) => {
+            pcs.push(next);
+            pcs.push(branch_target(next, (operand >> 8) as u8));
+        }
+        // WAI resumes after the instruction, after the IRQ handler if
+        // interrupts are enabled.
+        (0xcb, _) if set == InstructionSet::W65C02 => {
+            if regs.flags & 0x04 == 0 {
+                pcs.push(read_word(IRQ_VECTOR, IRQ_VECTOR + 1));
+            }
+            pcs.push(next);
+        }
+        _ => pcs.push(next),
+    }
+    pcs
+}
+
+/// Like [`next_pcs`], with the instruction set of the architecture `A`, for
+/// targets generic over the 6502 variant.
+///
+/// ```
+/// # use gdbstub_mos_arch::{next_pcs_for, Mos65C02Arch, MosRegs};
+/// let regs: MosRegs = MosRegs::default();
+/// // BRA $0012
+/// let pcs = next_pcs_for::<Mos65C02Arch, _>(&regs, |addr| [0x80, 0x10][addr as usize & 1]);
+/// assert_eq!(pcs.as_slice(), [0x0012]);
+/// ```
+pub fn next_pcs_for<A, const RC: usize>(regs: &MosRegs<RC>, read: impl FnMut(u16) -> u8) -> NextPcs
+where
+    A: MosVariant<Registers = MosRegs<RC>>,
+{
+    next_pcs(A::INSTRUCTION_SET, regs, read)
+}
+
+fn branch_target(next: u16, offset: u8) -> u16 {
+    next.wrapping_add(offset as i8 as u16)
+}
@@ -0,0 +1,330 @@
+use gdbstub::arch::{Arch, Registers};
+
+use crate::{reg_info, xml, MosBreakpointKind, RegAccess, RegInfo, RegTable, DEFAULT_RC_COUNT};
+
+/// Implements `Arch` for the WDC 65C816 with `RC` llvm-mos imaginary
+/// registers.
+///
+/// Addresses are 24 bits wide: the bank in bits 16–23 and the offset within
+/// the bank in bits 0–15.
+pub enum W65816Arch<const RC: usize = DEFAULT_RC_COUNT> {}
+
+/// Bit of P holding the index register width flag `x`, or `B` in emulation
+/// mode.
+const X_FLAG: u8 = 1 << 4;
+/// Bit of P holding the accumulator width flag `m`, or `U` in emulation
+/// mode.
+const M_FLAG: u8 = 1 << 5;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct W65816Regs<const RC: usize = DEFAULT_RC_COUNT> {
+    pub rc: [u8; RC],
+    /// Program counter within the program bank.
+    pub pc: u16,
+    /// Program bank register, bits 16–23 of the program counter.
+    pub pbr: u8,
+    /// Data bank register.
+    pub dbr: u8,
+    /// Accumulator. With the `m` flag set the high byte is the hidden B
+    /// accumulator.
+    pub a: u16,
+    pub x: u16,
+    pub y: u16,
+    pub s: u16,
+    /// Direct page register.
+    pub d: u16,
+    pub flags: u8,
+    /// Emulation mode flag E.
+    pub emulation: bool,
+}
+
+impl<const RC: usize> Default for W65816Regs<RC> {
+    fn default() -> Self {
+        W65816Regs {
+            rc: [0; RC],
+            pc: 0,
+            pbr: 0,
+            dbr: 0,
+            a: 0,
+            x: 0,
+            y: 0,
+            s: 0,
+            d: 0,
+            flags: 0,
+            emulation: false,
+        }
+    }
+}
+
+impl<const RC: usize> W65816Regs<RC> {
+    /// Returns the 16-bit imaginary register RS`n`, composed of RC`2n` (low
+    /// byte) and RC`2n+1` (high byte).
+    pub fn rs(&self, n: usize) -> u16 {
+        u16::from_le_bytes([self.rc[2 * n], self.rc[2 * n + 1]])
+    }
+
+    /// Sets the 16-bit imaginary register RS`n`, updating RC`2n` and
+    /// RC`2n+1`.
+    pub fn set_rs(&mut self, n: usize, value: u16) {
+        let [lo, hi] = value.to_le_bytes();
+        self.rc[2 * n] = lo;
+        self.rc[2 * n + 1] = hi;
+    }
+
+    /// Switches between native and emulation mode the way `XCE` does.
+    /// Entering emulation mode forces `m` and `x` to 1, clears the high bytes
+    /// of X and Y and moves the stack to page 1.
+    pub fn set_emulation(&mut self, emulation: bool) {
+        if emulation && !self.emulation {
+            self.flags |= M_FLAG | X_FLAG;
+        }
+        self.emulation = emulation;
+        self.normalize();
+    }
+
+    /// Applies the register constraints of the current mode: X and Y are 8
+    /// bits wide while `x` is set, and emulation mode keeps the stack in
+    /// page 1 with `m` and `x` forced to 1.
+    fn normalize(&mut self) {
+        if self.emulation {
+            self.flags |= M_FLAG | X_FLAG;
+            self.s = 0x0100 | (self.s & 0xff);
+        }
+        if self.flags & X_FLAG != 0 {
+            self.x &= 0xff;
+            self.y &= 0xff;
+        }
+    }
+
+    /// Sets the value of a single register as is, without applying the
+    /// constraints of the mode.
+    fn store(&mut self, reg: &W65816RegId<RC>, value: u32) -> Option<()> {
+        let info = reg.info()?;
+        if info.bitsize < 32 && value >> info.bitsize != 0 {
+            return None;
+        }
+        if let Some(bit) = reg.flag_bit() {
+            self.flags = (self.flags & !(1 << bit)) | ((value as u8) << bit);
+            return Some(());
+        }
+        match *reg {
+            W65816RegId::PC => {
+                self.pc = value as u16;
+                self.pbr = (value >> 16) as u8;
+            }
+            W65816RegId::PBR => self.pbr = value as u8,
+            W65816RegId::DBR => self.dbr = value as u8,
+            W65816RegId::A => self.a = value as u16,
+            W65816RegId::X => self.x = value as u16,
+            W65816RegId::Y => self.y = value as u16,
+            W65816RegId::S => self.s = value as u16,
+            W65816RegId::DP => self.d = value as u16,
+            W65816RegId::E => self.emulation = value != 0,
+            W65816RegId::RC(n) => self.rc[n] = value as u8,
+            W65816RegId::RS(n) => self.set_rs(n, value as u16),
+            _ => return None,
+        }
+        Some(())
+    }
+}
+
+impl<const RC: usize> RegAccess for W65816Regs<RC> {
+    type RegId = W65816RegId<RC>;
+    type Value = u32;
+
+    fn get_reg(&self, reg: &W65816RegId<RC>) -> Option<u32> {
+        if let Some(bit) = reg.flag_bit() {
+            return Some(((self.flags >> bit) & 1) as u32);
+        }
+        let value = match *reg {
+            W65816RegId::PC => ((self.pbr as u32) << 16) | self.pc as u32,
+            W65816RegId::PBR => self.pbr as u32,
+            W65816RegId::DBR => self.dbr as u32,
+            W65816RegId::A => self.a as u32,
+            W65816RegId::X => self.x as u32,
+            W65816RegId::Y => self.y as u32,
+            W65816RegId::S => self.s as u32,
+            W65816RegId::DP => self.d as u32,
+            W65816RegId::E => self.emulation as u32,
+            W65816RegId::RC(n) => *self.rc.get(n)? as u32,
+            W65816RegId::RS(n) if n < RC / 2 => self.rs(n) as u32,
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// Sets the value of a single register, then applies the constraints of
+    /// the resulting mode. Also returns `None` if it would clear `x` or `m`
+    /// in emulation mode, where they are forced to 1.
+    fn set_reg(&mut self, reg: &W65816RegId<RC>, value: u32) -> Option<()> {
+        let width_flag = matches!(reg, W65816RegId::XF | W65816RegId::M);
+        if self.emulation && width_flag && value == 0 {
+            return None;
+        }
+        match reg {
+            W65816RegId::E => {
+                reg.info()?;
+                if value >> 1 != 0 {
+                    return None;
+                }
+                self.set_emulation(value != 0);
+            }
+            _ => {
+                self.store(reg, value)?;
+                self.normalize();
+            }
+        }
+        Some(())
+    }
+}
+
+impl<const RC: usize> Registers for W65816Regs<RC> {
+    type ProgramCounter = u32;
+
+    fn pc(&self) -> Self::ProgramCounter {
+        ((self.pbr as u32) << 16) | self.pc as u32
+    }
+
+    fn gdb_serialize(&self, write_byte: impl FnMut(Option<u8>)) {
+        reg_info::serialize(self, write_byte)
+    }
+
+    fn gdb_deserialize(&mut self, bytes: &[u8]) -> Result<(), ()> {
+        if bytes.len() != W65816RegId::<RC>::PACKET_SIZE {
+            return Err(());
+        }
+
+        // Every register is decoded as sent, and the constraints of the
+        // packet's mode are applied once at the end, so that X and Y aren't
+        // truncated according to the `x` flag being replaced. Like `set_reg`,
+        // clearing `x` or `m` in emulation mode is rejected.
+        let mut regs = *self;
+        reg_info::decode(bytes, |reg, value| regs.store(reg, value))?;
+        if regs.emulation && regs.flags & (M_FLAG | X_FLAG) != M_FLAG | X_FLAG {
+            return Err(());
+        }
+        regs.normalize();
+        *self = regs;
+        Ok(())
+    }
+}
+
+/// Register ids of [`W65816Arch`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum W65816RegId<const RC: usize = DEFAULT_RC_COUNT> {
+    RC(usize),
+    RS(usize),
+    /// 24-bit program counter, including the program bank.
+    PC,
+    /// Program bank register, an alias of bits 16–23 of PC.
+    PBR,
+    /// Data bank register.
+    DBR,
+    A,
+    X,
+    Y,
+    S,
+    /// Direct page register.
+    DP,
+    C,
+    Z,
+    I,
+    D,
+    /// Index register width flag `x` (`B` in emulation mode), named to
+    /// avoid clashing with the X register.
+    XF,
+    /// Accumulator width flag `m` (`U` in emulation mode).
+    M,
+    V,
+    N,
+    /// Emulation mode flag.
+    E,
+}
+
+impl<const RC: usize> W65816RegId<RC> {
+    /// Registers preceding the imaginary registers, in register number and
+    /// `g` packet order.
+    const HEAD: [(Self, RegInfo); 17] = [
+        (W65816RegId::PC, RegInfo::new("PC", 24).generic("pc")),
+        (W65816RegId::PBR, RegInfo::new("PBR", 8).alias_at(2)),
+        (W65816RegId::A, RegInfo::new("A", 16).dwarf(0)),
+        (W65816RegId::X, RegInfo::new("X", 16).dwarf(2)),
+        (W65816RegId::Y, RegInfo::new("Y", 16).dwarf(4)),
+        (W65816RegId::S, RegInfo::new("S", 16)),
+        (W65816RegId::DP, RegInfo::new("DP", 16)),
+        (W65816RegId::DBR, RegInfo::new("DBR", 8)),
+        (W65816RegId::C, RegInfo::new("C", 1)),
+        (W65816RegId::Z, RegInfo::new("Z", 1)),
+        (W65816RegId::I, RegInfo::new("I", 1)),
+        (W65816RegId::D, RegInfo::new("D", 1)),
+        (W65816RegId::XF, RegInfo::new("XF", 1)),
+        (W65816RegId::M, RegInfo::new("M", 1)),
+        (W65816RegId::V, RegInfo::new("V", 1)),
+        (W65816RegId::N, RegInfo::new("N", 1)),
+        (W65816RegId::E, RegInfo::new("E", 1)),
+    ];
+
+    const HEAD_SIZE: usize = reg_info::packed_size(&Self::HEAD, Self::HEAD.len());
+
+    /// Number of registers in the target description.
+    pub const COUNT: usize = Self::HEAD.len() + RC + RC / 2;
+
+    /// Size of a complete `g` packet payload in bytes.
+    pub const PACKET_SIZE: usize = Self::HEAD_SIZE + RC;
+
+    /// Returns the register with register number `regnum` along with its
+    /// description.
+    pub const fn lookup(regnum: usize) -> Option<(Self, RegInfo)> {
+        assert!(
+            RC.is_multiple_of(2),
+            "number of imaginary registers must be even"
+        );
+        let rc = Self::HEAD.len();
+        let rs = rc + RC;
+        if regnum < rc {
+            Some(reg_info::place(&Self::HEAD, regnum, 0, 0))
+        } else if regnum < rs {
+            let i = regnum - rc;
+            let info = reg_info::rc_info(i).at(regnum, Self::HEAD_SIZE + i);
+            Some((W65816RegId::RC(i), info))
+        } else if regnum < Self::COUNT {
+            let i = regnum - rs;
+            let info = reg_info::rs_info(i).at(regnum, Self::HEAD_SIZE + 2 * i);
+            Some((W65816RegId::RS(i), info))
+        } else {
+            None
+        }
+    }
+
+    /// Bit position within P, for registers that are status flags.
+    fn flag_bit(&self) -> Option<u8> {
+        match self {
+            W65816RegId::C => Some(0),
+            W65816RegId::Z => Some(1),
+            W65816RegId::I => Some(2),
+            W65816RegId::D => Some(3),
+            W65816RegId::XF => Some(4),
+            W65816RegId::M => Some(5),
+            W65816RegId::V => Some(6),
+            W65816RegId::N => Some(7),
+            _ => None,
+        }
+    }
+}
+
+reg_info::reg_table!(W65816RegId);
+
+impl<const RC: usize> W65816Arch<RC> {
+    xml::target_xml!(w65816_target_xml());
+}
+
+impl<const RC: usize> Arch for W65816Arch<RC> {
+    type Usize = u32;
+    type Registers = W65816Regs<RC>;
+    type RegId = W65816RegId<RC>;
+    type BreakpointKind = MosBreakpointKind;
+
+    fn target_description_xml() -> Option<&'static str> {
+        Some(Self::TARGET_XML_STR)
+    }
+}
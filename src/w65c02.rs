@@ -0,0 +1,37 @@
+use gdbstub::arch::Arch;
+
+use crate::{
+    xml, InstructionSet, MosBreakpointKind, MosRegId, MosRegs, MosVariant, DEFAULT_RC_COUNT,
+};
+
+/// Implements `Arch` for the WDC W65C02S with `RC` llvm-mos imaginary
+/// registers.
+///
+/// The register set is the same as [`MOSArch`](crate::MOSArch); only the
+/// instruction set differs.
+///
+/// The target description names the architecture `mos:w65c02`, in the
+/// `family:machine` form of BFD architecture names: the `mos` family of
+/// llvm-mos, with the machine named after its `mosw65c02` CPU. The other
+/// variants follow the same scheme, so a debugger can tell the instruction
+/// sets apart without a separate query.
+pub enum Mos65C02Arch<const RC: usize = DEFAULT_RC_COUNT> {}
+
+impl<const RC: usize> Mos65C02Arch<RC> {
+    xml::target_xml!(mos_target_xml("mos:w65c02"));
+}
+
+impl<const RC: usize> Arch for Mos65C02Arch<RC> {
+    type Usize = u16;
+    type Registers = MosRegs<RC>;
+    type RegId = MosRegId<RC>;
+    type BreakpointKind = MosBreakpointKind;
+
+    fn target_description_xml() -> Option<&'static str> {
+        Some(Self::TARGET_XML_STR)
+    }
+}
+
+impl<const RC: usize> MosVariant for Mos65C02Arch<RC> {
+    const INSTRUCTION_SET: InstructionSet = InstructionSet::W65C02;
+}
@@ -0,0 +1,293 @@
+//! Target description XML, built at compile time so that it can be handed to
+//! gdbstub as a `&'static str` for every register configuration.
+
+use crate::{HuC6280RegId, Mos45GS02RegId, MosBankedRegId, MosRegId, RegInfo, W65816RegId};
+
+/// Fixed-capacity string buffer usable in `const` context. Text past the
+/// capacity is dropped but still counted, so that overflowing it is caught
+/// in [`XmlBuf::as_str`].
+pub(crate) struct XmlBuf<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> XmlBuf<N> {
+    const fn new() -> Self {
+        XmlBuf {
+            buf: [0; N],
+            len: 0,
+        }
+    }
+
+    const fn push(&mut self, s: &str) {
+        self.push_bytes(s.as_bytes());
+    }
+
+    const fn push_bytes(&mut self, bytes: &[u8]) {
+        if self.len < N {
+            let room = N - self.len;
+            let count = if bytes.len() < room {
+                bytes.len()
+            } else {
+                room
+            };
+            let (_, free) = self.buf.split_at_mut(self.len);
+            let (dst, _) = free.split_at_mut(count);
+            let (src, _) = bytes.split_at(count);
+            dst.copy_from_slice(src);
+        }
+        self.len += bytes.len();
+    }
+
+    /// Copies the text into a buffer of capacity `M`.
+    pub(crate) const fn resize<const M: usize>(&self) -> XmlBuf<M> {
+        let mut xml = XmlBuf::new();
+        let kept = if self.len < N { self.len } else { N };
+        let (bytes, _) = self.buf.split_at(kept);
+        xml.push_bytes(bytes);
+        xml.len = self.len;
+        xml
+    }
+
+    const fn push_num(&mut self, n: usize) {
+        let mut digits = [0u8; 20];
+        let mut start = digits.len();
+        let mut n = n;
+        loop {
+            start -= 1;
+            digits[start] = b'0' + (n % 10) as u8;
+            n /= 10;
+            if n == 0 {
+                break;
+            }
+        }
+        let (_, digits) = digits.split_at(start);
+        match core::str::from_utf8(digits) {
+            Ok(digits) => self.push(digits),
+            Err(_) => unreachable!(),
+        }
+    }
+
+    /// Size of the whole text in bytes, including any part that didn't fit.
+    pub(crate) const fn size(&self) -> usize {
+        self.len
+    }
+
+    const fn push_attr_num(&mut self, name: &str, value: usize) {
+        self.push(" ");
+        self.push(name);
+        self.push("=\"");
+        self.push_num(value);
+        self.push("\"");
+    }
+
+    /// Appends a `<reg>` element for `info`.
+    const fn reg(&mut self, info: &RegInfo) {
+        self.push("    <reg name=\"");
+        self.push(info.name);
+        if let Some(index) = info.index {
+            self.push_num(index);
+        }
+        self.push("\"");
+        if let Some(group_id) = info.group_id {
+            self.push_attr_num("group_id", group_id);
+        }
+        self.push_attr_num("bitsize", info.bitsize);
+        self.push_attr_num("offset", info.offset);
+        self.push_attr_num("regnum", info.regnum);
+        if let Some(dwarf_regnum) = info.dwarf_regnum {
+            self.push_attr_num("dwarf_regnum", dwarf_regnum);
+        }
+        if let Some(generic) = info.generic {
+            self.push(" generic=\"");
+            self.push(generic);
+            self.push("\"");
+        }
+        if let Some(value_regnums) = info.value_regnums {
+            self.push(" value_regnums=\"");
+            let mut i = 0;
+            while i < value_regnums.len() {
+                if i > 0 {
+                    self.push(",");
+                }
+                self.push_num(value_regnums[i]);
+                i += 1;
+            }
+            self.push("\"");
+        }
+        self.push(" />\n");
+    }
+
+    pub(crate) const fn as_str(&self) -> &str {
+        assert!(
+            self.len <= N,
+            "target description XML exceeds buffer capacity"
+        );
+        let (bytes, _) = self.buf.split_at(self.len);
+        match core::str::from_utf8(bytes) {
+            Ok(s) => s,
+            Err(_) => panic!("target description XML is not valid UTF-8"),
+        }
+    }
+}
+
+impl<const N: usize> XmlBuf<N> {
+    /// Starts a target description: the header, the `flags` type with one
+    /// single-bit field per `(name, bit)` in `flags`, the imaginary register
+    /// groups and the opening `<feature>` tag.
+    const fn begin(architecture: &str, flags: &[(&str, usize)]) -> Self {
+        let mut xml = XmlBuf::new();
+        xml.push(concat!(
+            "<?xml version=\"1.0\"?>\n",
+            "<!DOCTYPE target SYSTEM \"gdb-target.dtd\">\n",
+            "<target version=\"1.0\">\n",
+            "  <architecture>",
+        ));
+        xml.push(architecture);
+        xml.push("</architecture>\n  <flags id=\"flags\" size=\"1\">\n");
+        let mut i = 0;
+        while i < flags.len() {
+            let (name, bit) = flags[i];
+            xml.push("    <field name=\"");
+            xml.push(name);
+            xml.push("\"");
+            xml.push_attr_num("start", bit);
+            xml.push_attr_num("end", bit);
+            xml.push(" type=\"bool\" />\n");
+            i += 1;
+        }
+        xml.push(concat!(
+            "  </flags>\n",
+            "  <groups>\n",
+            "    <group id=\"1\" name=\"imaginary, 8-bit\"></group>\n",
+            "    <group id=\"2\" name=\"imaginary, 16-bit\"></group>\n",
+            "  </groups>\n",
+            "  <feature name=\"org.gnu.gdb.mos\">\n",
+        ));
+        xml
+    }
+
+    const fn end(mut self) -> Self {
+        self.push("  </feature>\n</target>\n");
+        self
+    }
+}
+
+/// Status flags, as `(name, bit)`, with the names of bits 4 and 5, which
+/// differ between CPUs.
+const fn flags<'a>(bit4: &'a str, bit5: &'a str) -> [(&'a str, usize); 8] {
+    [
+        ("C", 0),
+        ("Z", 1),
+        ("I", 2),
+        ("D", 3),
+        (bit4, 4),
+        (bit5, 5),
+        ("V", 6),
+        ("N", 7),
+    ]
+}
+
+/// Flags of the 6502, where bits 4 and 5 only exist on the stack.
+const MOS_FLAGS: [(&str, usize); 8] = flags("B", "U");
+
+/// Builds the target description for a 6502-family CPU with the
+/// [`MosRegId`] register set and `RC` imaginary registers.
+pub(crate) const fn mos_target_xml<const RC: usize, const N: usize>(
+    architecture: &str,
+) -> XmlBuf<N> {
+    let mut xml = XmlBuf::begin(architecture, &MOS_FLAGS);
+    let mut regnum = 0;
+    while let Some((_, info)) = MosRegId::<RC>::lookup(regnum) {
+        xml.reg(&info);
+        regnum += 1;
+    }
+    xml.end()
+}
+
+/// Builds the target description for a banked 6502 with `RC` imaginary
+/// registers.
+pub(crate) const fn banked_target_xml<const RC: usize, const N: usize>() -> XmlBuf<N> {
+    let mut xml = XmlBuf::begin("mos", &MOS_FLAGS);
+    let mut regnum = 0;
+    while let Some((_, info)) = MosBankedRegId::<RC>::lookup(regnum) {
+        xml.reg(&info);
+        regnum += 1;
+    }
+    xml.end()
+}
+
+/// Builds the target description for the 65C816 with `RC` imaginary
+/// registers.
+pub(crate) const fn w65816_target_xml<const RC: usize, const N: usize>() -> XmlBuf<N> {
+    let mut xml = XmlBuf::begin("mos:w65816", &flags("X", "M"));
+    let mut regnum = 0;
+    while let Some((_, info)) = W65816RegId::<RC>::lookup(regnum) {
+        xml.reg(&info);
+        regnum += 1;
+    }
+    xml.end()
+}
+
+/// Builds the target description for the 45GS02 with `RC` imaginary
+/// registers.
+pub(crate) const fn m45gs02_target_xml<const RC: usize, const N: usize>() -> XmlBuf<N> {
+    let mut xml = XmlBuf::begin("mos:45gs02", &flags("B", "E"));
+    let mut regnum = 0;
+    while let Some((_, info)) = Mos45GS02RegId::<RC>::lookup(regnum) {
+        xml.reg(&info);
+        regnum += 1;
+    }
+    xml.end()
+}
+
+/// Builds the target description for the HuC6280 with `RC` imaginary
+/// registers.
+pub(crate) const fn huc6280_target_xml<const RC: usize, const N: usize>() -> XmlBuf<N> {
+    let mut xml = XmlBuf::begin("mos:huc6280", &flags("B", "T"));
+    let mut regnum = 0;
+    while let Some((_, info)) = HuC6280RegId::<RC>::lookup(regnum) {
+        xml.reg(&info);
+        regnum += 1;
+    }
+    xml.end()
+}
+
+/// Declares `TARGET_XML_STR` in an `impl` block generic over `RC`, holding
+/// the description built by the generator `$build` with arguments `$args`.
+///
+/// Stable Rust can't size an array from `RC`, so the description is built
+/// once into a scratch buffer, then copied into buffers of a few sizes, and
+/// the smallest one it fits in is used. Only that one ends up in the binary.
+macro_rules! target_xml {
+    ($build:ident $args:tt) => {
+        $crate::xml::target_xml!(
+            @buckets $build $args,
+            TARGET_XML_1K = 1024,
+            TARGET_XML_2K = 2048,
+            TARGET_XML_3K = 3072,
+            TARGET_XML_4K = 4096,
+            TARGET_XML_5K = 5120,
+            TARGET_XML_6K = 6144,
+            TARGET_XML_7K = 7168,
+            TARGET_XML_8K = 8192,
+            TARGET_XML_10K = 10240,
+            TARGET_XML_12K = 12288,
+            TARGET_XML_16K = 16384,
+            TARGET_XML_24K = 24576,
+            TARGET_XML_32K = 32768
+        );
+    };
+    (@buckets $build:ident $args:tt, $($bucket:ident = $size:literal),+) => {
+        const TARGET_XML: $crate::xml::XmlBuf<32768> = $crate::xml::$build::<RC, 32768> $args;
+        $(const $bucket: $crate::xml::XmlBuf<$size> = Self::TARGET_XML.resize::<$size>();)+
+        const TARGET_XML_STR: &'static str = {
+            let size = Self::TARGET_XML.size();
+            $(if size <= $size { Self::$bucket.as_str() } else)+ {
+                panic!("target description XML exceeds buffer capacity")
+            }
+        };
+    };
+}
+
+pub(crate) use target_xml;
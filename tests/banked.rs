@@ -0,0 +1,131 @@
+use gdbstub::arch::{Arch, RegId, Registers};
+use gdbstub_mos_arch::memory_map::{MemoryKind, MemoryRegion};
+use gdbstub_mos_arch::{
+    BankWindow, BankedAddress, MosBankedArch, MosBankedRegId, MosBankedRegs, MosBreakpointKind,
+    MosRegId, MosRegs, RegAccess, RegTable,
+};
+
+#[test]
+fn address_encoding() {
+    assert_eq!(
+        BankedAddress::decode(0xa000),
+        Some(BankedAddress::Cpu(0xa000))
+    );
+    assert_eq!(
+        BankedAddress::decode(0x8003_a010),
+        Some(BankedAddress::Bank {
+            bank: 3,
+            addr: 0xa010,
+        })
+    );
+    assert_eq!(
+        BankedAddress::decode(0xffff_ffff),
+        Some(BankedAddress::Bank {
+            bank: BankedAddress::MAX_BANK,
+            addr: 0xffff,
+        })
+    );
+    assert_eq!(BankedAddress::decode(0x0001_0000), None);
+    assert_eq!(
+        BankedAddress::Bank {
+            bank: 0x1f,
+            addr: 0xc000,
+        }
+        .encode(),
+        0x801f_c000
+    );
+    assert_eq!(BankedAddress::Cpu(0x0801).encode(), 0x0801);
+
+    let banked = BankedAddress::decode(0x8002_8000).unwrap();
+    assert_eq!((banked.bank(), banked.cpu_address()), (Some(2), 0x8000));
+    assert_eq!(
+        banked.breakpoint_kind(MosBreakpointKind::Software),
+        MosBreakpointKind::Banked(2)
+    );
+    let cpu = BankedAddress::Cpu(0x8000);
+    assert_eq!((cpu.bank(), cpu.cpu_address()), (None, 0x8000));
+    assert_eq!(
+        cpu.breakpoint_kind(MosBreakpointKind::Software),
+        MosBreakpointKind::Software
+    );
+}
+
+#[test]
+fn bank_windows() {
+    let rom = BankWindow {
+        kind: MemoryKind::Rom,
+        start: 0xc000,
+        length: 0x4000,
+        first_bank: 1,
+        banks: 2,
+    };
+    assert_eq!(
+        rom.regions().collect::<Vec<_>>(),
+        [
+            MemoryRegion::rom(0x8001_c000, 0x4000),
+            MemoryRegion::rom(0x8002_c000, 0x4000),
+        ]
+    );
+    assert_eq!(
+        rom.address(2, 0xffff),
+        Some(BankedAddress::Bank {
+            bank: 2,
+            addr: 0xffff,
+        })
+    );
+    assert_eq!(rom.address(0, 0xc000), None);
+    assert_eq!(rom.address(3, 0xc000), None);
+    assert_eq!(rom.address(1, 0xbfff), None);
+}
+
+#[test]
+fn table_extends_6502_layout() {
+    let xml = MosBankedArch::<32>::target_description_xml().unwrap();
+    assert_eq!(xml.matches("<reg ").count(), MosBankedRegId::<32>::COUNT);
+    assert!(xml.contains("<architecture>mos</architecture>"));
+    assert!(xml.contains("<reg name=\"BANK\" bitsize=\"16\""));
+    for (reg, info) in MosBankedRegId::<32>::iter() {
+        let (raw, size) = MosBankedRegId::<32>::from_raw_id(info.regnum).unwrap();
+        assert_eq!(raw, reg);
+        assert_eq!(size.unwrap().get(), info.size());
+        if let MosBankedRegId::Mos(mos) = reg {
+            let mos_info = mos.info().unwrap();
+            assert_eq!(
+                (info.regnum, info.offset),
+                (mos_info.regnum, mos_info.offset)
+            );
+        }
+    }
+}
+
+#[test]
+fn registers_roundtrip() {
+    let mut regs: MosBankedRegs = MosBankedRegs {
+        base: MosRegs {
+            pc: 0xa123,
+            a: 0x42,
+            ..Default::default()
+        },
+        bank: 0x0105,
+    };
+    assert_eq!(regs.pc(), 0xa123);
+    let mut buf = [0; 2];
+    assert_eq!(regs.read_reg(&MosBankedRegId::Bank, &mut buf), Some(2));
+    assert_eq!(buf, [0x05, 0x01]);
+    assert_eq!(regs.write_reg(&MosBankedRegId::Bank, &[0x07]), None);
+    assert_eq!(
+        regs.write_reg(&MosBankedRegId::Bank, &[0x07, 0x00]),
+        Some(())
+    );
+    assert_eq!(regs.bank, 7);
+    assert_eq!(regs.set_reg(&MosBankedRegId::Mos(MosRegId::A), 0x100), None);
+
+    let mut bytes = Vec::new();
+    regs.gdb_serialize(|b| bytes.push(b.unwrap()));
+    assert_eq!(bytes.len(), MosBankedRegId::<32>::PACKET_SIZE);
+    assert_eq!(bytes[bytes.len() - 2..], [0x07, 0x00]);
+    let mut decoded: MosBankedRegs = MosBankedRegs::default();
+    assert_eq!(decoded.gdb_deserialize(&bytes), Ok(()));
+    assert_eq!(decoded, regs);
+    assert_eq!(decoded.gdb_deserialize(&bytes[1..]), Err(()));
+}
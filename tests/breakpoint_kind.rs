@@ -0,0 +1,41 @@
+use gdbstub::arch::BreakpointKind;
+use gdbstub_mos_arch::MosBreakpointKind;
+
+#[test]
+fn decode_kinds() {
+    assert_eq!(
+        MosBreakpointKind::from_usize(0),
+        Some(MosBreakpointKind::Software)
+    );
+    assert_eq!(
+        MosBreakpointKind::from_usize(1),
+        Some(MosBreakpointKind::Software)
+    );
+    assert_eq!(
+        MosBreakpointKind::from_usize(2),
+        Some(MosBreakpointKind::Hardware)
+    );
+    assert_eq!(MosBreakpointKind::from_usize(3), None);
+    assert_eq!(
+        MosBreakpointKind::from_usize(0x100),
+        Some(MosBreakpointKind::Banked(0))
+    );
+    assert_eq!(
+        MosBreakpointKind::from_usize(0x1_00ff),
+        Some(MosBreakpointKind::Banked(0xffff))
+    );
+    assert_eq!(MosBreakpointKind::from_usize(0x1_0100), None);
+}
+
+#[test]
+fn kinds_roundtrip() {
+    for kind in [
+        MosBreakpointKind::Software,
+        MosBreakpointKind::Hardware,
+        MosBreakpointKind::Banked(7),
+    ] {
+        assert_eq!(MosBreakpointKind::from_usize(kind.to_usize()), Some(kind));
+    }
+    assert!(MosBreakpointKind::Software.patches_memory());
+    assert!(!MosBreakpointKind::Banked(7).patches_memory());
+}
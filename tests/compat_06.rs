@@ -0,0 +1,105 @@
+use core::fmt::Debug;
+
+use gdbstub_06::arch::{Arch, RegId, Registers};
+use gdbstub_mos_arch::{
+    HuC6280Arch, HuC6280Regs, MOSArch, Mos45GS02Arch, Mos45GS02Regs, Mos65C02Arch, MosBankedArch,
+    MosBankedRegs, MosRegs, W65816Arch, W65816Regs,
+};
+
+/// Checks that the 0.6 traits of `A` agree with its 0.7 ones, and that
+/// `regs` survives a round trip through the 0.6 `g` packet encoding.
+fn check_arch<A, R, I>(regs: R)
+where
+    A: Arch<Registers = R, RegId = I> + gdbstub::arch::Arch<Registers = R, RegId = I>,
+    R: Registers + gdbstub::arch::Registers + Default + Debug + PartialEq,
+    I: RegId + gdbstub::arch::RegId + PartialEq,
+{
+    assert_eq!(
+        <A as Arch>::target_description_xml(),
+        <A as gdbstub::arch::Arch>::target_description_xml()
+    );
+
+    let mut bytes = Vec::new();
+    Registers::gdb_serialize(&regs, |b| bytes.push(b.unwrap()));
+    let mut bytes_07 = Vec::new();
+    gdbstub::arch::Registers::gdb_serialize(&regs, |b| bytes_07.push(b.unwrap()));
+    assert_eq!(bytes, bytes_07);
+
+    let mut decoded = R::default();
+    assert_eq!(Registers::gdb_deserialize(&mut decoded, &bytes), Ok(()));
+    assert_eq!(decoded, regs);
+    assert_eq!(
+        Registers::gdb_deserialize(&mut decoded, &bytes[1..]),
+        Err(())
+    );
+
+    for id in 0.. {
+        let reg = <I as RegId>::from_raw_id(id);
+        assert_eq!(reg, <I as gdbstub::arch::RegId>::from_raw_id(id));
+        if reg.is_none() {
+            break;
+        }
+    }
+}
+
+fn mos_regs() -> MosRegs<16> {
+    MosRegs {
+        rc: [0xa5; 16],
+        pc: 0x1234,
+        a: 0x56,
+        x: 0x78,
+        y: 0x9a,
+        s: 0xfd,
+        flags: 0xc3,
+    }
+}
+
+#[test]
+fn mos() {
+    check_arch::<MOSArch<16>, _, _>(mos_regs());
+    check_arch::<Mos65C02Arch<16>, _, _>(mos_regs());
+}
+
+#[test]
+fn w65816() {
+    check_arch::<W65816Arch<16>, _, _>(W65816Regs {
+        rc: [0xa5; 16],
+        pc: 0x8000,
+        pbr: 0x7e,
+        dbr: 0x01,
+        a: 0xbeef,
+        x: 0x1234,
+        y: 0x5678,
+        s: 0x1ff0,
+        d: 0x2100,
+        flags: 0xc3,
+        emulation: false,
+    });
+}
+
+#[test]
+fn m45gs02() {
+    check_arch::<Mos45GS02Arch<16>, _, _>(Mos45GS02Regs {
+        base: mos_regs(),
+        z: 0x12,
+        b: 0x34,
+        sph: 0x01,
+    });
+}
+
+#[test]
+fn huc6280() {
+    check_arch::<HuC6280Arch<16>, _, _>(HuC6280Regs {
+        base: mos_regs(),
+        mpr: [0xff, 0xf8, 1, 2, 3, 4, 5, 0],
+        high_speed: true,
+    });
+}
+
+#[test]
+fn banked() {
+    check_arch::<MosBankedArch<16>, _, _>(MosBankedRegs {
+        base: mos_regs(),
+        bank: 0x1234,
+    });
+}
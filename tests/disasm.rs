@@ -0,0 +1,120 @@
+use gdbstub_mos_arch::disasm::{decode, decode_bytes, AddressingMode};
+use gdbstub_mos_arch::InstructionSet;
+
+fn text(set: InstructionSet, address: u16, bytes: &[u8]) -> String {
+    let insn = decode_bytes(set, address, bytes).unwrap();
+    assert_eq!(insn.size(), bytes.len(), "{}", insn);
+    insn.to_string()
+}
+
+#[test]
+fn documented_opcode_counts() {
+    let documented = |set| {
+        (0..=255u8)
+            .filter(|op| !decode(set, 0, |_| *op).undocumented)
+            .count()
+    };
+    assert_eq!(documented(InstructionSet::Nmos6502), 151);
+    assert_eq!(documented(InstructionSet::W65C02), 212);
+}
+
+#[test]
+fn nmos_addressing_modes() {
+    let set = InstructionSet::Nmos6502;
+    assert_eq!(text(set, 0, &[0xea]), "NOP");
+    assert_eq!(text(set, 0, &[0x0a]), "ASL A");
+    assert_eq!(text(set, 0, &[0xa9, 0x0f]), "LDA #$0F");
+    assert_eq!(text(set, 0, &[0x85, 0xfb]), "STA $FB");
+    assert_eq!(text(set, 0, &[0xb5, 0x10]), "LDA $10,X");
+    assert_eq!(text(set, 0, &[0xb6, 0x10]), "LDX $10,Y");
+    assert_eq!(text(set, 0, &[0x8d, 0x20, 0xd0]), "STA $D020");
+    assert_eq!(text(set, 0, &[0x9d, 0x00, 0x04]), "STA $0400,X");
+    assert_eq!(text(set, 0, &[0xbe, 0x00, 0x04]), "LDX $0400,Y");
+    assert_eq!(text(set, 0, &[0x6c, 0xfc, 0xff]), "JMP ($FFFC)");
+    assert_eq!(text(set, 0, &[0xa1, 0xfb]), "LDA ($FB,X)");
+    assert_eq!(text(set, 0, &[0xb1, 0xfb]), "LDA ($FB),Y");
+    assert_eq!(text(set, 0x1000, &[0xd0, 0xfe]), "BNE $1000");
+    assert_eq!(text(set, 0x1000, &[0x10, 0x7f]), "BPL $1081");
+}
+
+#[test]
+fn nmos_undocumented_opcodes() {
+    let set = InstructionSet::Nmos6502;
+    assert_eq!(text(set, 0, &[0xa7, 0x02]), "LAX $02");
+    assert_eq!(text(set, 0, &[0x9f, 0x00, 0x20]), "SHA $2000,Y");
+    assert_eq!(text(set, 0, &[0x02]), "JAM");
+    let insn = decode_bytes(set, 0, &[0xeb, 0x01]).unwrap();
+    assert_eq!(insn.mnemonic, "SBC");
+    assert!(insn.undocumented);
+    assert!(!decode_bytes(set, 0, &[0xe9, 0x01]).unwrap().undocumented);
+}
+
+#[test]
+fn w65c02_extensions() {
+    let set = InstructionSet::W65C02;
+    assert_eq!(text(set, 0, &[0x1a]), "INC A");
+    assert_eq!(text(set, 0, &[0xb2, 0xfb]), "LDA ($FB)");
+    assert_eq!(text(set, 0, &[0x7c, 0x00, 0x20]), "JMP ($2000,X)");
+    assert_eq!(text(set, 0, &[0x9e, 0x00, 0x20]), "STZ $2000,X");
+    assert_eq!(text(set, 0, &[0xda]), "PHX");
+    assert_eq!(text(set, 0, &[0x87, 0x10]), "SMB0 $10");
+    assert_eq!(text(set, 0x1000, &[0x80, 0x02]), "BRA $1004");
+    assert_eq!(text(set, 0x1000, &[0x2f, 0x10, 0xfd]), "BBR2 $10,$1000");
+    assert_eq!(text(set, 0, &[0xcb]), "WAI");
+
+    let insn = decode_bytes(set, 0x1000, &[0x2f, 0x10, 0xfd]).unwrap();
+    assert_eq!(insn.mode, AddressingMode::ZeroPageRelative);
+    assert_eq!(insn.target(), Some(0x1000));
+}
+
+#[test]
+fn cycle_counts() {
+    let cycles = |set, bytes: &[u8]| {
+        let insn = decode_bytes(set, 0, bytes).unwrap();
+        (insn.cycles, insn.page_cycle)
+    };
+    let nmos = InstructionSet::Nmos6502;
+    let cmos = InstructionSet::W65C02;
+    assert_eq!(cycles(nmos, &[0x00]), (7, false));
+    assert_eq!(cycles(nmos, &[0xbd, 0, 0]), (4, true));
+    assert_eq!(cycles(nmos, &[0x9d, 0, 0]), (5, false));
+    assert_eq!(cycles(nmos, &[0xb1, 0]), (5, true));
+    assert_eq!(cycles(nmos, &[0x1e, 0, 0]), (7, false));
+    assert_eq!(cycles(nmos, &[0x6c, 0, 0]), (5, false));
+    assert_eq!(cycles(nmos, &[0xd0, 0]), (2, false));
+    assert_eq!(cycles(cmos, &[0x1e, 0, 0]), (6, true));
+    assert_eq!(cycles(cmos, &[0xfe, 0, 0]), (7, false));
+    assert_eq!(cycles(cmos, &[0x6c, 0, 0]), (6, false));
+    assert_eq!(cycles(cmos, &[0x80, 0]), (3, false));
+}
+
+#[test]
+fn symbols() {
+    let set = InstructionSet::Nmos6502;
+    let symbolize = |addr| match addr {
+        0x00fb => Some("ptr"),
+        0x0810 => Some("loop"),
+        0xd020 => Some("BORDER"),
+        _ => None,
+    };
+    let show = |bytes: &[u8]| {
+        decode_bytes(set, 0x0810, bytes)
+            .unwrap()
+            .display_with(symbolize)
+            .to_string()
+    };
+    assert_eq!(show(&[0xb1, 0xfb]), "LDA (ptr),Y");
+    assert_eq!(show(&[0x8d, 0x20, 0xd0]), "STA BORDER");
+    assert_eq!(show(&[0xd0, 0xfe]), "BNE loop");
+    // Immediates are never symbolized.
+    assert_eq!(show(&[0xa9, 0xfb]), "LDA #$FB");
+    assert_eq!(show(&[0xad, 0x21, 0xd0]), "LDA $D021");
+}
+
+#[test]
+fn truncated_bytes() {
+    let set = InstructionSet::Nmos6502;
+    assert!(decode_bytes(set, 0, &[]).is_none());
+    assert!(decode_bytes(set, 0, &[0x4c, 0x00]).is_none());
+    assert_eq!(decode_bytes(set, 0, &[0xe8, 0x00]).unwrap().size(), 1);
+}
@@ -0,0 +1,215 @@
+use gdbstub_mos_arch::loader::{Elf, ElfError, Segment};
+
+const SHT_PROGBITS: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const SHT_STRTAB: u32 = 3;
+const SHT_NOBITS: u32 = 8;
+const SHF_ALLOC: u32 = 2;
+
+struct SectionSpec {
+    name: &'static str,
+    kind: u32,
+    flags: u32,
+    addr: u32,
+    size: u32,
+    contents: Vec<u8>,
+    link: u32,
+}
+
+fn section(name: &'static str, kind: u32, addr: u32, size: u32) -> SectionSpec {
+    SectionSpec {
+        name,
+        kind,
+        flags: SHF_ALLOC,
+        addr,
+        size,
+        contents: Vec::new(),
+        link: 0,
+    }
+}
+
+/// Builds an llvm-mos style executable: `code` in `.text` at $0200, followed
+/// by four bytes of `.bss` and two of `.noinit`, and the `symbols` in
+/// `.symtab`.
+fn elf(code: &[u8], entry: u32, symbols: &[(&str, u32)]) -> Vec<u8> {
+    let text_addr = 0x200;
+    let bss_addr = text_addr + code.len() as u32;
+    let noinit_addr = bss_addr + 4;
+
+    let mut strtab = vec![0];
+    let mut symtab = vec![0; 16];
+    for (name, value) in symbols {
+        symtab.extend_from_slice(&(strtab.len() as u32).to_le_bytes());
+        symtab.extend_from_slice(&value.to_le_bytes());
+        symtab.extend_from_slice(&[0; 8]);
+        strtab.extend_from_slice(name.as_bytes());
+        strtab.push(0);
+    }
+
+    let mut sections = vec![
+        section("", 0, 0, 0),
+        SectionSpec {
+            contents: code.to_vec(),
+            ..section(".text", SHT_PROGBITS, text_addr, code.len() as u32)
+        },
+        section(".bss", SHT_NOBITS, bss_addr, 4),
+        section(".noinit", SHT_NOBITS, noinit_addr, 2),
+        SectionSpec {
+            flags: 0,
+            link: 5,
+            size: symtab.len() as u32,
+            contents: symtab,
+            ..section(".symtab", SHT_SYMTAB, 0, 0)
+        },
+        SectionSpec {
+            flags: 0,
+            size: strtab.len() as u32,
+            contents: strtab,
+            ..section(".strtab", SHT_STRTAB, 0, 0)
+        },
+    ];
+    let mut shstrtab = vec![0];
+    let mut name_offsets = vec![0];
+    for s in &sections[1..] {
+        name_offsets.push(shstrtab.len() as u32);
+        shstrtab.extend_from_slice(s.name.as_bytes());
+        shstrtab.push(0);
+    }
+    name_offsets.push(shstrtab.len() as u32);
+    shstrtab.extend_from_slice(b".shstrtab\0");
+    sections.push(SectionSpec {
+        flags: 0,
+        size: shstrtab.len() as u32,
+        contents: shstrtab,
+        ..section(".shstrtab", SHT_STRTAB, 0, 0)
+    });
+
+    // Header, one program header, then section contents and headers.
+    let mut out = vec![0; 52 + 32];
+    let mut offsets = Vec::new();
+    for s in &sections {
+        offsets.push(out.len() as u32);
+        out.extend_from_slice(&s.contents);
+    }
+    let shoff = out.len() as u32;
+    for (i, s) in sections.iter().enumerate() {
+        for word in [
+            name_offsets[i],
+            s.kind,
+            s.flags,
+            s.addr,
+            offsets[i],
+            s.size,
+            s.link,
+            0,
+            1,
+            if s.kind == SHT_SYMTAB { 16 } else { 0 },
+        ] {
+            out.extend_from_slice(&word.to_le_bytes());
+        }
+    }
+
+    out[..8].copy_from_slice(b"\x7fELF\x01\x01\x01\x00");
+    out[16..18].copy_from_slice(&2u16.to_le_bytes());
+    out[18..20].copy_from_slice(&6502u16.to_le_bytes());
+    out[20..24].copy_from_slice(&1u32.to_le_bytes());
+    out[24..28].copy_from_slice(&entry.to_le_bytes());
+    out[28..32].copy_from_slice(&52u32.to_le_bytes());
+    out[32..36].copy_from_slice(&shoff.to_le_bytes());
+    for (offset, half) in [(40, 52), (42, 32), (44, 1), (46, 40), (48, 7), (50, 6)] {
+        out[offset..offset + 2].copy_from_slice(&(half as u16).to_le_bytes());
+    }
+
+    let phdr = [
+        1,
+        offsets[1],
+        text_addr,
+        text_addr,
+        code.len() as u32,
+        code.len() as u32 + 6,
+        5,
+        1,
+    ];
+    for (i, word) in phdr.iter().enumerate() {
+        out[52 + i * 4..56 + i * 4].copy_from_slice(&word.to_le_bytes());
+    }
+    out
+}
+
+#[test]
+fn loads_segments() {
+    // LDA #0; STA __rc2; RTS
+    let code = [0xa9, 0x00, 0x85, 0x04, 0x60];
+    let bytes = elf(&code, 0x200, &[("_start", 0x200)]);
+    let elf = Elf::parse(&bytes).unwrap();
+    assert_eq!(elf.entry(), 0x200);
+
+    let segments: Vec<_> = elf.segments().collect();
+    assert_eq!(
+        segments,
+        [Ok(Segment {
+            address: 0x200,
+            data: &code[..],
+        })]
+    );
+
+    let mut mem = [0xff; 0x10000];
+    assert_eq!(elf.load(&mut mem), Ok(0x200));
+    assert_eq!(mem[0x200..0x205], code);
+    // .bss is cleared and .noinit is left alone.
+    assert_eq!(mem[0x205..0x209], [0; 4]);
+    assert_eq!(mem[0x209..0x20b], [0xff; 2]);
+    assert_eq!(mem[0x1ff], 0xff);
+}
+
+#[test]
+fn finds_imaginary_registers() {
+    let bytes = elf(
+        &[0x60],
+        0x200,
+        &[("_start", 0x200), ("__rc0", 0x02), ("__rc1", 0x03)],
+    );
+    let elf = Elf::parse(&bytes).unwrap();
+    assert_eq!(elf.rc_base(), Some(0x02));
+    assert_eq!(elf.symbol("__rc1"), Some(0x03));
+    assert_eq!(elf.symbol("_start"), Some(0x200));
+    assert_eq!(elf.symbol("__rc"), None);
+
+    let bytes = self::elf(&[0x60], 0x200, &[]);
+    assert_eq!(Elf::parse(&bytes).unwrap().rc_base(), None);
+}
+
+#[test]
+fn rejects_bad_files() {
+    let bytes = elf(&[0x60], 0x200, &[]);
+    assert_eq!(Elf::parse(b"MZ").unwrap_err(), ElfError::NotElf);
+    assert_eq!(Elf::parse(&bytes[..40]).unwrap_err(), ElfError::Truncated);
+    assert_eq!(Elf::parse(&bytes[..100]).unwrap_err(), ElfError::Truncated);
+
+    let mut wrong = bytes.clone();
+    wrong[4] = 2;
+    assert_eq!(Elf::parse(&wrong).unwrap_err(), ElfError::UnsupportedClass);
+    let mut wrong = bytes.clone();
+    wrong[18..20].copy_from_slice(&62u16.to_le_bytes());
+    assert_eq!(Elf::parse(&wrong).unwrap_err(), ElfError::WrongMachine(62));
+
+    // Banked code can be iterated over but not loaded into 64K.
+    let bytes = elf(&[0x60], 0x200, &[]);
+    let mut banked = bytes.clone();
+    banked[52 + 12..52 + 16].copy_from_slice(&0x1_8000u32.to_le_bytes());
+    let elf = Elf::parse(&banked).unwrap();
+    assert_eq!(elf.segments().next().unwrap().unwrap().address, 0x1_8000);
+    let mut mem = [0; 0x10000];
+    assert_eq!(elf.load(&mut mem), Err(ElfError::OutOfRange(0x1_8000)));
+    assert_eq!(mem, [0; 0x10000]);
+}
+
+#[test]
+fn loads_vectors() {
+    // A segment ending at $FFFF, like the interrupt vectors.
+    let mut bytes = elf(&[0x00, 0x02, 0x00, 0x02], 0x200, &[]);
+    bytes[52 + 12..52 + 16].copy_from_slice(&0xfffcu32.to_le_bytes());
+    let mut mem = [0; 0x10000];
+    assert_eq!(Elf::parse(&bytes).unwrap().load(&mut mem), Ok(0x200));
+    assert_eq!(mem[0xfffc..], [0x00, 0x02, 0x00, 0x02]);
+}
@@ -0,0 +1,347 @@
+use gdbstub::common::Signal;
+use gdbstub::stub::SingleThreadStopReason;
+use gdbstub::target::ext::base::single_register_access::SingleRegisterAccess;
+use gdbstub::target::ext::base::singlethread::{
+    SingleThreadBase, SingleThreadResume, SingleThreadSingleStep,
+};
+use gdbstub::target::ext::breakpoints::{HwBreakpoint, SwBreakpoint};
+use gdbstub::target::ext::memory_map::MemoryMap;
+use gdbstub::target::ext::section_offsets::Offsets;
+use gdbstub::target::Target;
+use gdbstub_mos_arch::emulator::{Emulator, RunEvent};
+use gdbstub_mos_arch::loader::Relocation;
+use gdbstub_mos_arch::memory_map::C64;
+use gdbstub_mos_arch::{
+    ImaginaryRegs, MosBreakpointKind, MosRegId, MosRegs, IRQ_VECTOR, NMI_VECTOR, RESET_VECTOR,
+};
+
+const FLAG_C: u8 = 0x01;
+const FLAG_Z: u8 = 0x02;
+const FLAG_I: u8 = 0x04;
+const FLAG_B: u8 = 0x10;
+const FLAG_V: u8 = 0x40;
+const FLAG_N: u8 = 0x80;
+
+/// Returns an emulator with `code` loaded at $0200 and the reset vector
+/// pointing there, after reset.
+fn emulator(code: &[u8]) -> Emulator<Box<[u8; 0x10000]>> {
+    let mut mem = Box::new([0; 0x10000]);
+    mem[0x200..0x200 + code.len()].copy_from_slice(code);
+    mem[RESET_VECTOR as usize..RESET_VECTOR as usize + 2].copy_from_slice(&[0x00, 0x02]);
+    let mut emu = Emulator::new(mem);
+    emu.reset();
+    emu
+}
+
+/// Runs until the CPU jams, returning the cycles taken.
+fn run_to_jam(emu: &mut Emulator<Box<[u8; 0x10000]>>) -> u64 {
+    let start = emu.cpu.cycles;
+    while !emu.cpu.jammed {
+        emu.cpu.step(&mut emu.bus);
+    }
+    emu.cpu.cycles - start
+}
+
+#[test]
+fn counts_cycles() {
+    // LDX #5; loop: DEX; BNE loop; JAM
+    let mut emu = emulator(&[0xa2, 0x05, 0xca, 0xd0, 0xfd, 0x02]);
+    assert_eq!(run_to_jam(&mut emu), 2 + 5 * 2 + 4 * 3 + 2);
+    assert_eq!(emu.cpu.regs.x, 0);
+    assert_eq!(emu.cpu.regs.pc, 0x205);
+
+    // LDA $02F0,X takes a cycle more for crossing into page 3, STA $02F0,X
+    // always takes 5.
+    let mut emu = emulator(&[0xbd, 0xf0, 0x02, 0x9d, 0xf0, 0x02, 0x02]);
+    emu.cpu.regs.x = 0x20;
+    assert_eq!(run_to_jam(&mut emu), 5 + 5);
+}
+
+#[test]
+fn arithmetic() {
+    // CLC; LDA #$50; ADC #$50 sets V and N.
+    let mut emu = emulator(&[0x18, 0xa9, 0x50, 0x69, 0x50, 0x02]);
+    run_to_jam(&mut emu);
+    assert_eq!(emu.cpu.regs.a, 0xa0);
+    assert_eq!(
+        emu.cpu.regs.flags & (FLAG_V | FLAG_N | FLAG_C),
+        FLAG_V | FLAG_N
+    );
+
+    // SEC; LDA #$00; SBC #$01 borrows.
+    let mut emu = emulator(&[0x38, 0xa9, 0x00, 0xe9, 0x01, 0x02]);
+    run_to_jam(&mut emu);
+    assert_eq!(emu.cpu.regs.a, 0xff);
+    assert_eq!(emu.cpu.regs.flags & (FLAG_C | FLAG_N), FLAG_N);
+
+    // SED; CLC; LDA #$19; ADC #$29 is $48 in BCD; SEC; SBC #$49 is $99
+    // with a borrow.
+    let mut emu = emulator(&[
+        0xf8, 0x18, 0xa9, 0x19, 0x69, 0x29, 0x85, 0x10, 0x38, 0xe9, 0x49, 0x02,
+    ]);
+    run_to_jam(&mut emu);
+    assert_eq!(emu.bus[0x10], 0x48);
+    assert_eq!(emu.cpu.regs.a, 0x99);
+    assert_eq!(emu.cpu.regs.flags & FLAG_C, 0);
+}
+
+#[test]
+fn undocumented_opcodes() {
+    // LAX $10; DCP $11
+    let mut emu = emulator(&[0xa7, 0x10, 0xc7, 0x11, 0x02]);
+    emu.bus[0x10] = 0x42;
+    emu.bus[0x11] = 0x43;
+    run_to_jam(&mut emu);
+    assert_eq!((emu.cpu.regs.a, emu.cpu.regs.x), (0x42, 0x42));
+    assert_eq!(emu.bus[0x11], 0x42);
+    assert_eq!(emu.cpu.regs.flags & (FLAG_Z | FLAG_C), FLAG_Z | FLAG_C);
+}
+
+#[test]
+fn control_flow() {
+    // JSR sub; JMP ($02FF); sub: RTS
+    let mut emu = emulator(&[0x20, 0x10, 0x02, 0x6c, 0xff, 0x02]);
+    emu.bus[0x210] = 0x60;
+    // JMP ($02FF) reads the high byte from $0200, not $0300.
+    emu.bus[0x2ff] = 0x00;
+    emu.bus[0x300] = 0x04;
+    emu.bus[0x2000] = 0x02;
+    emu.cpu.step(&mut emu.bus);
+    assert_eq!(emu.cpu.regs.pc, 0x210);
+    assert_eq!(emu.cpu.regs.s, 0xfd - 2);
+    emu.cpu.step(&mut emu.bus);
+    assert_eq!(emu.cpu.regs.pc, 0x203);
+    emu.cpu.step(&mut emu.bus);
+    assert_eq!(emu.cpu.regs.pc, 0x2000);
+}
+
+#[test]
+fn interrupts() {
+    // CLI; NOP; NOP; BRK at $0203. The handler at $0300 is RTI.
+    let mut emu = emulator(&[0x58, 0xea, 0xea, 0x00, 0xff, 0xea]);
+    emu.bus[IRQ_VECTOR as usize..].copy_from_slice(&[0x00, 0x03]);
+    emu.bus[NMI_VECTOR as usize..NMI_VECTOR as usize + 2].copy_from_slice(&[0x00, 0x03]);
+    emu.bus[0x300] = 0x40;
+    emu.cpu.step(&mut emu.bus);
+
+    // IRQ is taken when enabled, and pushes the flags with B clear.
+    emu.cpu.set_irq(true);
+    assert_eq!(emu.cpu.step(&mut emu.bus), 7);
+    assert_eq!(emu.cpu.regs.pc, 0x300);
+    assert_eq!(emu.bus[0x100 | (emu.cpu.regs.s as usize + 1)] & FLAG_B, 0);
+    emu.cpu.set_irq(false);
+    emu.cpu.step(&mut emu.bus);
+    assert_eq!(emu.cpu.regs.pc, 0x201);
+    assert_eq!(emu.cpu.regs.flags & FLAG_I, 0);
+
+    // NMI ignores I.
+    emu.cpu.regs.flags |= FLAG_I;
+    emu.cpu.nmi();
+    emu.cpu.step(&mut emu.bus);
+    assert_eq!(emu.cpu.regs.pc, 0x300);
+    for _ in 0..3 {
+        emu.cpu.step(&mut emu.bus);
+    }
+    assert_eq!(emu.cpu.regs.pc, 0x203);
+
+    // BRK pushes the address after its padding byte, with B set.
+    emu.cpu.step(&mut emu.bus);
+    assert_eq!(emu.cpu.regs.pc, 0x300);
+    assert_ne!(emu.bus[0x100 | (emu.cpu.regs.s as usize + 1)] & FLAG_B, 0);
+    emu.cpu.step(&mut emu.bus);
+    assert_eq!(emu.cpu.regs.pc, 0x205);
+}
+
+#[test]
+fn target_breakpoints_and_stepping() {
+    // loop: INX; INY; JMP loop
+    let mut emu = emulator(&[0xe8, 0xc8, 0x4c, 0x00, 0x02]);
+    assert!(matches!(
+        emu.add_sw_breakpoint(0x201, MosBreakpointKind::Software),
+        Ok(true)
+    ));
+    assert!(matches!(
+        emu.add_sw_breakpoint(0x201, MosBreakpointKind::Banked(1)),
+        Ok(false)
+    ));
+
+    SingleThreadResume::resume(&mut emu, None).unwrap();
+    let event = emu.run(|| false);
+    assert_eq!(
+        event,
+        RunEvent::Stopped(SingleThreadStopReason::SwBreak(()))
+    );
+    assert_eq!(emu.cpu.regs.pc, 0x201);
+
+    // Resuming from a breakpoint runs until it is hit again.
+    let event = emu.run(|| false);
+    assert_eq!(
+        event,
+        RunEvent::Stopped(SingleThreadStopReason::SwBreak(()))
+    );
+    let mut regs = MosRegs::default();
+    assert!(emu.read_registers(&mut regs).is_ok());
+    assert_eq!((regs.pc, regs.x, regs.y), (0x201, 2, 1));
+
+    SingleThreadSingleStep::step(&mut emu, None).unwrap();
+    assert_eq!(
+        emu.run(|| false),
+        RunEvent::Stopped(SingleThreadStopReason::DoneStep)
+    );
+    assert_eq!(emu.cpu.regs.pc, 0x202);
+
+    // Without breakpoints, the loop runs until there is incoming data.
+    assert!(matches!(
+        emu.remove_sw_breakpoint(0x201, MosBreakpointKind::Software),
+        Ok(true)
+    ));
+    SingleThreadResume::resume(&mut emu, None).unwrap();
+    let mut polls = 0;
+    assert_eq!(
+        emu.run(|| {
+            polls += 1;
+            polls == 3
+        }),
+        RunEvent::IncomingData
+    );
+}
+
+#[test]
+fn target_hardware_breakpoints() {
+    // loop: INX; INY; JMP loop
+    let mut emu = emulator(&[0xe8, 0xc8, 0x4c, 0x00, 0x02]);
+    assert!(matches!(
+        emu.add_hw_breakpoint(0x201, MosBreakpointKind::Hardware),
+        Ok(true)
+    ));
+    SingleThreadResume::resume(&mut emu, None).unwrap();
+    assert_eq!(
+        emu.run(|| false),
+        RunEvent::Stopped(SingleThreadStopReason::HwBreak(()))
+    );
+
+    // Software and hardware breakpoints at the same address are removed
+    // separately.
+    assert!(matches!(
+        emu.add_sw_breakpoint(0x201, MosBreakpointKind::Software),
+        Ok(true)
+    ));
+    assert!(matches!(
+        emu.remove_hw_breakpoint(0x201, MosBreakpointKind::Hardware),
+        Ok(true)
+    ));
+    assert!(matches!(
+        emu.remove_hw_breakpoint(0x201, MosBreakpointKind::Hardware),
+        Ok(false)
+    ));
+    assert!(emu.has_breakpoint(0x201));
+    assert_eq!(
+        emu.run(|| false),
+        RunEvent::Stopped(SingleThreadStopReason::SwBreak(()))
+    );
+    assert!(matches!(
+        emu.remove_sw_breakpoint(0x201, MosBreakpointKind::Software),
+        Ok(true)
+    ));
+    assert!(!emu.has_breakpoint(0x201));
+}
+
+#[test]
+fn target_memory_and_jam() {
+    let mut emu = emulator(&[0x02]);
+    assert!(emu.write_addrs(0xfff0, &[0xaa; 16]).is_ok());
+    assert!(emu.write_addrs(0xfff0, &[0xaa; 17]).is_err());
+    let mut buf = [0; 32];
+    assert!(matches!(emu.read_addrs(0xfff0, &mut buf), Ok(16)));
+    assert_eq!(buf[..16], [0xaa; 16]);
+
+    SingleThreadResume::resume(&mut emu, None).unwrap();
+    assert_eq!(
+        emu.run(|| false),
+        RunEvent::Stopped(SingleThreadStopReason::Signal(Signal::SIGILL))
+    );
+    assert_eq!(emu.cpu.regs.pc, 0x200);
+}
+
+#[test]
+fn run_with_stop_check() {
+    // LDA #3; STA $FFF8; JMP $0205
+    let mut emu = emulator(&[0xa9, 0x03, 0x8d, 0xf8, 0xff, 0x4c, 0x05, 0x02]);
+    SingleThreadResume::resume(&mut emu, None).unwrap();
+    let event = emu.run_with(
+        || false,
+        |mem| (mem[0xfff8] != 0).then(|| SingleThreadStopReason::Exited(mem[0xfff8])),
+    );
+    assert_eq!(event, RunEvent::Stopped(SingleThreadStopReason::Exited(3)));
+    assert_eq!(emu.cpu.regs.pc, 0x205);
+}
+
+#[test]
+fn imaginary_registers_in_memory() {
+    // LDA #$42; STA __rc2; JAM
+    let mut emu = emulator(&[0xa9, 0x42, 0x85, 0x04, 0x02]);
+    emu.imaginary_regs = Some(ImaginaryRegs::new(0x02));
+    run_to_jam(&mut emu);
+
+    let mut regs = MosRegs::default();
+    assert!(emu.read_registers(&mut regs).is_ok());
+    assert_eq!(regs.rc[2], 0x42);
+
+    regs.set_rs(3, 0x1234);
+    assert!(emu.write_registers(&regs).is_ok());
+    assert_eq!(emu.bus[0x08..0x0a], [0x34, 0x12]);
+
+    assert!(emu.write_register((), MosRegId::RC(0), &[0x99]).is_ok());
+    assert_eq!(emu.bus[0x02], 0x99);
+    assert!(emu.write_addrs(0x03, &[0x77]).is_ok());
+    let mut buf = [0; 1];
+    assert!(matches!(
+        emu.read_register((), MosRegId::RC(1), &mut buf),
+        Ok(1)
+    ));
+    assert_eq!(buf, [0x77]);
+}
+
+#[test]
+fn reports_section_offsets() {
+    let mut emu = emulator(&[0x02]);
+    assert!(emu.support_section_offsets().is_none());
+
+    emu.relocation = Some(Relocation {
+        text: 0x3000,
+        data: 0x200d,
+        bss: 0x1012,
+    });
+    let offsets = emu.support_section_offsets().unwrap();
+    assert!(matches!(
+        offsets.get_section_offsets(),
+        Ok(Offsets::Sections {
+            text: 0x3000,
+            data: 0x200d,
+            bss: Some(0x1012),
+        })
+    ));
+}
+
+#[test]
+fn reports_memory_map() {
+    let mut emu = emulator(&[0x02]);
+    assert!(emu.support_memory_map().is_none());
+
+    emu.memory_map = Some(C64);
+    assert!(emu.support_memory_map().is_some());
+    let mut buf = [0; 1024];
+    let Ok(len) = emu.memory_map_xml(0, 1024, &mut buf) else {
+        panic!("can't read the memory map");
+    };
+    let xml = std::str::from_utf8(&buf[..len]).unwrap();
+    assert!(xml.contains("<memory type=\"rom\" start=\"0xe000\" length=\"0x2000\"/>"));
+
+    let mut piece = [0; 16];
+    assert!(matches!(emu.memory_map_xml(5, 8, &mut piece), Ok(8)));
+    assert_eq!(piece[..8], buf[5..13]);
+    assert!(matches!(
+        emu.memory_map_xml(len as u64, 8, &mut piece),
+        Ok(0)
+    ));
+}
@@ -0,0 +1,225 @@
+//! Runs the `mos-gdbserver` binary and talks to it over the GDB remote
+//! protocol.
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::process::{Child, Command, Output, Stdio};
+use std::thread;
+use std::time::Duration;
+
+fn gdbserver() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_mos-gdbserver"))
+}
+
+fn run(args: &[&str]) -> Output {
+    gdbserver().args(args).output().unwrap()
+}
+
+/// Writes `contents` to a file named `name` in a scratch directory.
+fn program(name: &str, contents: &[u8]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("mos-gdbserver-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+/// A running server and the debugger's connection to it.
+struct Session {
+    child: Child,
+    stream: TcpStream,
+}
+
+impl Session {
+    fn start(program: &PathBuf, args: &[&str]) -> Self {
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let listen = format!("127.0.0.1:{}", port);
+        let mut child = gdbserver()
+            .args(["--listen", &listen])
+            .args(args)
+            .arg(program)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        for _ in 0..100 {
+            if let Ok(stream) = TcpStream::connect(&listen) {
+                return Session { child, stream };
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        let _ = child.kill();
+        let _ = child.wait();
+        panic!("mos-gdbserver didn't start listening");
+    }
+
+    /// Sends the packet `body` and returns the body of the reply.
+    fn packet(&mut self, body: &str) -> String {
+        let checksum = body.bytes().fold(0u8, |sum, b| sum.wrapping_add(b));
+        write!(self.stream, "${}#{:02x}", body, checksum).unwrap();
+        let mut reply = Vec::new();
+        let mut byte = [0];
+        loop {
+            self.stream.read_exact(&mut byte).unwrap();
+            match byte[0] {
+                b'+' if reply.is_empty() => {}
+                b'#' => break,
+                b => reply.push(b),
+            }
+        }
+        let mut checksum = [0; 2];
+        self.stream.read_exact(&mut checksum).unwrap();
+        self.stream.write_all(b"+").unwrap();
+        String::from_utf8(reply[1..].to_vec()).unwrap()
+    }
+
+    /// Returns the PC, the first register of the `g` packet.
+    fn pc(&mut self) -> u16 {
+        let regs = self.packet("g");
+        let bytes = u16::from_str_radix(&regs[..4], 16).unwrap();
+        bytes.swap_bytes()
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[test]
+fn help() {
+    let output = run(&["--help"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("Usage: mos-gdbserver"));
+}
+
+#[test]
+fn argument_errors() {
+    for (args, message) in [
+        (&[][..], "missing PROGRAM"),
+        (&["--bogus", "a.bin"], "unknown option `--bogus`"),
+        (&["a.bin", "b.bin"], "unexpected argument `b.bin`"),
+        (&["--load"], "`--load` needs a value"),
+        (&["--load", "$10000", "a.bin"], "invalid address `$10000`"),
+        (&["--rom", "0xe000", "a.bin"], "expected START-END"),
+        (&["--rom", "$ffff-$e000", "a.bin"], "START is after END"),
+        (&["--machine", "pet", "a.bin"], "unknown machine `pet`"),
+    ] {
+        let output = run(args);
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains(message), "{:?}: {}", args, stderr);
+    }
+}
+
+#[test]
+fn load_errors() {
+    let prg = program("short.prg", &[0x01]);
+    let output = run(&[prg.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("can't load"));
+
+    let raw = program("large.bin", &[0xea; 0x200]);
+    let output = run(&["--load", "0xff00", raw.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("doesn't fit at $FF00"));
+
+    // An ELF file for another machine isn't mistaken for a raw binary.
+    let mut elf = vec![0; 52];
+    elf[..7].copy_from_slice(b"\x7fELF\x01\x01\x01");
+    elf[18] = 3;
+    let elf = program("x86.elf", &elf);
+    let output = run(&[elf.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("ELF file for machine 3, not MOS"));
+}
+
+#[test]
+fn detects_prg() {
+    // 10 SYS 2062, then NOP.
+    let mut prg = vec![0x01, 0x08, 0x0c, 0x08, 0x0a, 0x00, 0x9e];
+    prg.extend_from_slice(b"2062\0\0\0\xea");
+    let path = program("sys.prg", &prg);
+    assert_eq!(Session::start(&path, &[]).pc(), 2062);
+}
+
+#[test]
+fn detects_intel_hex() {
+    let path = program(
+        "start.hex",
+        b":03100000EAEAEA2F\n:0400000500001234B1\n:00000001FF\n",
+    );
+    assert_eq!(Session::start(&path, &[]).pc(), 0x1234);
+}
+
+#[test]
+fn detects_s_record() {
+    let path = program("start.s19", b"S1061000EAEAEA2B\nS9034000BC\n");
+    assert_eq!(Session::start(&path, &[]).pc(), 0x4000);
+}
+
+#[test]
+fn raw_binary_by_default() {
+    let path = program("code.bin", &[0xea; 4]);
+    assert_eq!(Session::start(&path, &["--load", "$0300"]).pc(), 0x0300);
+    // The entry option overrides the reset vector.
+    let mut session = Session::start(&path, &["--entry", "0x0302"]);
+    assert_eq!(session.pc(), 0x0302);
+}
+
+#[test]
+fn detects_ines() {
+    // One 16K PRG-ROM bank, mirrored at $8000 and $C000, resetting to $8123.
+    let mut rom = b"NES\x1a\x01\x00".to_vec();
+    rom.resize(16, 0);
+    rom.resize(16 + 0x4000, 0xea);
+    rom[16 + 0x3ffc..16 + 0x3ffe].copy_from_slice(&[0x23, 0x81]);
+    // Detected by its contents, whatever the extension.
+    let path = program("game.bin", &rom);
+    assert_eq!(Session::start(&path, &[]).pc(), 0x8123);
+
+    let path = program("truncated.nes", &rom[..0x1000]);
+    let output = run(&[path.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("shorter than its header says"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn detects_xex() {
+    let mut xex = vec![0xff, 0xff];
+    // Bytes at the top of the stack, where the INIT call's return address
+    // goes.
+    xex.extend([0xfe, 0x01, 0xff, 0x01, 0x12, 0x34]);
+    // INIT routine at $0600: LDA #$42; STA $0700; RTS.
+    xex.extend([0x00, 0x06, 0x05, 0x06, 0xa9, 0x42, 0x8d, 0x00, 0x07, 0x60]);
+    xex.extend([0xe2, 0x02, 0xe3, 0x02, 0x00, 0x06]);
+    // Program at $2000, overwriting the INIT routine's code afterwards.
+    xex.extend([0x00, 0x20, 0x01, 0x20, 0xea, 0xea]);
+    xex.extend([0x00, 0x06, 0x00, 0x06, 0x00]);
+    xex.extend([0xe0, 0x02, 0xe1, 0x02, 0x00, 0x20]);
+    let path = program("game.xex", &xex);
+    let mut session = Session::start(&path, &[]);
+    assert_eq!(session.pc(), 0x2000);
+    // The INIT routine ran before the segment after it was loaded.
+    assert_eq!(session.packet("m700,1"), "42");
+    assert_eq!(session.packet("m600,1"), "00");
+    // The stack bytes are restored after the call.
+    assert_eq!(session.packet("m1fe,2"), "1234");
+    drop(session);
+
+    // The INIT routine can't write to ROM either.
+    let mut session = Session::start(&path, &["--rom", "$0700-$07ff"]);
+    assert_eq!(session.packet("m700,1"), "00");
+}
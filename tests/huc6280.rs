@@ -0,0 +1,37 @@
+use gdbstub::arch::{Arch, RegId, Registers};
+use gdbstub_mos_arch::{HuC6280Arch, HuC6280RegId, HuC6280Regs, MosRegId, RegAccess, RegTable};
+
+#[test]
+fn mprs_are_registers() {
+    let xml = HuC6280Arch::<32>::target_description_xml().unwrap();
+    assert_eq!(xml.matches("<reg ").count(), HuC6280RegId::<32>::COUNT);
+    for n in 0..8 {
+        assert!(xml.contains(&format!("<reg name=\"MPR{}\"", n)));
+    }
+    assert!(xml.contains("<reg name=\"T\""));
+    for (reg, info) in HuC6280RegId::<32>::iter() {
+        let (raw, size) = HuC6280RegId::<32>::from_raw_id(info.regnum).unwrap();
+        assert_eq!(raw, reg);
+        assert_eq!(size.unwrap().get(), info.size());
+    }
+}
+
+#[test]
+fn mpr_translation_and_roundtrip() {
+    let mut regs: HuC6280Regs = HuC6280Regs::default();
+    regs.set_reg(&HuC6280RegId::MPR(7), 0x00).unwrap();
+    regs.set_reg(&HuC6280RegId::MPR(1), 0xf8).unwrap();
+    regs.set_reg(&HuC6280RegId::Speed, 1).unwrap();
+    regs.set_reg(&HuC6280RegId::Mos(MosRegId::U), 1).unwrap();
+    assert_eq!(regs.physical_address(0x2010), 0x1f_0010);
+    assert_eq!(regs.physical_address(0xfffe), 0x00_1ffe);
+    assert_eq!(regs.set_reg(&HuC6280RegId::MPR(8), 0), None);
+    assert_eq!(regs.set_reg(&HuC6280RegId::Speed, 2), None);
+
+    let mut bytes = Vec::new();
+    regs.gdb_serialize(|b| bytes.push(b.unwrap()));
+    assert_eq!(bytes.len(), HuC6280RegId::<32>::PACKET_SIZE);
+    let mut decoded: HuC6280Regs = HuC6280Regs::default();
+    assert_eq!(decoded.gdb_deserialize(&bytes), Ok(()));
+    assert_eq!(decoded, regs);
+}
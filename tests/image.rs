@@ -0,0 +1,245 @@
+use gdbstub_mos_arch::loader::{raw, ImageError, IntelHex, SRecord, Segment};
+
+/// Returns an Intel HEX record line with a correct checksum.
+fn ihex(kind: u8, addr: u16, data: &[u8]) -> String {
+    let mut bytes = vec![data.len() as u8];
+    bytes.extend(addr.to_be_bytes());
+    bytes.push(kind);
+    bytes.extend(data);
+    let sum = bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+    bytes.push(sum.wrapping_neg());
+    let hex: String = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+    format!(":{}\n", hex)
+}
+
+/// Returns an S-record line with a correct checksum.
+fn srec(kind: u8, addr: &[u8], data: &[u8]) -> String {
+    let mut bytes = vec![(addr.len() + data.len() + 1) as u8];
+    bytes.extend(addr);
+    bytes.extend(data);
+    let sum = bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+    bytes.push(!sum);
+    let hex: String = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+    format!("S{}{}\n", kind, hex)
+}
+
+#[test]
+fn intel_hex() {
+    let text = b":10010000214601360121470136007EFE09D2190140\r\n\
+        :100110002146017E17C20001FF5F16002148011928\r\n\
+        \r\n\
+        :0400000500000100F6\r\n\
+        :00000001FF\r\n\
+        garbage after the end\n";
+    let hex = IntelHex::parse(text).unwrap();
+    assert_eq!(hex.start_address(), Some(0x0100));
+    let records: Vec<_> = hex.records().collect();
+    assert_eq!(records.len(), 2);
+    assert_eq!(
+        records[0].segment(),
+        Segment {
+            address: 0x0100,
+            data: &[
+                0x21, 0x46, 0x01, 0x36, 0x01, 0x21, 0x47, 0x01, 0x36, 0x00, 0x7e, 0xfe, 0x09, 0xd2,
+                0x19, 0x01
+            ],
+        }
+    );
+    assert_eq!(records[1].address, 0x0110);
+
+    let mut mem = [0; 0x10000];
+    hex.load(&mut mem);
+    assert_eq!(mem[0x0100], 0x21);
+    assert_eq!(mem[0x011f], 0x19);
+    assert_eq!(mem[0x0120], 0);
+}
+
+#[test]
+fn intel_hex_extended_addresses() {
+    // Extended segment address $F000 puts offset $F000 at $FF000, outside
+    // the address space; segment $0000 is fine.
+    let text = [
+        ihex(0x02, 0, &[0x00, 0x00]),
+        ihex(0x00, 0xfffc, &[0x00, 0x80, 0x00, 0x80]),
+        ihex(0x03, 0, &[0x00, 0x00, 0x80, 0x00]),
+        ihex(0x01, 0, &[]),
+    ]
+    .concat();
+    let hex = IntelHex::parse(text.as_bytes()).unwrap();
+    assert_eq!(hex.start_address(), Some(0x8000));
+    let mut mem = [0; 0x10000];
+    hex.load(&mut mem);
+    assert_eq!(mem[0xfffc..], [0x00, 0x80, 0x00, 0x80]);
+
+    let text = [
+        ihex(0x02, 0, &[0xf0, 0x00]),
+        ihex(0x00, 0xf000, &[0xea]),
+        ihex(0x01, 0, &[]),
+    ]
+    .concat();
+    assert_eq!(
+        IntelHex::parse(text.as_bytes()),
+        Err(ImageError::OutOfRange(0xff000))
+    );
+    let text = [
+        ihex(0x04, 0, &[0x00, 0x01]),
+        ihex(0x00, 0, &[0xea]),
+        ihex(0x01, 0, &[]),
+    ]
+    .concat();
+    assert_eq!(
+        IntelHex::parse(text.as_bytes()),
+        Err(ImageError::OutOfRange(0x10000))
+    );
+    // A record running past $FFFF.
+    let text = [ihex(0x00, 0xffff, &[1, 2]), ihex(0x01, 0, &[])].concat();
+    assert_eq!(
+        IntelHex::parse(text.as_bytes()),
+        Err(ImageError::OutOfRange(0xffff))
+    );
+}
+
+#[test]
+fn intel_hex_errors() {
+    let eof = ihex(0x01, 0, &[]);
+    let parse = |lines: &[&str]| IntelHex::parse(lines.concat().as_bytes()).map(|_| ());
+    assert_eq!(
+        parse(&[":10010000214601360121470136007EFE09D2190141\n", &eof]),
+        Err(ImageError::Checksum(1))
+    );
+    assert_eq!(
+        parse(&["\n", "10010000\n", &eof]),
+        Err(ImageError::Syntax(2))
+    );
+    assert_eq!(
+        parse(&[":0100000001FE\n", ":01\n"]),
+        Err(ImageError::Syntax(2))
+    );
+    assert_eq!(parse(&[":0G00000001FE\n"]), Err(ImageError::Syntax(1)));
+    assert_eq!(
+        parse(&[&ihex(0x06, 0, &[]), &eof]),
+        Err(ImageError::UnsupportedRecord(1))
+    );
+    assert_eq!(
+        parse(&[&ihex(0x04, 0, &[0]), &eof]),
+        Err(ImageError::Syntax(1))
+    );
+    assert_eq!(
+        parse(&[&ihex(0x00, 0x200, &[1, 2, 3])]),
+        Err(ImageError::MissingEnd)
+    );
+    assert_eq!(
+        parse(&[
+            &ihex(0x00, 0x200, &[1, 2, 3]),
+            &ihex(0x00, 0x1fe, &[1, 2, 3]),
+            &eof
+        ]),
+        Err(ImageError::Overlap(0x200))
+    );
+}
+
+#[test]
+fn s_records() {
+    let text = b"S00F000068656C6C6F202020202000003C\n\
+        S11F00007C0802A6900100049421FFF07C6C1B787C8C23783C6000003863000026\n\
+        S11F001C4BFFFFE5398000007D83637880010014382100107C0803A64E800020E9\n\
+        S111003848656C6C6F20776F726C642E0A0042\n\
+        S5030003F9\n\
+        S9030000FC\n";
+    let srec = SRecord::parse(text).unwrap();
+    // A start address of 0 means there is none.
+    assert_eq!(srec.start_address(), None);
+    let records: Vec<_> = srec.records().collect();
+    assert_eq!(
+        records.iter().map(|r| r.address).collect::<Vec<_>>(),
+        [0x00, 0x1c, 0x38]
+    );
+    assert_eq!(records[2].data(), b"Hello world.\n\0");
+
+    let mut mem = [0xff; 0x10000];
+    srec.load(&mut mem);
+    assert_eq!(mem[..4], [0x7c, 0x08, 0x02, 0xa6]);
+    assert_eq!(mem[0x45], 0);
+    assert_eq!(mem[0x46], 0xff);
+}
+
+#[test]
+fn s_record_address_sizes() {
+    let text = [
+        srec(2, &[0x00, 0xc0, 0x00], &[0xa9, 0x01]),
+        srec(3, &[0x00, 0x00, 0xff, 0xfe], &[0x00, 0xc0]),
+        srec(7, &[0x00, 0x00, 0xc0, 0x00], &[]),
+    ]
+    .concat();
+    let srec_image = SRecord::parse(text.as_bytes()).unwrap();
+    assert_eq!(srec_image.start_address(), Some(0xc000));
+    let mut mem = [0; 0x10000];
+    srec_image.load(&mut mem);
+    assert_eq!(mem[0xc000..0xc002], [0xa9, 0x01]);
+    assert_eq!(mem[0xfffe..], [0x00, 0xc0]);
+
+    let text = [
+        srec(2, &[0x01, 0x00, 0x00], &[0xea]),
+        srec(8, &[0, 0, 0], &[]),
+    ]
+    .concat();
+    assert_eq!(
+        SRecord::parse(text.as_bytes()),
+        Err(ImageError::OutOfRange(0x10000))
+    );
+    let text = srec(8, &[0x01, 0, 0], &[]);
+    assert_eq!(
+        SRecord::parse(text.as_bytes()),
+        Err(ImageError::OutOfRange(0x10000))
+    );
+}
+
+#[test]
+fn s_record_zero_start_address() {
+    for (kind, addr) in [(7, &[0, 0, 0, 0][..]), (8, &[0, 0, 0]), (9, &[0, 0])] {
+        let text = [srec(1, &[0x02, 0x00], &[0xea]), srec(kind, addr, &[])].concat();
+        let srec_image = SRecord::parse(text.as_bytes()).unwrap();
+        assert_eq!(srec_image.start_address(), None, "S{}", kind);
+    }
+    let text = [srec(1, &[0x02, 0x00], &[0xea]), srec(9, &[0x02, 0x00], &[])].concat();
+    let srec_image = SRecord::parse(text.as_bytes()).unwrap();
+    assert_eq!(srec_image.start_address(), Some(0x0200));
+}
+
+#[test]
+fn s_record_errors() {
+    let end = srec(9, &[0, 0], &[]);
+    let parse = |lines: &[&str]| SRecord::parse(lines.concat().as_bytes()).map(|_| ());
+    assert_eq!(parse(&["S9030000FD\n"]), Err(ImageError::Checksum(1)));
+    assert_eq!(parse(&["S9040000FC\n"]), Err(ImageError::Syntax(1)));
+    assert_eq!(parse(&[":00000001FF\n"]), Err(ImageError::Syntax(1)));
+    assert_eq!(
+        parse(&["S4030000FC\n"]),
+        Err(ImageError::UnsupportedRecord(1))
+    );
+    assert_eq!(
+        parse(&[&srec(1, &[0x02, 0x00], &[1])]),
+        Err(ImageError::MissingEnd)
+    );
+    assert_eq!(
+        parse(&[
+            &srec(1, &[0x02, 0x00], &[1, 2]),
+            &srec(1, &[0x02, 0x01], &[3]),
+            &end
+        ]),
+        Err(ImageError::Overlap(0x201))
+    );
+}
+
+#[test]
+fn raw_binaries() {
+    assert_eq!(
+        raw(0x8000, &[1, 2, 3]),
+        Ok(Segment {
+            address: 0x8000,
+            data: &[1, 2, 3],
+        })
+    );
+    assert!(raw(0, &[0; 0x10000]).is_ok());
+    assert_eq!(raw(0xfffe, &[1, 2, 3]), Err(ImageError::OutOfRange(0xfffe)));
+}
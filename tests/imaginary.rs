@@ -0,0 +1,77 @@
+use gdbstub_mos_arch::{ImaginaryRegs, MosRegId, MosRegs};
+
+#[test]
+fn addresses() {
+    let imaginary = ImaginaryRegs::new(0x02);
+    assert_eq!(imaginary.address(&MosRegId::<32>::RC(0)), Some(0x02));
+    assert_eq!(imaginary.address(&MosRegId::<32>::RC(31)), Some(0x21));
+    assert_eq!(imaginary.address(&MosRegId::<32>::RS(3)), Some(0x08));
+    assert_eq!(imaginary.address(&MosRegId::<32>::RC(32)), None);
+    assert_eq!(imaginary.address(&MosRegId::<32>::RS(16)), None);
+    assert_eq!(imaginary.address(&MosRegId::<32>::A), None);
+}
+
+#[test]
+fn load_and_store() {
+    let imaginary = ImaginaryRegs::new(0x80);
+    let mut mem = [0; 0x10000];
+    for (i, byte) in mem[0x80..0xa0].iter_mut().enumerate() {
+        *byte = i as u8;
+    }
+    let mut regs = MosRegs::<32>::default();
+    imaginary.load(&mut mem, &mut regs);
+    assert_eq!(regs.rc[5], 5);
+    assert_eq!(regs.rs(1), 0x0302);
+
+    regs.set_rs(0, 0xbeef);
+    imaginary.store(&mut mem, &regs);
+    assert_eq!(mem[0x80..0x83], [0xef, 0xbe, 0x02]);
+    assert_eq!(mem[0xa0], 0);
+}
+
+#[test]
+fn single_registers_go_to_memory() {
+    let imaginary = ImaginaryRegs::new(0x10);
+    let mut mem = [0; 0x10000];
+    let mut regs = MosRegs::<32>::default();
+
+    // Writing memory is visible through the registers.
+    mem[0x14] = 0x34;
+    mem[0x15] = 0x12;
+    let mut buf = [0; 2];
+    assert_eq!(
+        imaginary.read_reg(&mut mem, &mut regs, &MosRegId::RS(2), &mut buf),
+        Some(2)
+    );
+    assert_eq!(buf, [0x34, 0x12]);
+
+    // Writing registers is visible in memory, and only touches the register
+    // written.
+    regs.rc[0] = 0xaa;
+    assert_eq!(
+        imaginary.write_reg(&mut mem, &mut regs, &MosRegId::RC(5), &[0x56]),
+        Some(())
+    );
+    assert_eq!(mem[0x10..0x16], [0, 0, 0, 0, 0x34, 0x56]);
+    assert_eq!(
+        imaginary.write_reg(&mut mem, &mut regs, &MosRegId::A, &[0x99]),
+        Some(())
+    );
+    assert_eq!(regs.a, 0x99);
+    assert_eq!(
+        imaginary.write_reg(&mut mem, &mut regs, &MosRegId::RC(32), &[0]),
+        None
+    );
+}
+
+#[test]
+fn wraps_at_end_of_memory() {
+    let imaginary = ImaginaryRegs::new(0xffff);
+    let mut mem = [0; 0x10000];
+    let mut regs = MosRegs::<4>::default();
+    assert_eq!(
+        imaginary.write_reg(&mut mem, &mut regs, &MosRegId::RS(0), &[0x01, 0x02]),
+        Some(())
+    );
+    assert_eq!((mem[0xffff], mem[0]), (0x01, 0x02));
+}
@@ -0,0 +1,116 @@
+use gdbstub_mos_arch::loader::{INes, INesError, Mirroring, CHR_BANK_SIZE, PRG_BANK_SIZE};
+
+/// Builds a ROM whose PRG banks are filled with their bank number and end
+/// with vectors pointing to $8000 + bank.
+fn rom(header: [u8; 12], trainer: bool) -> Vec<u8> {
+    let mut bytes = b"NES\x1a".to_vec();
+    bytes.extend_from_slice(&header);
+    if trainer {
+        bytes[6] |= 0x04;
+        bytes.extend_from_slice(&[0x7e; 512]);
+    }
+    for bank in 0..header[0] {
+        let mut prg = vec![bank; PRG_BANK_SIZE];
+        for vector in prg[PRG_BANK_SIZE - 6..].chunks_mut(2) {
+            vector.copy_from_slice(&[bank, 0x80]);
+        }
+        bytes.extend_from_slice(&prg);
+    }
+    bytes.extend(std::iter::repeat_n(
+        0xcc,
+        header[1] as usize * CHR_BANK_SIZE,
+    ));
+    bytes
+}
+
+#[test]
+fn nrom() {
+    let bytes = rom([1, 1, 0x01, 0, 0, 0, 0, 0, 0, 0, 0, 0], false);
+    let rom = INes::parse(&bytes).unwrap();
+    assert_eq!(rom.mapper, 0);
+    assert_eq!(rom.mirroring, Mirroring::Vertical);
+    assert!(!rom.nes2 && !rom.battery);
+    assert_eq!(rom.prg_rom().len(), 0x4000);
+    assert_eq!(rom.chr_rom(), &[0xcc; 0x2000][..]);
+    assert_eq!(rom.trainer(), None);
+    assert_eq!(rom.reset_vector(), 0x8000);
+
+    // A single 16K bank is mirrored at $8000 and $C000.
+    let mut mem = [0xff; 0x10000];
+    rom.load(&mut mem);
+    assert_eq!(mem[0x7fff], 0xff);
+    assert_eq!(mem[0x8000], 0);
+    assert_eq!(mem[0xc000], 0);
+    assert_eq!(mem[0xfffc..0xfffe], [0x00, 0x80]);
+}
+
+#[test]
+fn banked_prg() {
+    // UxROM with 8 banks, a trainer and battery.
+    let bytes = rom([8, 0, 0x22, 0, 0, 0, 0, 0, 0, 0, 0, 0], true);
+    let rom = INes::parse(&bytes).unwrap();
+    assert_eq!(rom.mapper, 2);
+    assert_eq!(rom.mirroring, Mirroring::Horizontal);
+    assert!(rom.battery);
+    assert_eq!(rom.trainer(), Some(&[0x7e; 512][..]));
+    assert_eq!(rom.chr_rom(), &[]);
+    assert_eq!(rom.prg_banks(PRG_BANK_SIZE), 8);
+    assert_eq!(rom.prg_banks(0x2000), 16);
+    assert_eq!(rom.reset_vector(), 0x8007);
+
+    let mut mem = [0; 0x10000];
+    rom.load(&mut mem);
+    assert_eq!((mem[0x8000], mem[0xc000]), (0, 7));
+    assert_eq!(mem[0x7000], 0x7e);
+
+    // Switching the bank at $8000, with bank numbers wrapping around.
+    assert_eq!(rom.map_prg(&mut mem, 0x8000, PRG_BANK_SIZE, 3), Some(()));
+    assert_eq!((mem[0x8000], mem[0xbff0]), (3, 3));
+    assert_eq!(rom.map_prg(&mut mem, 0x8000, PRG_BANK_SIZE, 13), Some(()));
+    assert_eq!(mem[0x8000], 5);
+    assert_eq!(rom.prg_bank(0x8000, 1).unwrap()[0], 2);
+    assert_eq!(rom.map_prg(&mut mem, 0xe000, PRG_BANK_SIZE, 0), None);
+}
+
+#[test]
+fn mapper_number() {
+    // MMC3, with the upper nibble in flags 7.
+    let bytes = rom([2, 1, 0x40, 0x00, 0, 0, 0, 0, 0, 0, 0, 0], false);
+    assert_eq!(INes::parse(&bytes).unwrap().mapper, 4);
+    let bytes = rom([2, 1, 0x18, 0x40, 0, 0, 0, 0, 0, 0, 0, 0], false);
+    let rom = INes::parse(&bytes).unwrap();
+    assert_eq!((rom.mapper, rom.mirroring), (0x41, Mirroring::FourScreen));
+
+    // Garbage in the padding of old headers hides flags 7.
+    let mut bytes = rom.prg_rom().to_vec();
+    bytes.splice(0..0, *b"NES\x1a\x02\x00\x10DiskDude!");
+    assert_eq!(INes::parse(&bytes).unwrap().mapper, 1);
+}
+
+#[test]
+fn nes2() {
+    // Mapper 0x123, submapper 5, 4 PRG banks through the MSB nibble.
+    let mut bytes = rom([4, 0, 0x30, 0x28, 0x51, 0x00, 0, 0, 0, 0, 0, 0], false);
+    let rom = INes::parse(&bytes).unwrap();
+    assert!(rom.nes2);
+    assert_eq!((rom.mapper, rom.submapper), (0x123, 5));
+    assert_eq!(rom.prg_rom().len(), 4 * PRG_BANK_SIZE);
+
+    // Exponent-multiplier notation: 2^14 × 3 bytes of PRG-ROM.
+    bytes[4] = 14 << 2 | 1;
+    bytes[9] = 0x0f;
+    assert_eq!(INes::parse(&bytes).unwrap().prg_rom().len(), 3 * 0x4000);
+    bytes[4] = 20 << 2;
+    assert_eq!(INes::parse(&bytes), Err(INesError::Truncated));
+}
+
+#[test]
+fn rejects_bad_files() {
+    assert_eq!(INes::parse(b"NES"), Err(INesError::NotINes));
+    assert_eq!(INes::parse(b"NES\x1a\x01"), Err(INesError::Truncated));
+    let bytes = rom([2, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], false);
+    assert_eq!(
+        INes::parse(&bytes[..bytes.len() - 1]),
+        Err(INesError::Truncated)
+    );
+}
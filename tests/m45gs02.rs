@@ -0,0 +1,120 @@
+use gdbstub::arch::{Arch, RegId, Registers};
+use gdbstub_mos_arch::{
+    M45GS02Address, Mos45GS02Arch, Mos45GS02RegId, Mos45GS02Regs, MosRegId, MosRegs, RegAccess,
+    RegTable,
+};
+
+#[test]
+fn address_encoding() {
+    assert_eq!(
+        M45GS02Address::decode(0xd020),
+        Some(M45GS02Address::Cpu(0xd020))
+    );
+    assert_eq!(
+        M45GS02Address::decode(0x8ffd_f000),
+        Some(M45GS02Address::Flat(0xffd_f000))
+    );
+    assert_eq!(M45GS02Address::decode(0x0001_0000), None);
+    assert_eq!(M45GS02Address::decode(0x9000_0000), None);
+    assert_eq!(M45GS02Address::Flat(0x802_0000).encode(), 0x8802_0000);
+    assert_eq!(M45GS02Address::Cpu(0x2001).encode(), 0x2001);
+}
+
+#[test]
+fn table_extends_6502_layout() {
+    let xml = Mos45GS02Arch::<32>::target_description_xml().unwrap();
+    assert_eq!(xml.matches("<reg ").count(), Mos45GS02RegId::<32>::COUNT);
+    for (reg, info) in Mos45GS02RegId::<32>::iter() {
+        let (raw, _) = Mos45GS02RegId::<32>::from_raw_id(info.regnum).unwrap();
+        assert_eq!(raw, reg);
+        if let Mos45GS02RegId::Mos(mos) = reg {
+            let mos_info = mos.info().unwrap();
+            assert_eq!(
+                (info.regnum, info.offset),
+                (mos_info.regnum, mos_info.offset)
+            );
+        }
+    }
+    assert!(xml.contains("<reg name=\"ZF\""));
+    assert!(xml.contains("<reg name=\"BP\""));
+
+    // Q is made of A, X, Y and Z, which aren't contiguous in the packet.
+    let q = Mos45GS02RegId::<32>::Q.info().unwrap();
+    assert!(q.alias);
+    let parts: Vec<_> = q
+        .value_regnums
+        .unwrap()
+        .iter()
+        .map(|regnum| Mos45GS02RegId::<32>::from_raw_id(*regnum).unwrap().0)
+        .collect();
+    assert_eq!(
+        parts,
+        [
+            Mos45GS02RegId::Mos(MosRegId::A),
+            Mos45GS02RegId::Mos(MosRegId::X),
+            Mos45GS02RegId::Mos(MosRegId::Y),
+            Mos45GS02RegId::Z,
+        ]
+    );
+    assert_eq!(
+        q.offset,
+        Mos45GS02RegId::<32>::Mos(MosRegId::A)
+            .info()
+            .unwrap()
+            .offset
+    );
+    assert!(xml.contains(&format!(
+        "<reg name=\"Q\" bitsize=\"32\" offset=\"2\" regnum=\"{}\" value_regnums=\"1,2,3,{}\" />",
+        q.regnum,
+        MosRegId::<32>::COUNT
+    )));
+}
+
+#[test]
+fn quad_register_access() {
+    let mut regs: Mos45GS02Regs = Mos45GS02Regs::default();
+    let q = Mos45GS02RegId::Q;
+    assert_eq!(regs.write_reg(&q, &[0x78, 0x56, 0x34, 0x12]), Some(()));
+    assert_eq!(
+        (regs.base.a, regs.base.x, regs.base.y, regs.z),
+        (0x78, 0x56, 0x34, 0x12)
+    );
+    regs.z = 0xab;
+    let mut buf = [0; 4];
+    assert_eq!(regs.read_reg(&q, &mut buf), Some(4));
+    assert_eq!(buf, [0x78, 0x56, 0x34, 0xab]);
+    assert_eq!(regs.write_reg(&q, &[0; 2]), None);
+
+    // Q isn't transferred separately in the `g` packet.
+    let mut bytes = Vec::new();
+    regs.gdb_serialize(|b| bytes.push(b.unwrap()));
+    assert_eq!(bytes.len(), Mos45GS02RegId::<32>::PACKET_SIZE);
+}
+
+#[test]
+fn quad_and_roundtrip() {
+    let mut regs: Mos45GS02Regs = Mos45GS02Regs {
+        base: MosRegs {
+            pc: 0x2011,
+            flags: 0x24,
+            ..Default::default()
+        },
+        b: 0x16,
+        sph: 0x01,
+        ..Default::default()
+    };
+    regs.set_q(0x1234_5678);
+    assert_eq!(
+        (regs.base.a, regs.base.x, regs.base.y, regs.z),
+        (0x78, 0x56, 0x34, 0x12)
+    );
+    assert_eq!(regs.q(), 0x1234_5678);
+    assert_eq!(regs.get_reg(&Mos45GS02RegId::Mos(MosRegId::U)), Some(1));
+
+    let mut bytes = Vec::new();
+    regs.gdb_serialize(|b| bytes.push(b.unwrap()));
+    assert_eq!(bytes.len(), Mos45GS02RegId::<32>::PACKET_SIZE);
+    let mut decoded: Mos45GS02Regs = Mos45GS02Regs::default();
+    assert_eq!(decoded.gdb_deserialize(&bytes), Ok(()));
+    assert_eq!(decoded, regs);
+}
@@ -0,0 +1,108 @@
+use gdbstub_mos_arch::memory_map::{
+    memory_map_xml, MemoryKind, MemoryRegion, APPLE_II, ATARI_800XL, C64, NES, VIC20, X16,
+    X16_RAM_BANKS, X16_ROM_BANKS,
+};
+use gdbstub_mos_arch::BankedAddress;
+
+const REGIONS: [MemoryRegion; 3] = [
+    MemoryRegion::ram(0x0000, 0x8000),
+    MemoryRegion {
+        kind: MemoryKind::Flash { block_size: 0x1000 },
+        start: 0x8000,
+        length: 0x4000,
+    },
+    MemoryRegion::rom(0xc000, 0x4000),
+];
+
+fn whole(regions: &[MemoryRegion]) -> String {
+    let mut buf = vec![0; 65536];
+    let len = memory_map_xml(regions.iter().copied(), 0, &mut buf);
+    assert!(len < buf.len());
+    String::from_utf8(buf[..len].to_vec()).unwrap()
+}
+
+#[test]
+fn describes_regions() {
+    let xml = whole(&REGIONS);
+    assert!(xml.starts_with("<?xml version=\"1.0\"?>\n<!DOCTYPE memory-map"));
+    assert!(xml.contains("<memory type=\"ram\" start=\"0x0\" length=\"0x8000\"/>"));
+    assert!(xml.contains(concat!(
+        "<memory type=\"flash\" start=\"0x8000\" length=\"0x4000\">\n",
+        "    <property name=\"blocksize\">0x1000</property>\n",
+        "  </memory>",
+    )));
+    assert!(xml.contains("<memory type=\"rom\" start=\"0xc000\" length=\"0x4000\"/>"));
+    assert!(xml.ends_with("</memory-map>\n"));
+    assert_eq!(REGIONS[2].end(), 0x10000);
+}
+
+#[test]
+fn reads_in_pieces() {
+    let xml = whole(&REGIONS);
+    for chunk in [1, 7, 64, 1000] {
+        let mut read = Vec::new();
+        let mut buf = vec![0; chunk];
+        loop {
+            let len = memory_map_xml(REGIONS, read.len() as u64, &mut buf);
+            if len == 0 {
+                break;
+            }
+            read.extend_from_slice(&buf[..len]);
+        }
+        assert_eq!(read, xml.as_bytes());
+    }
+    let mut buf = [0; 16];
+    assert_eq!(memory_map_xml(REGIONS, 1 << 40, &mut buf), 0);
+    assert_eq!(memory_map_xml(REGIONS, 0, &mut []), 0);
+}
+
+#[test]
+fn profiles_cover_address_space() {
+    for profile in [C64, NES, ATARI_800XL, APPLE_II, VIC20, X16] {
+        let mut end = 0;
+        for region in profile {
+            assert_eq!(region.start as u64, end);
+            assert!(region.length > 0);
+            end = region.end();
+        }
+        assert_eq!(end, 0x10000);
+    }
+
+    let is_rom = |profile: &[MemoryRegion], addr: u32| {
+        let region = profile
+            .iter()
+            .find(|region| (region.start as u64..region.end()).contains(&(addr as u64)))
+            .unwrap();
+        region.kind == MemoryKind::Rom
+    };
+    assert!(is_rom(C64, 0xa000) && !is_rom(C64, 0xd020) && is_rom(C64, 0xfffc));
+    assert!(!is_rom(NES, 0x6000) && is_rom(NES, 0x8000));
+    assert!(is_rom(ATARI_800XL, 0xc000) && !is_rom(ATARI_800XL, 0xd400));
+    assert!(!is_rom(APPLE_II, 0xc000) && is_rom(APPLE_II, 0xd000));
+    assert!(is_rom(VIC20, 0x8000) && !is_rom(VIC20, 0x9000) && !is_rom(VIC20, 0xa000));
+    assert!(!is_rom(X16, 0xa000) && is_rom(X16, 0xc000));
+}
+
+#[test]
+fn x16_banks() {
+    let regions: Vec<_> = X16
+        .iter()
+        .copied()
+        .chain(X16_RAM_BANKS.regions())
+        .chain(X16_ROM_BANKS.regions())
+        .collect();
+    assert_eq!(regions.len(), X16.len() + 256 + 32);
+    let bank = BankedAddress::Bank {
+        bank: 255,
+        addr: 0xa000,
+    };
+    assert!(regions.contains(&MemoryRegion::ram(bank.encode(), 0x2000)));
+    let bank = BankedAddress::Bank {
+        bank: 31,
+        addr: 0xc000,
+    };
+    assert!(regions.contains(&MemoryRegion::rom(bank.encode(), 0x4000)));
+
+    let xml = whole(&regions);
+    assert!(xml.contains("<memory type=\"rom\" start=\"0x801fc000\" length=\"0x4000\"/>"));
+}
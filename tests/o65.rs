@@ -0,0 +1,205 @@
+use gdbstub::target::ext::section_offsets::Offsets;
+use gdbstub_mos_arch::loader::{O65Bases, O65Error, Relocation, O65};
+
+const BSS_ZERO: u16 = 0x0200;
+const PAGE_RELOC: u16 = 0x4000;
+
+/// A module assembled for text at $1000, data at $2000, bss at $3000 and
+/// zero page at $10:
+///
+/// ```text
+/// main: LDA #<msg
+///       LDX #>msg
+///       JSR chrout
+///       JMP main
+///       LDA zp
+///       RTS
+/// msg:  .byte "HI", 0
+///       .word main+7
+/// ```
+fn module(mode: u16) -> Vec<u8> {
+    let page = mode & PAGE_RELOC != 0;
+    let mut bytes = b"\x01\x00o65\x00".to_vec();
+    for word in [mode, 0x1000, 13, 0x2000, 5, 0x3000, 4, 0x0010, 2, 0] {
+        bytes.extend(u16::to_le_bytes(word));
+    }
+    bytes.extend(b"\x08\x00t.o65\x00\x00");
+    bytes.extend([
+        0xa9, 0x00, 0xa2, 0x20, 0x20, 0x00, 0x00, 0x4c, 0x00, 0x10, 0xa5, 0x10, 0x60,
+    ]);
+    bytes.extend(b"HI\x00\x07\x10");
+    bytes.extend(b"\x01\x00chrout\x00");
+    // Text relocations: LOW data, HIGH data, WORD chrout, WORD text, LOW
+    // zero page.
+    bytes.extend([0x02, 0x23, 0x02, 0x43]);
+    if !page {
+        bytes.push(0x00);
+    }
+    bytes.extend([0x02, 0x80, 0x00, 0x00, 0x03, 0x82, 0x03, 0x25, 0x00]);
+    // Data relocations: WORD text.
+    bytes.extend([0x04, 0x82, 0x00]);
+    bytes.extend(b"\x02\x00main\x00\x02\x00\x10msg\x00\x03\x00\x20");
+    bytes
+}
+
+#[test]
+fn parses_header() {
+    let bytes = module(BSS_ZERO);
+    let o65 = O65::parse(&bytes).unwrap();
+    assert_eq!(
+        o65.bases(),
+        O65Bases {
+            text: 0x1000,
+            data: 0x2000,
+            bss: 0x3000,
+            zero: 0x10,
+        }
+    );
+    assert_eq!(
+        (o65.text().len(), o65.data(), o65.bss_len(), o65.zero_len()),
+        (13, &b"HI\x00\x07\x10"[..], 4, 2)
+    );
+    assert!(!o65.is_65816() && !o65.is_object() && !o65.is_chained() && !o65.page_relocation());
+    assert_eq!(o65.options().collect::<Vec<_>>(), [(0, &b"t.o65\x00"[..])]);
+    assert_eq!(o65.undefined().collect::<Vec<_>>(), [&b"chrout"[..]]);
+    assert_eq!(o65.export("msg", &o65.bases()), Some(0x2000));
+}
+
+#[test]
+fn relocates() {
+    let bytes = module(BSS_ZERO);
+    let o65 = O65::parse(&bytes).unwrap();
+    let bases = o65.bases_at(0x4000);
+    assert_eq!(
+        bases,
+        O65Bases {
+            text: 0x4000,
+            data: 0x400d,
+            bss: 0x4012,
+            zero: 0x10,
+        }
+    );
+
+    let mut mem = [0xff; 0x10000];
+    let resolve = |name: &str| (name == "chrout").then_some(0xffd2);
+    assert_eq!(o65.load(&mut mem, &bases, resolve), Ok(()));
+    assert_eq!(
+        mem[0x4000..0x400d],
+        [0xa9, 0x0d, 0xa2, 0x40, 0x20, 0xd2, 0xff, 0x4c, 0x00, 0x40, 0xa5, 0x10, 0x60]
+    );
+    assert_eq!(mem[0x400d..0x4012], *b"HI\x00\x07\x40");
+    assert_eq!(mem[0x4012..0x4016], [0; 4]);
+    assert_eq!(mem[0x4016], 0xff);
+
+    assert_eq!(
+        o65.exports(&bases).collect::<Vec<_>>(),
+        [(&b"main"[..], 0x4000), (&b"msg"[..], 0x400d)]
+    );
+    let relocation = o65.relocation(&bases);
+    assert_eq!(
+        relocation,
+        Relocation {
+            text: 0x3000,
+            data: 0x200d,
+            bss: 0x1012,
+        }
+    );
+    assert!(matches!(
+        relocation.offsets(),
+        Offsets::Sections {
+            text: 0x3000,
+            data: 0x200d,
+            bss: Some(0x1012),
+        }
+    ));
+}
+
+#[test]
+fn high_byte_carry() {
+    // Moving msg from $2000 to $20F0 and then by $10 more carries into the
+    // high byte, which needs the low byte stored in the relocation entry.
+    let bytes = module(0);
+    let o65 = O65::parse(&bytes).unwrap();
+    let bases = O65Bases {
+        data: 0x2100,
+        ..o65.bases()
+    };
+    let mut mem = [0; 0x10000];
+    assert_eq!(o65.load(&mut mem, &bases, |_| Some(0)), Ok(()));
+    assert_eq!((mem[0x1001], mem[0x1003]), (0x00, 0x21));
+
+    let mut bytes = module(0);
+    let low = bytes.iter().position(|b| *b == 0x43).unwrap() + 1;
+    bytes[low] = 0xf0;
+    let o65 = O65::parse(&bytes).unwrap();
+    let bases = O65Bases {
+        data: 0x2010,
+        ..o65.bases()
+    };
+    assert_eq!(o65.load(&mut mem, &bases, |_| Some(0)), Ok(()));
+    assert_eq!(mem[0x1003], 0x21);
+}
+
+#[test]
+fn page_relocation() {
+    let bytes = module(PAGE_RELOC);
+    let o65 = O65::parse(&bytes).unwrap();
+    assert!(o65.page_relocation());
+    let bases = o65.bases_at(0x4000);
+    assert_eq!((bases.data, bases.bss), (0x4100, 0x4200));
+
+    let mut mem = [0; 0x10000];
+    assert_eq!(o65.load(&mut mem, &bases, |_| Some(0xffd2)), Ok(()));
+    assert_eq!(mem[0x4003], 0x41);
+    // bss isn't cleared without the bsszero mode bit.
+    mem[0x4200] = 0xaa;
+    assert_eq!(o65.load(&mut mem, &bases, |_| Some(0xffd2)), Ok(()));
+    assert_eq!(mem[0x4200], 0xaa);
+
+    let misaligned = O65Bases {
+        text: 0x4001,
+        ..bases
+    };
+    assert_eq!(
+        o65.load(&mut mem, &misaligned, |_| Some(0)),
+        Err(O65Error::Misaligned)
+    );
+}
+
+#[test]
+fn load_errors() {
+    let bytes = module(BSS_ZERO);
+    let o65 = O65::parse(&bytes).unwrap();
+    let mut mem = [0; 0x10000];
+    assert_eq!(
+        o65.load(&mut mem, &o65.bases(), |_| None),
+        Err(O65Error::Unresolved(0))
+    );
+    assert_eq!(
+        o65.load(&mut mem, &o65.bases_at(0xfff8), |_| Some(0)),
+        Err(O65Error::OutOfRange(0xfff8))
+    );
+    assert_eq!(mem, [0; 0x10000]);
+}
+
+#[test]
+fn rejects_bad_files() {
+    let bytes = module(0);
+    assert_eq!(O65::parse(b"\x01\x00o64\x00"), Err(O65Error::NotO65));
+    let mut wide = bytes.clone();
+    wide[7] |= 0x20;
+    assert_eq!(O65::parse(&wide), Err(O65Error::Unsupported));
+    for len in [8, 30, 40, 60, bytes.len() - 1] {
+        assert_eq!(O65::parse(&bytes[..len]), Err(O65Error::Truncated));
+    }
+
+    // A relocation past the end of the text segment.
+    let mut bad = bytes.clone();
+    let table = bytes.windows(2).position(|w| w == [0x03, 0x25]).unwrap();
+    bad[table] = 0x05;
+    assert_eq!(O65::parse(&bad), Err(O65Error::BadRelocation(table)));
+    // An unknown relocation type.
+    let mut bad = bytes.clone();
+    bad[table + 1] = 0xc2;
+    assert_eq!(O65::parse(&bad), Err(O65Error::BadRelocation(table)));
+}
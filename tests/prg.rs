@@ -0,0 +1,70 @@
+use gdbstub_mos_arch::loader::{Prg, PrgError, Segment, BASIC_START};
+
+/// `10 SYS 2061` followed by `INC $D020; JMP $080D`.
+const HELLO: [u8; 20] = [
+    0x01, 0x08, // load address
+    0x0b, 0x08, 0x0a, 0x00, 0x9e, b'2', b'0', b'6', b'1', 0x00, 0x00, 0x00, // BASIC
+    0xee, 0x20, 0xd0, 0x4c, 0x0d, 0x08,
+];
+
+#[test]
+fn loads_basic_program() {
+    let prg = Prg::parse(&HELLO).unwrap();
+    assert_eq!(prg.address(), BASIC_START);
+    assert_eq!(
+        prg.segment(),
+        Segment {
+            address: 0x0801,
+            data: &HELLO[2..],
+        }
+    );
+    assert_eq!(prg.sys_address(), Some(2061));
+    assert_eq!(prg.entry(), 0x080d);
+
+    let mut mem = [0; 0x10000];
+    prg.load(&mut mem);
+    assert_eq!(mem[0x080d..0x0813], HELLO[14..]);
+    assert_eq!(mem[0x0800], 0);
+}
+
+#[test]
+fn sys_stub_variants() {
+    let stub = |line: &[u8]| {
+        let mut bytes = vec![0x01, 0x08, 0x0b, 0x08, 0x0a, 0x00];
+        bytes.extend_from_slice(line);
+        bytes.extend_from_slice(&[0, 0, 0]);
+        Prg::parse(&bytes).unwrap().sys_address()
+    };
+    assert_eq!(stub(b"\x9e 4096"), Some(4096));
+    assert_eq!(stub(b"\x9e49152:\x80"), Some(49152));
+    assert_eq!(stub(b"\x9e(2061)"), Some(2061));
+    assert_eq!(stub(b"\x9e ( 2061 )"), Some(2061));
+    // REM, then SYS
+    assert_eq!(stub(b"\x8fX:\x9e2064"), Some(2064));
+    assert_eq!(stub(b"\x9e"), None);
+    assert_eq!(stub(b"\x9e70000"), None);
+    assert_eq!(stub(b"\x99\"HI\""), None);
+}
+
+#[test]
+fn machine_code_program() {
+    // Loaded at $C000, started with SYS 49152 by hand.
+    let prg = Prg::parse(&[0x00, 0xc0, 0x60]).unwrap();
+    assert_eq!(prg.sys_address(), None);
+    assert_eq!(prg.entry(), 0xc000);
+
+    // Up to the end of memory.
+    let prg = Prg::parse(&[0xfe, 0xff, 0x34, 0x12]).unwrap();
+    let mut mem = [0; 0x10000];
+    prg.load(&mut mem);
+    assert_eq!(mem[0xfffe..], [0x34, 0x12]);
+}
+
+#[test]
+fn rejects_bad_files() {
+    assert_eq!(Prg::parse(&[0x01]), Err(PrgError::Truncated));
+    assert_eq!(
+        Prg::parse(&[0xff, 0xff, 1, 2]),
+        Err(PrgError::OutOfRange(0xffff))
+    );
+}
@@ -0,0 +1,79 @@
+use gdbstub::arch::{Arch, RegId, Registers};
+use gdbstub_mos_arch::{MOSArch, MosRegId, MosRegs, RegAccess, RegTable};
+
+/// Checks that the target XML, `from_raw_id` and `gdb_serialize` all agree
+/// with the register table.
+fn check_table<const RC: usize>() {
+    let xml = MOSArch::<RC>::target_description_xml().unwrap();
+    assert!(xml.starts_with("<?xml"));
+    assert_eq!(xml.matches("<reg ").count(), MosRegId::<RC>::COUNT);
+
+    let mut packet_offset = 0;
+    for regnum in 0..MosRegId::<RC>::COUNT {
+        let (reg, info) = MosRegId::<RC>::lookup(regnum).unwrap();
+        assert_eq!(info.regnum, regnum);
+
+        let (raw, size) = MosRegId::<RC>::from_raw_id(regnum).unwrap();
+        assert_eq!(raw, reg);
+        assert_eq!(size.unwrap().get(), info.size());
+        assert_eq!(reg.info(), Some(info));
+
+        let name = match info.index {
+            Some(index) => format!("{}{}", info.name, index),
+            None => info.name.to_string(),
+        };
+        let line = xml
+            .lines()
+            .find(|line| line.contains(&format!("<reg name=\"{}\"", name)))
+            .unwrap_or_else(|| panic!("{} missing from target XML", name));
+        assert!(line.contains(&format!(" bitsize=\"{}\"", info.bitsize)));
+        assert!(line.contains(&format!(" offset=\"{}\"", info.offset)));
+        assert!(line.contains(&format!(" regnum=\"{}\"", regnum)));
+
+        // Non-alias registers are laid out back to back in regnum order.
+        if !info.alias {
+            assert_eq!(info.offset, packet_offset, "{}", name);
+            packet_offset += info.size();
+        }
+
+        // A value written to the register shows up in the serialized packet
+        // at the offset the table advertises.
+        let value = if info.bitsize == 1 { 1 } else { 0xa55a };
+        let value = value & ((1u32 << info.bitsize) - 1) as u16;
+        let mut regs = MosRegs::<RC>::default();
+        regs.set_reg(&reg, value).unwrap();
+        let mut bytes = Vec::new();
+        regs.gdb_serialize(|b| bytes.push(b.unwrap()));
+        assert_eq!(bytes.len(), MosRegId::<RC>::PACKET_SIZE);
+        assert_eq!(
+            bytes[info.offset..info.offset + info.size()],
+            value.to_le_bytes()[..info.size()],
+            "{}",
+            name
+        );
+    }
+    assert_eq!(packet_offset, MosRegId::<RC>::PACKET_SIZE);
+    assert!(MosRegId::<RC>::lookup(MosRegId::<RC>::COUNT).is_none());
+    assert!(MosRegId::<RC>::from_raw_id(MosRegId::<RC>::COUNT).is_none());
+}
+
+#[test]
+fn table_agrees_default() {
+    check_table::<32>();
+}
+
+#[test]
+fn table_agrees_reduced() {
+    check_table::<16>();
+}
+
+#[test]
+fn table_agrees_no_imaginary_registers() {
+    check_table::<0>();
+}
+
+#[test]
+fn table_agrees_many_imaginary_registers() {
+    // Descriptions larger than the default one still fit.
+    check_table::<128>();
+}
@@ -0,0 +1,118 @@
+use gdbstub::arch::Registers;
+use gdbstub_mos_arch::{MosRegId, MosRegs, RegAccess};
+use proptest::prelude::*;
+
+fn serialize(regs: &MosRegs) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    regs.gdb_serialize(|b| bytes.push(b.unwrap()));
+    bytes
+}
+
+fn any_regs() -> impl Strategy<Value = MosRegs> {
+    (any::<[u8; 32]>(), any::<u16>(), any::<[u8; 5]>()).prop_map(|(rc, pc, [a, x, y, s, flags])| {
+        MosRegs {
+            rc,
+            pc,
+            a,
+            x,
+            y,
+            s,
+            flags,
+        }
+    })
+}
+
+proptest! {
+    #[test]
+    fn serialize_roundtrip(regs in any_regs()) {
+        let mut decoded: MosRegs = MosRegs::default();
+        prop_assert_eq!(decoded.gdb_deserialize(&serialize(&regs)), Ok(()));
+        prop_assert_eq!(decoded, regs);
+    }
+
+    #[test]
+    fn legacy_packet_keeps_d_i_b_u(regs in any_regs(), old in any_regs()) {
+        let bytes = serialize(&regs);
+        let mut decoded = old;
+        prop_assert_eq!(decoded.gdb_deserialize(&bytes[..42]), Ok(()));
+        prop_assert_eq!(decoded.flags, (regs.flags & 0b11000011) | (old.flags & 0b00111100));
+        prop_assert_eq!(decoded.rc, regs.rc);
+    }
+
+    #[test]
+    fn deserialize_arbitrary_bytes(bytes in proptest::collection::vec(any::<u8>(), 0..64)) {
+        let mut regs: MosRegs = MosRegs::default();
+        let before = regs;
+        if regs.gdb_deserialize(&bytes).is_err() {
+            prop_assert_eq!(regs, before);
+        }
+    }
+}
+
+#[test]
+fn deserialize_rejects_bad_flags() {
+    let mut bytes = serialize(&MosRegs::default());
+    bytes[7] = 2;
+    let mut regs: MosRegs = MosRegs::default();
+    assert_eq!(regs.gdb_deserialize(&bytes), Err(()));
+}
+
+#[test]
+fn reduced_rc_count() {
+    let regs = MosRegs::<16> {
+        rc: [0x5a; 16],
+        pc: 0x1234,
+        flags: 0xff,
+        ..Default::default()
+    };
+    let mut bytes = Vec::new();
+    regs.gdb_serialize(|b| bytes.push(b.unwrap()));
+    assert_eq!(bytes.len(), 30);
+
+    let mut decoded = MosRegs::<16>::default();
+    assert_eq!(decoded.gdb_deserialize(&bytes), Ok(()));
+    assert_eq!(decoded, regs);
+    assert_eq!(decoded.gdb_deserialize(&bytes[..26]), Ok(()));
+    assert_eq!(
+        decoded.gdb_deserialize(&serialize(&MosRegs::default())),
+        Err(())
+    );
+}
+
+#[test]
+fn single_register_access() {
+    let mut regs: MosRegs = MosRegs {
+        pc: 0x1234,
+        a: 0x56,
+        flags: 0x81,
+        ..Default::default()
+    };
+    regs.set_rs(1, 0xbeef);
+    let mut buf = [0; 4];
+    assert_eq!(regs.read_reg(&MosRegId::PC, &mut buf), Some(2));
+    assert_eq!(buf[..2], [0x34, 0x12]);
+    assert_eq!(regs.read_reg(&MosRegId::A, &mut buf), Some(1));
+    assert_eq!(buf[0], 0x56);
+    assert_eq!(regs.read_reg(&MosRegId::N, &mut buf), Some(1));
+    assert_eq!(buf[0], 1);
+    assert_eq!(regs.read_reg(&MosRegId::RS(1), &mut buf), Some(2));
+    assert_eq!(buf[..2], [0xef, 0xbe]);
+    assert_eq!(regs.read_reg(&MosRegId::PC, &mut buf[..1]), None);
+    assert_eq!(regs.read_reg(&MosRegId::RC(32), &mut buf), None);
+
+    assert_eq!(regs.write_reg(&MosRegId::PC, &[0x00, 0x80]), Some(()));
+    assert_eq!(regs.pc, 0x8000);
+    assert_eq!(regs.write_reg(&MosRegId::RS(0), &[0x01, 0x02]), Some(()));
+    assert_eq!(regs.rc[..2], [0x01, 0x02]);
+    assert_eq!(regs.write_reg(&MosRegId::Z, &[1]), Some(()));
+    assert_eq!(regs.flags, 0x83);
+
+    // Writes must be exactly as wide as the register.
+    let before = regs;
+    assert_eq!(regs.write_reg(&MosRegId::PC, &[0x12]), None);
+    assert_eq!(regs.write_reg(&MosRegId::RS(0), &[0x12]), None);
+    assert_eq!(regs.write_reg(&MosRegId::A, &[0x12, 0x00]), None);
+    assert_eq!(regs.write_reg(&MosRegId::A, &[]), None);
+    assert_eq!(regs.write_reg(&MosRegId::C, &[2]), None);
+    assert_eq!(regs, before);
+}
@@ -0,0 +1,192 @@
+use gdbstub_mos_arch::{next_pcs, InstructionSet, MosRegs, IRQ_VECTOR};
+
+/// Instruction lengths by opcode, one row of 16 opcodes per line.
+const NMOS_LENGTHS: &str = "\
+    1212222212123333\
+    2212222213133333\
+    3212222212123333\
+    2212222213133333\
+    1212222212123333\
+    2212222213133333\
+    1212222212123333\
+    2212222213133333\
+    2222222212123333\
+    2212222213133333\
+    2222222212123333\
+    2212222213133333\
+    2222222212123333\
+    2212222213133333\
+    2222222212123333\
+    2212222213133333";
+
+const W65C02_LENGTHS: &str = "\
+    1221222212113333\
+    2221222213113333\
+    3221222212113333\
+    2221222213113333\
+    1221222212113333\
+    2221222213113333\
+    1221222212113333\
+    2221222213113333\
+    2221222212113333\
+    2221222213113333\
+    2221222212113333\
+    2221222213113333\
+    2221222212113333\
+    2221222213113333\
+    2221222212113333\
+    2221222213113333";
+
+fn regs(pc: u16) -> MosRegs {
+    MosRegs {
+        pc,
+        ..MosRegs::default()
+    }
+}
+
+fn step(set: InstructionSet, regs: &MosRegs, mem: &[u8; 0x10000]) -> Vec<u16> {
+    let mut pcs = next_pcs(set, regs, |addr| mem[addr as usize])
+        .as_slice()
+        .to_vec();
+    pcs.sort();
+    pcs
+}
+
+fn check_lengths(set: InstructionSet, lengths: &str, control_flow: impl Fn(u8) -> bool) {
+    for (opcode, len) in lengths.bytes().enumerate() {
+        let opcode = opcode as u8;
+        if control_flow(opcode) {
+            continue;
+        }
+        let mut mem = [0; 0x10000];
+        mem[0x1000] = opcode;
+        let next = 0x1000 + (len - b'0') as u16;
+        assert_eq!(step(set, &regs(0x1000), &mem), [next], "{:02x}", opcode);
+    }
+}
+
+#[test]
+fn nmos_fallthrough_lengths() {
+    check_lengths(InstructionSet::Nmos6502, NMOS_LENGTHS, |op| {
+        matches!(op, 0x00 | 0x20 | 0x40 | 0x60 | 0x4c | 0x6c)
+            || op & 0x1f == 0x10
+            || op & 0x1f == 0x12
+            || matches!(op, 0x02 | 0x22 | 0x42 | 0x62)
+    });
+}
+
+#[test]
+fn w65c02_fallthrough_lengths() {
+    check_lengths(InstructionSet::W65C02, W65C02_LENGTHS, |op| {
+        matches!(
+            op,
+            0x00 | 0x20 | 0x40 | 0x60 | 0x4c | 0x6c | 0x7c | 0x80 | 0xcb | 0xdb
+        ) || op & 0x1f == 0x10
+            || op & 0x0f == 0x0f
+    });
+}
+
+#[test]
+fn branches_report_both_destinations() {
+    let mut mem = [0; 0x10000];
+    // BNE -4, BEQ +$10
+    mem[0x1000..0x1004].copy_from_slice(&[0xd0, 0xfc, 0xf0, 0x10]);
+    let set = InstructionSet::Nmos6502;
+    assert_eq!(step(set, &regs(0x1000), &mem), [0x0ffe, 0x1002]);
+    assert_eq!(step(set, &regs(0x1002), &mem), [0x1004, 0x1014]);
+
+    // A branch to the next instruction has a single destination.
+    mem[0x1000..0x1002].copy_from_slice(&[0x90, 0x00]);
+    assert_eq!(step(set, &regs(0x1000), &mem), [0x1002]);
+
+    // The target wraps around the address space.
+    mem[0xfffd..0xffff].copy_from_slice(&[0x90, 0x10]);
+    assert_eq!(step(set, &regs(0xfffd), &mem), [0x000f, 0xffff]);
+}
+
+#[test]
+fn jmp_indirect_page_wrap() {
+    let mut mem = [0; 0x10000];
+    mem[0x1000..0x1003].copy_from_slice(&[0x6c, 0xff, 0x20]);
+    mem[0x20ff] = 0x34;
+    mem[0x2000] = 0x12;
+    mem[0x2100] = 0x56;
+    assert_eq!(
+        step(InstructionSet::Nmos6502, &regs(0x1000), &mem),
+        [0x1234]
+    );
+    assert_eq!(step(InstructionSet::W65C02, &regs(0x1000), &mem), [0x5634]);
+}
+
+#[test]
+fn subroutines_and_interrupts() {
+    let mut mem = [0; 0x10000];
+    let set = InstructionSet::Nmos6502;
+
+    // JSR $2345
+    mem[0x1000..0x1003].copy_from_slice(&[0x20, 0x45, 0x23]);
+    assert_eq!(step(set, &regs(0x1000), &mem), [0x2345]);
+
+    // RTS returns to the pushed address plus one.
+    mem[0x1000] = 0x60;
+    mem[0x1fe..0x200].copy_from_slice(&[0x02, 0x30]);
+    let mut r = regs(0x1000);
+    r.s = 0xfd;
+    assert_eq!(step(set, &r, &mem), [0x3003]);
+
+    // RTI pulls the flags, then the exact return address. The stack pointer
+    // wraps within page one.
+    mem[0x1000] = 0x40;
+    mem[0x1ff] = 0x24;
+    mem[0x100..0x102].copy_from_slice(&[0x78, 0x56]);
+    r.s = 0xfe;
+    assert_eq!(step(set, &r, &mem), [0x5678]);
+
+    // BRK jumps through the IRQ vector.
+    mem[0x1000] = 0x00;
+    mem[IRQ_VECTOR as usize..].copy_from_slice(&[0xcd, 0xab]);
+    assert_eq!(step(set, &regs(0x1000), &mem), [0xabcd]);
+
+    // WAI continues after the IRQ handler unless interrupts are disabled.
+    let set = InstructionSet::W65C02;
+    mem[0x1000] = 0xcb;
+    assert_eq!(step(set, &regs(0x1000), &mem), [0x1001, 0xabcd]);
+    let mut r = regs(0x1000);
+    r.flags = 0x04;
+    assert_eq!(step(set, &r, &mem), [0x1001]);
+}
+
+#[test]
+fn w65c02_control_flow() {
+    let mut mem = [0; 0x10000];
+    let set = InstructionSet::W65C02;
+
+    // BRA always branches.
+    mem[0x1000..0x1002].copy_from_slice(&[0x80, 0x10]);
+    assert_eq!(step(set, &regs(0x1000), &mem), [0x1012]);
+
+    // JMP ($2000,X)
+    mem[0x1000..0x1003].copy_from_slice(&[0x7c, 0x00, 0x20]);
+    mem[0x2004..0x2006].copy_from_slice(&[0x00, 0x40]);
+    let mut r = regs(0x1000);
+    r.x = 4;
+    assert_eq!(step(set, &r, &mem), [0x4000]);
+
+    // BBS7 $12,-3
+    mem[0x1000..0x1003].copy_from_slice(&[0xff, 0x12, 0xfd]);
+    assert_eq!(step(set, &regs(0x1000), &mem), [0x1000, 0x1003]);
+}
+
+#[test]
+fn halting_opcodes_have_no_successor() {
+    let mut mem = [0; 0x10000];
+    mem[0x1000] = 0x02;
+    assert!(next_pcs(InstructionSet::Nmos6502, &regs(0x1000), |a| mem[a as usize]).is_empty());
+    mem[0x1000] = 0xdb;
+    assert!(next_pcs(InstructionSet::W65C02, &regs(0x1000), |a| mem[a as usize]).is_empty());
+    // On the NMOS 6502, $DB is the undocumented DCP abs,Y.
+    assert_eq!(
+        step(InstructionSet::Nmos6502, &regs(0x1000), &mem),
+        [0x1003]
+    );
+}
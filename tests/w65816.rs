@@ -0,0 +1,128 @@
+use gdbstub::arch::{Arch, RegId, Registers};
+use gdbstub_mos_arch::{RegAccess, RegTable, W65816Arch, W65816RegId, W65816Regs};
+
+fn serialize(regs: &W65816Regs) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    regs.gdb_serialize(|b| bytes.push(b.unwrap()));
+    bytes
+}
+
+#[test]
+fn table_agrees() {
+    let xml = W65816Arch::<32>::target_description_xml().unwrap();
+    assert_eq!(xml.matches("<reg ").count(), W65816RegId::<32>::COUNT);
+    for (reg, info) in W65816RegId::<32>::iter() {
+        let (raw, size) = W65816RegId::<32>::from_raw_id(info.regnum).unwrap();
+        assert_eq!(raw, reg);
+        assert_eq!(size.unwrap().get(), info.size());
+        assert!(xml.contains(&format!(
+            " bitsize=\"{}\" offset=\"{}\" regnum=\"{}\"",
+            info.bitsize, info.offset, info.regnum
+        )));
+    }
+    assert_eq!(
+        serialize(&W65816Regs::default()).len(),
+        W65816RegId::<32>::PACKET_SIZE
+    );
+}
+
+#[test]
+fn pc_includes_program_bank() {
+    let mut regs: W65816Regs = W65816Regs::default();
+    regs.set_reg(&W65816RegId::PC, 0x12_3456).unwrap();
+    assert_eq!((regs.pbr, regs.pc), (0x12, 0x3456));
+    assert_eq!(regs.pc(), 0x12_3456);
+    assert_eq!(regs.get_reg(&W65816RegId::PBR), Some(0x12));
+    assert_eq!(&serialize(&regs)[..3], &[0x56, 0x34, 0x12]);
+    assert_eq!(regs.set_reg(&W65816RegId::PC, 0x100_0000), None);
+}
+
+#[test]
+fn emulation_mode_constraints() {
+    let mut regs: W65816Regs = W65816Regs {
+        x: 0x1234,
+        y: 0x5678,
+        s: 0x1fff,
+        ..Default::default()
+    };
+    regs.set_emulation(true);
+    assert_eq!((regs.x, regs.y, regs.s), (0x34, 0x78, 0x01ff));
+    assert_eq!(regs.get_reg(&W65816RegId::M), Some(1));
+    assert_eq!(regs.get_reg(&W65816RegId::XF), Some(1));
+
+    // m and x stay set when returning to native mode, until cleared.
+    regs.set_emulation(false);
+    regs.set_reg(&W65816RegId::XF, 0).unwrap();
+    regs.set_reg(&W65816RegId::X, 0xbeef).unwrap();
+    assert_eq!(regs.x, 0xbeef);
+}
+
+#[test]
+fn serialize_roundtrip() {
+    let regs: W65816Regs = W65816Regs {
+        rc: [0xa5; 32],
+        pc: 0x8000,
+        pbr: 0x7e,
+        dbr: 0x01,
+        a: 0xbeef,
+        x: 0x1234,
+        y: 0x5678,
+        s: 0x1ff0,
+        d: 0x2100,
+        flags: 0xc3,
+        emulation: false,
+    };
+    let mut decoded: W65816Regs = W65816Regs::default();
+    assert_eq!(decoded.gdb_deserialize(&serialize(&regs)), Ok(()));
+    assert_eq!(decoded, regs);
+    assert_eq!(decoded.gdb_deserialize(&[0; 4]), Err(()));
+}
+
+#[test]
+fn deserialize_widens_index_registers() {
+    // The packet clears `x`, so its 16-bit X and Y must survive even though
+    // the registers being replaced are 8 bits wide.
+    let regs: W65816Regs = W65816Regs {
+        x: 0x1234,
+        y: 0x5678,
+        flags: 0x00,
+        ..Default::default()
+    };
+    let mut decoded: W65816Regs = W65816Regs {
+        flags: 0x30,
+        ..Default::default()
+    };
+    assert_eq!(decoded.gdb_deserialize(&serialize(&regs)), Ok(()));
+    assert_eq!(decoded, regs);
+
+    // And the other way around: setting `x` truncates them.
+    let narrow: W65816Regs = W65816Regs {
+        flags: 0x10,
+        ..regs
+    };
+    assert_eq!(decoded.gdb_deserialize(&serialize(&narrow)), Ok(()));
+    assert_eq!((decoded.x, decoded.y), (0x34, 0x78));
+}
+
+#[test]
+fn width_flags_fixed_in_emulation_mode() {
+    let mut regs: W65816Regs = W65816Regs::default();
+    regs.set_emulation(true);
+    assert_eq!(regs.set_reg(&W65816RegId::XF, 0), None);
+    assert_eq!(regs.set_reg(&W65816RegId::M, 0), None);
+    assert_eq!(regs.set_reg(&W65816RegId::XF, 1), Some(()));
+    assert_eq!(regs.get_reg(&W65816RegId::XF), Some(1));
+    assert_eq!(regs.set_reg(&W65816RegId::E, 2), None);
+    assert_eq!(regs.write_reg(&W65816RegId::M, &[0]), None);
+
+    // A `G` packet can't clear them either.
+    let before = regs;
+    for flags in [0x00, 0x10, 0x20] {
+        let packet = serialize(&W65816Regs { flags, ..regs });
+        assert_eq!(regs.gdb_deserialize(&packet), Err(()));
+        assert_eq!(regs, before);
+    }
+    let packet = serialize(&W65816Regs { a: 0x42, ..regs });
+    assert_eq!(regs.gdb_deserialize(&packet), Ok(()));
+    assert_eq!(regs.a, 0x42);
+}
@@ -0,0 +1,36 @@
+use gdbstub::arch::Arch;
+use gdbstub_mos_arch::{
+    next_pcs_for, InstructionSet, MOSArch, Mos65C02Arch, MosRegId, MosRegs, MosVariant,
+};
+
+#[test]
+fn target_xml() {
+    let xml = Mos65C02Arch::<32>::target_description_xml().unwrap();
+    assert!(xml.contains("<architecture>mos:w65c02</architecture>"));
+    assert_eq!(xml.matches("<reg ").count(), MosRegId::<32>::COUNT);
+
+    // Same registers as the 6502, only the architecture differs.
+    let mos = MOSArch::<32>::target_description_xml().unwrap();
+    assert_eq!(
+        xml.replace("mos:w65c02", "mos"),
+        mos,
+        "register set differs from MOSArch"
+    );
+}
+
+#[test]
+fn instruction_set() {
+    assert_eq!(Mos65C02Arch::<32>::INSTRUCTION_SET, InstructionSet::W65C02);
+    assert_eq!(MOSArch::<32>::INSTRUCTION_SET, InstructionSet::Nmos6502);
+
+    // $80 is BRA on the 65C02 and a two-byte NOP on the NMOS 6502.
+    let regs: MosRegs = MosRegs {
+        pc: 0x1000,
+        ..Default::default()
+    };
+    let read = |addr: u16| if addr == 0x1000 { 0x80 } else { 0x10 };
+    let pcs = next_pcs_for::<Mos65C02Arch, _>(&regs, read);
+    assert_eq!(pcs.as_slice(), [0x1012]);
+    let pcs = next_pcs_for::<MOSArch, _>(&regs, read);
+    assert_eq!(pcs.as_slice(), [0x1002]);
+}
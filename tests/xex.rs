@@ -0,0 +1,121 @@
+use gdbstub_mos_arch::loader::{Segment, Xex, XexError, XexEvent, INITAD, RUNAD};
+
+/// Returns a segment with its header.
+fn segment(start: u16, data: &[u8]) -> Vec<u8> {
+    let end = start + (data.len() as u16 - 1);
+    let mut bytes = [start.to_le_bytes(), end.to_le_bytes()].concat();
+    bytes.extend_from_slice(data);
+    bytes
+}
+
+#[test]
+fn dos_load_sequence() {
+    let mut bytes = vec![0xff, 0xff];
+    // A loader with an INIT routine, then the program and its RUNAD. The
+    // $FFFF before the second segment is optional.
+    bytes.extend(segment(0x0600, &[0xa9, 0x00, 0x60]));
+    bytes.extend(segment(INITAD, &[0x00, 0x06]));
+    bytes.extend([0xff, 0xff]);
+    bytes.extend(segment(0x2000, &[0x4c, 0x00, 0x20]));
+    bytes.extend(segment(RUNAD, &[0x00, 0x20]));
+
+    let xex = Xex::parse(&bytes).unwrap();
+    let events: Vec<_> = xex.events().collect();
+    assert_eq!(
+        events,
+        [
+            XexEvent::Segment(Segment {
+                address: 0x0600,
+                data: &[0xa9, 0x00, 0x60],
+            }),
+            XexEvent::Segment(Segment {
+                address: 0x02e2,
+                data: &[0x00, 0x06],
+            }),
+            XexEvent::Init(0x0600),
+            XexEvent::Segment(Segment {
+                address: 0x2000,
+                data: &[0x4c, 0x00, 0x20],
+            }),
+            XexEvent::Segment(Segment {
+                address: 0x02e0,
+                data: &[0x00, 0x20],
+            }),
+            XexEvent::Run(0x2000),
+        ]
+    );
+    assert_eq!(xex.segments().count(), 4);
+    assert_eq!(xex.run_address(), Some(0x2000));
+
+    let mut mem = [0; 0x10000];
+    for segment in xex.segments() {
+        assert_eq!(segment.load(&mut mem), Some(()));
+    }
+    assert_eq!(mem[0x2000..0x2003], [0x4c, 0x00, 0x20]);
+}
+
+#[test]
+fn init_and_run_in_one_segment() {
+    // A segment covering both vectors runs INIT, and RUNAD is used at the
+    // end. Every INITAD write causes a call, even to the same address.
+    let mut bytes = vec![0xff, 0xff];
+    bytes.extend(segment(RUNAD, &[0x00, 0x30, 0x00, 0x40]));
+    bytes.extend(segment(INITAD, &[0x00, 0x40]));
+    let events: Vec<_> = Xex::parse(&bytes).unwrap().events().collect();
+    assert_eq!(events.len(), 5);
+    assert_eq!(events[1], XexEvent::Init(0x4000));
+    assert_eq!(events[3], XexEvent::Init(0x4000));
+    assert_eq!(events[4], XexEvent::Run(0x3000));
+}
+
+#[test]
+fn without_runad() {
+    let mut bytes = vec![0xff, 0xff];
+    bytes.extend(segment(0xfffe, &[0x34, 0x12]));
+    let xex = Xex::parse(&bytes).unwrap();
+    assert_eq!(xex.run_address(), None);
+    assert_eq!(xex.events().count(), 1);
+    let mut mem = [0; 0x10000];
+    assert_eq!(xex.segments().next().unwrap().load(&mut mem), Some(()));
+    assert_eq!(mem[0xfffe..], [0x34, 0x12]);
+}
+
+#[test]
+fn vectors_need_both_bytes() {
+    // Single bytes of INITAD and RUNAD don't make an address on their own.
+    let mut bytes = vec![0xff, 0xff];
+    bytes.extend(segment(INITAD, &[0x00, 0x06]));
+    bytes.extend(segment(INITAD + 1, &[0x07]));
+    bytes.extend(segment(RUNAD + 1, &[0x20]));
+    let events: Vec<_> = Xex::parse(&bytes).unwrap().events().collect();
+    assert_eq!(events.len(), 4);
+    assert_eq!(events[1], XexEvent::Init(0x0600));
+    assert!(!events[2..]
+        .iter()
+        .any(|event| matches!(event, XexEvent::Init(_) | XexEvent::Run(_))));
+
+    // Split over two segments, the call comes after the second one.
+    let mut bytes = vec![0xff, 0xff];
+    bytes.extend(segment(INITAD, &[0x00]));
+    bytes.extend(segment(INITAD + 1, &[0x07]));
+    bytes.extend(segment(RUNAD, &[0x00]));
+    bytes.extend(segment(RUNAD + 1, &[0x20]));
+    let events: Vec<_> = Xex::parse(&bytes).unwrap().events().collect();
+    assert_eq!(events.len(), 6);
+    assert_eq!(events[2], XexEvent::Init(0x0700));
+    assert_eq!(events[5], XexEvent::Run(0x2000));
+}
+
+#[test]
+fn rejects_bad_files() {
+    assert_eq!(Xex::parse(&segment(0x2000, &[0])), Err(XexError::NotXex));
+    assert_eq!(Xex::parse(&[0xff, 0xff]), Err(XexError::Truncated));
+    assert_eq!(
+        Xex::parse(&[0xff, 0xff, 0x00, 0x20, 0x01, 0x20, 0x00]),
+        Err(XexError::Truncated)
+    );
+    assert_eq!(
+        Xex::parse(&[0xff, 0xff, 0x00, 0x20, 0xff, 0x1f]),
+        Err(XexError::BadSegment(0x2000))
+    );
+}